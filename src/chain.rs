@@ -1,29 +1,44 @@
-use std::fmt;
+use std::{fmt, time::Duration};
 
 use bech32::Hrp;
 use cosmrs::{
-    AccountId,
-    proto::cosmos::{
-        auth::v1beta1::{BaseAccount, Bech32PrefixRequest, QueryAccountRequest},
-        base::v1beta1::DecCoin,
-        distribution::v1beta1::{QueryParamsRequest, QueryValidatorCommissionRequest},
-        vesting::v1beta1::{ContinuousVestingAccount, PeriodicVestingAccount},
+    AccountId, Any,
+    proto::{
+        Timestamp,
+        cosmos::{
+            auth::v1beta1::{BaseAccount, Bech32PrefixRequest, QueryAccountRequest},
+            authz::v1beta1::{GenericAuthorization, Grant, QueryGrantsRequest},
+            bank::v1beta1::{MsgSend, QueryAllBalancesRequest, SendAuthorization},
+            base::{
+                query::v1beta1::PageRequest,
+                v1beta1::{Coin, DecCoin},
+            },
+            distribution::v1beta1::{
+                QueryDelegatorWithdrawAddressRequest, QueryParamsRequest,
+                QueryValidatorCommissionRequest,
+            },
+            vesting::v1beta1::{ContinuousVestingAccount, PeriodicVestingAccount},
+        },
+        prost::Name,
     },
 };
-use cosmrs::{rpc::HttpClient, tendermint::chain::Id};
+use cosmrs::{crypto::LegacyAminoMultisig, rpc::HttpClient, tendermint::chain::Id};
 use eyre::{Context, ContextCompat, bail};
-use tracing::trace;
+use serde::{Deserialize, Serialize};
+use time::UtcDateTime;
+use tracing::{debug, trace, warn};
 
 use crate::{
     cosmos_sdk_extra::{
         abci_query::{
-            Bech32Prefix, QueryAccount, QueryDistributionParams, QueryValidatorCommission,
-            execute_abci_query,
+            Bech32Prefix, QueryAccount, QueryAllBalances, QueryDelegatorWithdrawAddress,
+            QueryDistributionParams, QueryGrants, QueryValidatorCommission, execute_abci_query,
         },
         ethermint::EthAccount,
         injective::EthAccount as InjectiveEthAccount,
         rpc::get_status,
     },
+    report::CoinAmount,
     wallet::WalletKeyType,
 };
 
@@ -41,6 +56,29 @@ impl fmt::Debug for Bech32Prefixes {
     }
 }
 
+impl Bech32Prefixes {
+    pub fn parse(prefix: &str, supplied_valoper_hrp: Option<&String>) -> eyre::Result<Self> {
+        let account_prefix = Hrp::parse(prefix).wrap_err("failed to parse account prefix")?;
+        let valoper_prefix = Hrp::parse(
+            supplied_valoper_hrp
+                .cloned()
+                .unwrap_or_else(|| {
+                    // Usually chains have `valoper` suffix to normal account bech32 prefix.
+                    // This assumption works quite well in the wild, but there are some chains which
+                    // don't use this scheme
+                    format!("{prefix}valoper")
+                })
+                .as_str(),
+        )
+        .wrap_err("failed to parse valoper prefix")?;
+
+        Ok(Self {
+            account_prefix,
+            valoper_prefix,
+        })
+    }
+}
+
 pub async fn get_chain_bech32_prefixes(
     client: &HttpClient,
     supplied_account_hrp: Option<&String>,
@@ -56,24 +94,7 @@ pub async fn get_chain_bech32_prefixes(
             .wrap_err("failed to query chain bech32 prefix")?
     };
 
-    let account_prefix = Hrp::parse(&prefix).wrap_err("failed to parse account prefix")?;
-    let valoper_prefix = Hrp::parse(
-        supplied_valoper_hrp
-            .cloned()
-            .unwrap_or_else(|| {
-                // Usually chains have `valoper` suffix to normal account bech32 prefix.
-                // This assumption works quite well in the wild, but there are some chains which
-                // don't use this scheme
-                format!("{prefix}valoper")
-            })
-            .as_str(),
-    )
-    .wrap_err("failed to parse valoper prefix")?;
-
-    Ok(Bech32Prefixes {
-        account_prefix,
-        valoper_prefix,
-    })
+    Bech32Prefixes::parse(&prefix, supplied_valoper_hrp)
 }
 
 pub struct ChainInfo {
@@ -175,10 +196,11 @@ pub async fn get_account_info(
         type_url => bail!("unsupported account type '{type_url}'"),
     };
 
-    let wallet_key_type = if let Some(pub_key) = base_account.pub_key.as_ref() {
-        Some(WalletKeyType::try_from(pub_key)?)
-    } else {
-        None
+    let wallet_key_type = match base_account.pub_key.as_ref() {
+        // Multisig accounts are signed for by their members, see `ResolvedAccounts::delegator_multisig`
+        Some(pub_key) if pub_key.type_url == LegacyAminoMultisig::TYPE_URL => None,
+        Some(pub_key) => Some(WalletKeyType::try_from(pub_key)?),
+        None => None,
     };
 
     Ok(Some((base_account, wallet_key_type)))
@@ -201,3 +223,376 @@ pub async fn get_validator_commission(
         .commission
         .map(|commission| commission.commission))
 }
+
+pub async fn get_balances(client: &HttpClient, account_id: &AccountId) -> eyre::Result<Vec<Coin>> {
+    let balances = execute_abci_query::<QueryAllBalances>(
+        client,
+        QueryAllBalancesRequest {
+            address: account_id.to_string(),
+            ..Default::default()
+        },
+    )
+    .await
+    .wrap_err("failed to query account balances")?;
+
+    Ok(balances.balances)
+}
+
+pub async fn get_withdraw_address(
+    client: &HttpClient,
+    delegator_address: &AccountId,
+) -> eyre::Result<String> {
+    let response = execute_abci_query::<QueryDelegatorWithdrawAddress>(
+        client,
+        QueryDelegatorWithdrawAddressRequest {
+            delegator_address: delegator_address.to_string(),
+        },
+    )
+    .await
+    .wrap_err("failed to query delegator withdraw address")?;
+
+    Ok(response.withdraw_address)
+}
+
+/// Authz grant given by granter to grantee
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GrantInfo {
+    /// Type URL of the message the grant authorizes
+    pub msg_type_url: String,
+    /// Grants without expiration never expire
+    pub expiration: Option<Timestamp>,
+    /// Remaining spend limit of a `SendAuthorization` grant. Empty for `GenericAuthorization`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub spend_limit: Vec<CoinAmount>,
+    /// Addresses a `SendAuthorization` grant allows sending to. Any address is allowed if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow_list: Vec<String>,
+}
+
+impl GrantInfo {
+    /// Decodes grant, returns `None` for authorizations this tool does not grant
+    pub fn from_grant(grant: &Grant) -> eyre::Result<Option<Self>> {
+        let Some(authorization) = &grant.authorization else {
+            return Ok(None);
+        };
+
+        // NOTE: this tool grants GenericAuthorization and SendAuthorization only
+        let grant_info = if authorization.type_url == GenericAuthorization::type_url() {
+            let authorization: GenericAuthorization = authorization
+                .to_msg()
+                .wrap_err("failed to decode authorization")?;
+            Self {
+                msg_type_url: authorization.msg,
+                expiration: grant.expiration,
+                spend_limit: Vec::new(),
+                allow_list: Vec::new(),
+            }
+        } else if authorization.type_url == SendAuthorization::type_url() {
+            let authorization: SendAuthorization = authorization
+                .to_msg()
+                .wrap_err("failed to decode authorization")?;
+            Self {
+                msg_type_url: MsgSend::type_url(),
+                expiration: grant.expiration,
+                spend_limit: authorization
+                    .spend_limit
+                    .into_iter()
+                    .map(|coin| CoinAmount {
+                        denom: coin.denom,
+                        amount: coin.amount,
+                    })
+                    .collect(),
+                allow_list: authorization.allow_list,
+            }
+        } else {
+            debug!(
+                type_url = authorization.type_url,
+                "ignoring unsupported authorization"
+            );
+            return Ok(None);
+        };
+
+        Ok(Some(grant_info))
+    }
+
+    /// Whether both grants authorize the same. Spend limit amounts are not compared, as they go
+    /// down with every send
+    pub fn same_authorization(&self, other: &GrantInfo) -> bool {
+        self.msg_type_url == other.msg_type_url
+            && self.spend_limit.is_empty() == other.spend_limit.is_empty()
+            && self.allow_list == other.allow_list
+    }
+
+    /// Whether the grant already gives what `desired` asks for: it has not expired, authorizes the
+    /// same, and expires at the same time. Sub-second precision of the expiration is ignored.
+    /// With a non-zero `tolerance`, a grant expiring at most `tolerance` before the desired
+    /// expiration, or any time after it, is good enough as well
+    pub fn fulfills(&self, desired: &GrantInfo, now: UtcDateTime, tolerance: Duration) -> bool {
+        if self.is_expired(now) || !self.same_authorization(desired) {
+            return false;
+        }
+
+        match (self.expiration, desired.expiration) {
+            (Some(expiration), Some(desired)) if !tolerance.is_zero() => {
+                expiration.seconds
+                    >= desired
+                        .seconds
+                        .saturating_sub(tolerance.as_secs().try_into().unwrap_or(i64::MAX))
+            }
+            (expiration, desired) => {
+                expiration.map(|expiration| expiration.seconds)
+                    == desired.map(|expiration| expiration.seconds)
+            }
+        }
+    }
+
+    /// Whether the grant expires before `now + window`. Already expired grants are included
+    pub fn expires_within(&self, now: UtcDateTime, window: Duration) -> bool {
+        self.expiration
+            .as_ref()
+            .is_some_and(|expiration| expiration.seconds <= (now + window).unix_timestamp())
+    }
+
+    pub fn is_expired(&self, now: UtcDateTime) -> bool {
+        self.expires_within(now, Duration::ZERO)
+    }
+
+    /// Encodes the authorization back, as it has to be given when re-issuing the grant.
+    /// `SendAuthorization` always has a spend limit, which tells it apart from `GenericAuthorization`
+    pub fn to_authorization(&self) -> eyre::Result<Any> {
+        let authorization = if self.spend_limit.is_empty() {
+            Any::from_msg(&GenericAuthorization {
+                msg: self.msg_type_url.clone(),
+            })?
+        } else {
+            Any::from_msg(&SendAuthorization {
+                spend_limit: self
+                    .spend_limit
+                    .iter()
+                    .map(|coin| Coin {
+                        denom: coin.denom.clone(),
+                        amount: coin.amount.clone(),
+                    })
+                    .collect(),
+                allow_list: self.allow_list.clone(),
+            })?
+        };
+
+        Ok(authorization)
+    }
+}
+
+/// Returns grants which expire within given window, logging a warning for each of them.
+/// Zero window disables the check
+pub fn expiring_grants(grants: &[GrantInfo], window: Duration) -> Vec<GrantInfo> {
+    if window.is_zero() {
+        return Vec::new();
+    }
+
+    let now = UtcDateTime::now();
+    let mut expiring = Vec::new();
+    for grant in grants {
+        if !grant.expires_within(now, window) {
+            continue;
+        }
+
+        if grant.is_expired(now) {
+            warn!(
+                msg_type_url = grant.msg_type_url,
+                "grant has expired, renew it with `renew-grants`"
+            );
+        } else {
+            warn!(
+                msg_type_url = grant.msg_type_url,
+                expiration = grant.expiration.as_ref().map(|e| e.seconds),
+                "grant expires soon, renew it with `renew-grants`"
+            );
+        }
+        expiring.push(grant.clone());
+    }
+
+    expiring
+}
+
+pub async fn get_grants(
+    client: &HttpClient,
+    granter: &AccountId,
+    grantee: &AccountId,
+) -> eyre::Result<Vec<GrantInfo>> {
+    let mut grants = Vec::new();
+    let mut next_key = Vec::new();
+    loop {
+        let response = execute_abci_query::<QueryGrants>(
+            client,
+            QueryGrantsRequest {
+                granter: granter.to_string(),
+                grantee: grantee.to_string(),
+                msg_type_url: String::new(),
+                pagination: Some(PageRequest {
+                    key: next_key,
+                    ..Default::default()
+                }),
+            },
+        )
+        .await
+        .wrap_err("failed to query authz grants")?;
+
+        for grant in response.grants.iter() {
+            if let Some(grant) = GrantInfo::from_grant(grant)? {
+                grants.push(grant);
+            }
+        }
+
+        // Empty `next_key` marks the last page
+        match response.pagination {
+            Some(pagination) if !pagination.next_key.is_empty() => {
+                next_key = pagination.next_key;
+            }
+            _ => break,
+        }
+    }
+
+    Ok(grants)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use cosmrs::proto::{
+        Timestamp,
+        cosmos::{
+            authz::v1beta1::{GenericAuthorization, Grant},
+            bank::v1beta1::{MsgSend, SendAuthorization},
+            distribution::v1beta1::MsgWithdrawDelegatorReward,
+        },
+        prost::Name,
+    };
+    use pretty_assertions::assert_eq;
+    use time::UtcDateTime;
+
+    use super::GrantInfo;
+    use crate::report::CoinAmount;
+
+    const REWARD_ADDRESS: &str = "osmo176pmhxsfz0cpgr705y5m4hy4kdxq2eq2atl2fy";
+
+    fn generic(msg_type_url: String, expiration: Option<i64>) -> GrantInfo {
+        GrantInfo {
+            msg_type_url,
+            expiration: expiration.map(|seconds| Timestamp { seconds, nanos: 0 }),
+            spend_limit: Vec::new(),
+            allow_list: Vec::new(),
+        }
+    }
+
+    fn send(spend_limit: &str, allow_list: &[&str]) -> GrantInfo {
+        GrantInfo {
+            msg_type_url: MsgSend::type_url(),
+            expiration: None,
+            spend_limit: vec![CoinAmount {
+                denom: "uosmo".to_string(),
+                amount: spend_limit.to_string(),
+            }],
+            allow_list: allow_list
+                .iter()
+                .map(|address| address.to_string())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_same_authorization() {
+        let reward = generic(MsgWithdrawDelegatorReward::type_url(), None);
+        assert!(reward.same_authorization(&reward));
+        // Expiration is not part of the authorization
+        assert!(reward.same_authorization(&generic(
+            MsgWithdrawDelegatorReward::type_url(),
+            Some(1_000)
+        )));
+        assert!(!reward.same_authorization(&generic(MsgSend::type_url(), None)));
+
+        // Spend limit goes down with every send
+        assert!(send("1000", &[REWARD_ADDRESS]).same_authorization(&send("5", &[REWARD_ADDRESS])));
+        assert!(!send("1000", &[REWARD_ADDRESS]).same_authorization(&send("1000", &[])));
+        // Unrestricted MsgSend is not the same as SendAuthorization
+        assert!(!send("1000", &[]).same_authorization(&generic(MsgSend::type_url(), None)));
+    }
+
+    #[test]
+    fn test_fulfills() {
+        let now = UtcDateTime::now();
+        let future = (now + Duration::from_secs(3600)).unix_timestamp();
+        let past = (now - Duration::from_secs(3600)).unix_timestamp();
+        let grant = |expiration| generic(MsgWithdrawDelegatorReward::type_url(), expiration);
+
+        let exact = Duration::ZERO;
+
+        assert!(grant(None).fulfills(&grant(None), now, exact));
+        assert!(grant(Some(future)).fulfills(&grant(Some(future)), now, exact));
+        // Sub-second precision is ignored
+        let mut precise = grant(Some(future));
+        precise.expiration.as_mut().unwrap().nanos = 500;
+        assert!(precise.fulfills(&grant(Some(future)), now, exact));
+
+        // Different expiration is re-issued
+        assert!(!grant(None).fulfills(&grant(Some(future)), now, exact));
+        assert!(!grant(Some(future)).fulfills(&grant(None), now, exact));
+        assert!(!grant(Some(future)).fulfills(&grant(Some(future + 1)), now, exact));
+        assert!(!grant(Some(future + 1)).fulfills(&grant(Some(future)), now, exact));
+
+        // Within tolerance, slightly earlier or any later expiration is kept
+        let tolerance = Duration::from_secs(60);
+        assert!(grant(Some(future)).fulfills(&grant(Some(future + 60)), now, tolerance));
+        assert!(grant(Some(future + 3600)).fulfills(&grant(Some(future)), now, tolerance));
+        assert!(!grant(Some(future)).fulfills(&grant(Some(future + 61)), now, tolerance));
+        // Grant which never expires is still re-issued when expiration is asked for
+        assert!(!grant(None).fulfills(&grant(Some(future)), now, tolerance));
+        assert!(!grant(Some(future)).fulfills(&grant(None), now, tolerance));
+
+        // Expired grant never fulfills, even if the same is asked for
+        assert!(!grant(Some(past)).fulfills(&grant(Some(past)), now, exact));
+        assert!(!grant(Some(past)).fulfills(&grant(Some(past)), now, tolerance));
+
+        assert!(
+            !grant(None).fulfills(&generic(MsgSend::type_url(), None), now, exact),
+            "different message type"
+        );
+    }
+
+    #[test]
+    fn test_to_authorization() {
+        let expiration = Some(Timestamp {
+            seconds: 1_700_000_000,
+            nanos: 0,
+        });
+
+        for grant in [
+            generic(MsgWithdrawDelegatorReward::type_url(), None),
+            generic(MsgSend::type_url(), None),
+            send("1000", &[REWARD_ADDRESS]),
+            send("1000", &[]),
+        ] {
+            let grant = GrantInfo {
+                expiration,
+                ..grant
+            };
+            let authorization = grant.to_authorization().unwrap();
+            let expected_type_url = if grant.spend_limit.is_empty() {
+                GenericAuthorization::type_url()
+            } else {
+                SendAuthorization::type_url()
+            };
+            assert_eq!(authorization.type_url, expected_type_url);
+
+            // Decodes back to the same grant
+            let decoded = GrantInfo::from_grant(&Grant {
+                authorization: Some(authorization),
+                expiration,
+            })
+            .unwrap();
+            assert_eq!(decoded, Some(grant));
+        }
+
+        assert_eq!(GrantInfo::from_grant(&Grant::default()).unwrap(), None);
+    }
+}
@@ -0,0 +1,130 @@
+use std::time::Duration;
+
+use bip32::secp256k1::elliptic_curve::rand_core::{OsRng, RngCore};
+use eyre::bail;
+use tokio::{
+    signal::unix::{SignalKind, signal},
+    sync::watch,
+    time::sleep,
+};
+use tracing::{error, info};
+
+use crate::{
+    cmd::{AccountArgs, TransactionArgs, withdraw::Withdrawer},
+    cosmos_sdk_extra::str_coin::StrCoin,
+    ledger::Ledger,
+    notify::Notifier,
+};
+
+#[allow(clippy::too_many_arguments)]
+pub async fn daemon(
+    rpc_url: &str,
+    account_hrp: Option<&String>,
+    valoper_hrp: Option<&String>,
+    account: AccountArgs,
+    transaction_args: TransactionArgs,
+    thresholds: Vec<StrCoin>,
+    interval: Duration,
+    jitter: Duration,
+    grant_expiry_warning: Duration,
+    notifier: Option<&Notifier>,
+    ledger: Option<Ledger>,
+) -> eyre::Result<()> {
+    if transaction_args.generate_only {
+        bail!("--generate-only is not supported in daemon mode");
+    }
+
+    let mut shutdown = shutdown_signal()?;
+    let mut withdrawer = Withdrawer::new(
+        rpc_url,
+        account_hrp,
+        valoper_hrp,
+        account,
+        transaction_args,
+        thresholds,
+        ledger,
+    )
+    .await?
+    .with_grant_expiry_warning(grant_expiry_warning);
+
+    loop {
+        // Withdrawal is deliberately not raced against the shutdown signal - once a transaction
+        // has been broadcast, we want to see it committed before exiting.
+        let result = withdrawer.withdraw().await;
+        match &result {
+            Ok(report) => info!(%report, "withdraw run finished"),
+            Err(err) => error!(?err, "withdraw run failed"),
+        }
+
+        if let Some(notifier) = notifier {
+            notifier.notify(None, &result).await;
+        }
+
+        if *shutdown.borrow() {
+            break;
+        }
+
+        let delay = interval + random_jitter(jitter);
+        info!(?delay, "waiting for next withdraw run");
+
+        tokio::select! {
+            _ = sleep(delay) => {}
+            _ = shutdown.changed() => break,
+        }
+    }
+
+    info!("shutting down");
+    Ok(())
+}
+
+/// Returns a receiver which flips to `true` once SIGTERM or SIGINT is received
+fn shutdown_signal() -> eyre::Result<watch::Receiver<bool>> {
+    let mut sigterm = signal(SignalKind::terminate())?;
+    let mut sigint = signal(SignalKind::interrupt())?;
+    let (tx, rx) = watch::channel(false);
+
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = sigterm.recv() => {}
+            _ = sigint.recv() => {}
+        }
+
+        info!("received shutdown signal, finishing current withdraw run");
+        let _ = tx.send(true);
+    });
+
+    Ok(rx)
+}
+
+fn random_jitter(jitter: Duration) -> Duration {
+    let jitter_ms = jitter.as_millis() as u64;
+    if jitter_ms == 0 {
+        return Duration::ZERO;
+    }
+
+    Duration::from_millis(OsRng.next_u64() % (jitter_ms + 1))
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::random_jitter;
+
+    #[test]
+    fn test_random_jitter() {
+        assert_eq!(random_jitter(Duration::ZERO), Duration::ZERO);
+        // Sub-millisecond jitter is rounded down to nothing
+        assert_eq!(random_jitter(Duration::from_micros(999)), Duration::ZERO);
+
+        let jitter = Duration::from_millis(5);
+        for _ in 0..1000 {
+            assert!(random_jitter(jitter) <= jitter);
+        }
+
+        let jitter = Duration::from_secs(60);
+        let delays: Vec<_> = (0..100).map(|_| random_jitter(jitter)).collect();
+        assert!(delays.iter().all(|delay| *delay <= jitter));
+        assert!(delays.iter().any(|delay| *delay != delays[0]));
+    }
+}
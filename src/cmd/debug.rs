@@ -1,11 +1,15 @@
+use std::path::PathBuf;
+
 use bech32::Hrp;
+use bip32::DerivationPath;
 use clap::Subcommand;
 use cosmrs::rpc::HttpClient;
 use serde_json::json;
 
 use crate::{
     chain::get_chain_bech32_prefixes,
-    wallet::{TxSigner, WalletKeyType, derive_key},
+    secret::SecretString,
+    wallet::{TxSigner, WalletKeyType, default_hd_path, derive_key, read_mnemonic_passphrase},
 };
 
 #[derive(Clone, Debug, Subcommand)]
@@ -14,7 +18,7 @@ pub enum DebugSubcommand {
     DeriveAddress {
         /// 24-word mnemonic
         #[clap(long, env = "MNEMONIC", hide_env_values = true)]
-        mnemonic: String,
+        mnemonic: SecretString,
 
         /// Wallet key type. Supported values are secp256k1, and eth_secp256k1.
         #[clap(long, default_value = "secp256k1")]
@@ -23,6 +27,23 @@ pub enum DebugSubcommand {
         /// Coin type. Defaults to 118, which is widely used by many Cosmos SDK based networks
         #[clap(long, default_value = "118")]
         coin_type: u64,
+
+        /// HD derivation path, e.g. `m/44'/118'/1'/0/0`. Overrides coin type. Defaults to `m/44'/{coin_type}'/0'/0/0`
+        #[clap(long)]
+        hd_path: Option<DerivationPath>,
+
+        /// BIP39 passphrase, also known as 25th word
+        #[clap(
+            long,
+            env = "MNEMONIC_PASSPHRASE",
+            hide_env_values = true,
+            conflicts_with = "passphrase_file"
+        )]
+        passphrase: Option<SecretString>,
+
+        /// File containing BIP39 passphrase. Trailing newline is ignored
+        #[clap(long, env = "MNEMONIC_PASSPHRASE_FILE")]
+        passphrase_file: Option<PathBuf>,
     },
 }
 
@@ -37,14 +58,25 @@ pub async fn debug(
             mnemonic,
             key_type,
             coin_type,
+            hd_path,
+            passphrase,
+            passphrase_file,
         } => {
+            let passphrase =
+                read_mnemonic_passphrase(passphrase.as_deref(), passphrase_file.as_deref())?;
+            let hd_path = match hd_path {
+                Some(hd_path) => hd_path,
+                None => default_hd_path(coin_type)?,
+            };
+
             derive_address(
                 rpc_url,
                 account_hrp,
                 valoper_hrp,
                 &mnemonic,
+                &passphrase,
                 key_type,
-                coin_type,
+                &hd_path,
             )
             .await?
         }
@@ -57,10 +89,11 @@ async fn derive_address(
     account_hrp: Option<&String>,
     valoper_hrp: Option<&String>,
     mnemonic: &str,
+    passphrase: &str,
     key_type: WalletKeyType,
-    coin_type: u64,
+    hd_path: &DerivationPath,
 ) -> eyre::Result<()> {
-    let signing_key = derive_key(mnemonic, "", coin_type)?;
+    let signing_key = derive_key(mnemonic, passphrase, hd_path)?;
     let signer = TxSigner::new(signing_key, key_type);
 
     // Ensure that we have HRPs for deriving account ids
@@ -0,0 +1,109 @@
+use eyre::ContextCompat;
+use serde::Serialize;
+use tracing::info;
+
+use crate::{
+    ledger::{Ledger, LedgerEntry, LedgerFilter, LedgerTotal, sum_coins},
+    report::{CoinAmount, OutputFormat},
+};
+
+#[derive(Debug, Serialize)]
+pub struct HistoryReport {
+    pub entries: Vec<LedgerEntry>,
+    /// Withdrawn amounts, per chain and denom
+    pub collected: Vec<LedgerTotal>,
+    /// Withdrawn delegation rewards, per chain and denom. Entries written before ledger version 3
+    /// are only counted in `collected`
+    pub rewards: Vec<LedgerTotal>,
+    /// Withdrawn validator commission, per chain and denom. Entries written before ledger version 3
+    /// are only counted in `collected`
+    pub commission: Vec<LedgerTotal>,
+    /// Paid transaction fees, per chain and denom
+    pub fees: Vec<LedgerTotal>,
+}
+
+pub fn history(
+    ledger: Option<&Ledger>,
+    filter: LedgerFilter,
+    output: OutputFormat,
+) -> eyre::Result<()> {
+    let ledger = ledger.wrap_err("--ledger-file is required for querying history")?;
+
+    let mut entries = Vec::new();
+    for entry in ledger.read()? {
+        if filter.matches(&entry)? {
+            entries.push(entry);
+        }
+    }
+
+    info!(path = ?ledger.path(), entries = entries.len(), "read ledger");
+
+    let denom = filter.denom.as_deref();
+    let total = |coins: fn(&LedgerEntry) -> &[CoinAmount]| {
+        sum_coins(
+            entries.iter().flat_map(|entry| {
+                coins(entry)
+                    .iter()
+                    .map(|coin| (entry.chain_id.as_str(), coin))
+            }),
+            denom,
+        )
+    };
+    let collected = total(|entry| &entry.collected)?;
+    let rewards = total(|entry| &entry.rewards)?;
+    let commission = total(|entry| &entry.commission)?;
+    let fees = total(|entry| &entry.fee)?;
+
+    let report = HistoryReport {
+        entries,
+        collected,
+        rewards,
+        commission,
+        fees,
+    };
+
+    match output {
+        OutputFormat::Text => print_text(&report),
+        OutputFormat::Json => output.print_report(&report, None)?,
+    }
+
+    Ok(())
+}
+
+fn print_text(report: &HistoryReport) {
+    let format_coins = |coins: &[CoinAmount]| {
+        coins
+            .iter()
+            .map(|coin| format!("{}{}", coin.amount, coin.denom))
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+
+    for entry in report.entries.iter() {
+        println!(
+            "{} {} {} {} {} collected={} rewards={} commission={} fee={} reward_address={}",
+            entry.timestamp,
+            entry.chain_id,
+            entry.height,
+            entry.tx_hash,
+            entry.kind,
+            format_coins(&entry.collected),
+            format_coins(&entry.rewards),
+            format_coins(&entry.commission),
+            format_coins(&entry.fee),
+            entry.reward_address,
+        );
+    }
+
+    for (title, totals) in [
+        ("collected", &report.collected),
+        ("rewards", &report.rewards),
+        ("commission", &report.commission),
+        ("fees", &report.fees),
+    ] {
+        println!("total {title}:");
+        for total in totals.iter() {
+            println!("  {} {}{}", total.chain_id, total.amount, total.denom);
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use std::{io::BufRead, path::PathBuf};
+
+use bech32::Hrp;
+use bip32::{
+    DerivationPath, Language, Mnemonic,
+    secp256k1::{ecdsa::VerifyingKey, elliptic_curve::rand_core::OsRng},
+};
+use clap::Subcommand;
+use eyre::{Context, bail};
+use serde::Serialize;
+use tracing::info;
+use zeroize::Zeroizing;
+
+use crate::{
+    cmd::KeystoreArgs,
+    keystore::KeystoreEntry,
+    report::OutputFormat,
+    secret::SecretString,
+    wallet::{
+        WalletKeyType, default_hd_path, derive_key, public_key_account_id, read_mnemonic_passphrase,
+    },
+};
+
+#[derive(Clone, Debug, Subcommand)]
+pub enum KeysSubcommand {
+    /// Add a key derived from a mnemonic read from stdin
+    Add {
+        /// Key name
+        name: String,
+
+        #[clap(flatten)]
+        derivation: DerivationArgs,
+
+        /// Address prefixes to print the address for
+        #[clap(long = "hrp", value_delimiter = ',', default_value = "cosmos")]
+        hrps: Vec<String>,
+    },
+    /// Generate a new key. The mnemonic is printed to stderr once, back it up
+    Generate {
+        /// Key name
+        name: String,
+
+        #[clap(flatten)]
+        derivation: DerivationArgs,
+
+        /// Address prefixes to print the address for
+        #[clap(long = "hrp", value_delimiter = ',', default_value = "cosmos")]
+        hrps: Vec<String>,
+    },
+    /// List keys in the keystore
+    List,
+    /// Show addresses of a key. Does not need the keystore passphrase
+    ShowAddress {
+        /// Key name
+        name: String,
+
+        /// Address prefixes to print the address for
+        #[clap(long = "hrp", value_delimiter = ',', default_value = "cosmos")]
+        hrps: Vec<String>,
+    },
+    /// Delete a key from the keystore
+    Delete {
+        /// Key name
+        name: String,
+
+        /// Confirm deletion. Deleted keys cannot be recovered without their mnemonic
+        #[clap(long)]
+        yes: bool,
+    },
+}
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct DerivationArgs {
+    /// Wallet key type. Supported values are secp256k1, and eth_secp256k1.
+    #[clap(long, default_value = "secp256k1")]
+    key_type: WalletKeyType,
+
+    /// Coin type. Defaults to 118, which is widely used by many Cosmos SDK based networks
+    #[clap(long, default_value = "118")]
+    coin_type: u64,
+
+    /// HD derivation path, e.g. `m/44'/118'/1'/0/0`. Overrides coin type. Defaults to `m/44'/{coin_type}'/0'/0/0`
+    #[clap(long)]
+    hd_path: Option<DerivationPath>,
+
+    /// BIP39 passphrase, also known as 25th word
+    #[clap(
+        long,
+        env = "MNEMONIC_PASSPHRASE",
+        hide_env_values = true,
+        conflicts_with = "mnemonic_passphrase_file"
+    )]
+    mnemonic_passphrase: Option<SecretString>,
+
+    /// File containing BIP39 passphrase. Trailing newline is ignored
+    #[clap(long, env = "MNEMONIC_PASSPHRASE_FILE")]
+    mnemonic_passphrase_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct KeyReport {
+    pub name: String,
+    pub key_type: WalletKeyType,
+    pub public_key: String,
+    pub created_at: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub addresses: Vec<KeyAddress>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct KeyAddress {
+    pub hrp: String,
+    pub address: String,
+}
+
+impl KeyReport {
+    fn new(entry: KeystoreEntry, hrps: &[String]) -> eyre::Result<Self> {
+        let public_key_bytes =
+            hex::decode(&entry.public_key).wrap_err("failed to decode key public key")?;
+        let public_key =
+            VerifyingKey::from_sec1_bytes(&public_key_bytes).wrap_err("invalid key public key")?;
+
+        let addresses = hrps
+            .iter()
+            .map(|hrp| {
+                let address =
+                    public_key_account_id(&public_key, entry.key_type, &Hrp::parse(hrp)?)?;
+                Ok(KeyAddress {
+                    hrp: hrp.clone(),
+                    address: address.to_string(),
+                })
+            })
+            .collect::<eyre::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            name: entry.name,
+            key_type: entry.key_type,
+            public_key: entry.public_key,
+            created_at: entry.created_at,
+            addresses,
+        })
+    }
+}
+
+pub fn keys(
+    keystore_args: &KeystoreArgs,
+    keys: KeysSubcommand,
+    output: OutputFormat,
+) -> eyre::Result<()> {
+    let keystore = keystore_args.keystore()?;
+
+    let reports = match keys {
+        KeysSubcommand::Add {
+            name,
+            derivation,
+            hrps,
+        } => {
+            let mut mnemonic = Zeroizing::new(String::new());
+            std::io::stdin()
+                .lock()
+                .read_line(&mut mnemonic)
+                .wrap_err("failed to read mnemonic from stdin")?;
+
+            let (signing_key, key_type) = derivation.derive(mnemonic.trim())?;
+            let entry = keystore.add(&name, &signing_key, key_type, keystore_args.passphrase()?)?;
+            info!(name, path = ?keystore.path(), "added key");
+
+            vec![KeyReport::new(entry, &hrps)?]
+        }
+        KeysSubcommand::Generate {
+            name,
+            derivation,
+            hrps,
+        } => {
+            let mnemonic = Mnemonic::random(OsRng, Language::English);
+
+            let (signing_key, key_type) = derivation.derive(mnemonic.phrase())?;
+            let entry = keystore.add(&name, &signing_key, key_type, keystore_args.passphrase()?)?;
+            info!(name, path = ?keystore.path(), "generated key");
+
+            // Printed to stderr so that it does not end up in the machine readable output
+            eprintln!("mnemonic: {}", mnemonic.phrase());
+
+            vec![KeyReport::new(entry, &hrps)?]
+        }
+        KeysSubcommand::List => keystore
+            .list()?
+            .into_iter()
+            .map(|entry| KeyReport::new(entry, &[]))
+            .collect::<eyre::Result<Vec<_>>>()?,
+        KeysSubcommand::ShowAddress { name, hrps } => {
+            vec![KeyReport::new(keystore.get(&name)?, &hrps)?]
+        }
+        KeysSubcommand::Delete { name, yes } => {
+            if !yes {
+                bail!("refusing to delete key '{name}' without --yes");
+            }
+
+            keystore.delete(&name)?;
+            info!(name, path = ?keystore.path(), "deleted key");
+
+            Vec::new()
+        }
+    };
+
+    match output {
+        OutputFormat::Text => print_text(&reports),
+        OutputFormat::Json => output.print_report(&reports, None)?,
+    }
+
+    Ok(())
+}
+
+impl DerivationArgs {
+    fn derive(
+        &self,
+        mnemonic: &str,
+    ) -> eyre::Result<(bip32::secp256k1::ecdsa::SigningKey, WalletKeyType)> {
+        let passphrase = read_mnemonic_passphrase(
+            self.mnemonic_passphrase.as_deref(),
+            self.mnemonic_passphrase_file.as_deref(),
+        )?;
+        let hd_path = match &self.hd_path {
+            Some(hd_path) => hd_path.clone(),
+            None => default_hd_path(self.coin_type)?,
+        };
+
+        Ok((derive_key(mnemonic, &passphrase, &hd_path)?, self.key_type))
+    }
+}
+
+fn print_text(reports: &[KeyReport]) {
+    for report in reports {
+        if report.addresses.is_empty() {
+            println!(
+                "{} {} {} {}",
+                report.name,
+                report.key_type.type_name(),
+                report.public_key,
+                report.created_at
+            );
+        }
+
+        for address in &report.addresses {
+            println!("{} {}", report.name, address.address);
+        }
+    }
+}
@@ -1,36 +1,90 @@
 use std::num::ParseIntError;
+use std::os::fd::RawFd;
+use std::path::PathBuf;
 use std::str::FromStr;
 
+use bip32::DerivationPath;
 use clap::{Args, Subcommand};
 use cosmrs::AccountId;
+use cosmrs::crypto::LegacyAminoMultisig;
 use cosmrs::proto::cosmos::auth::v1beta1::BaseAccount;
 use cosmrs::rpc::HttpClient;
-use eyre::{ContextCompat, eyre};
+use duration_string::DurationString;
+use eyre::{Context, ContextCompat, bail, eyre};
 use tracing::trace;
 
+mod daemon;
 mod debug;
+mod history;
+mod keys;
+mod plan;
+mod renew_grants;
+mod run;
 mod setup_valoper;
+mod sign;
+mod status;
+mod teardown;
 mod withdraw;
 
 use crate::chain::get_account_info;
-use crate::wallet::WalletKeyType;
-use crate::{chain::ChainInfo, cosmos_sdk_extra::str_coin::FloatStrCoin};
-
+use crate::keystore::Keystore;
+use crate::lock::AccountLock;
+use crate::secret::SecretString;
+use crate::ser::{CosmosJsonSerializable, deserialize_from_str};
+use crate::wallet::{TxSignMode, WalletKeyType, split_eip712_msgs};
+use crate::{
+    chain::{Bech32Prefixes, ChainInfo},
+    cosmos_sdk_extra::str_coin::{FloatStrCoin, StrCoin},
+};
+
+pub use self::daemon::daemon;
 pub use self::debug::{DebugSubcommand, debug};
+pub use self::history::history;
+pub use self::keys::{KeysSubcommand, keys};
+pub use self::plan::{WithdrawAction, withdraw_apply, withdraw_plan};
+pub use self::renew_grants::renew_grants;
+pub use self::run::run;
 pub use self::setup_valoper::setup_valoper;
-pub use self::withdraw::withdraw;
+pub use self::sign::{MultisignArgs, SignArgs, broadcast, multisign, sign, sign_multisig};
+pub use self::status::status;
+pub use self::teardown::teardown;
+pub use self::withdraw::{withdraw, withdraw_offline};
 
-#[derive(Debug, Default, Subcommand)]
+#[derive(Debug, Subcommand)]
 pub enum SetupValoperMethod {
-    #[default]
-    /// Determine valoper setup method based on available chain functionality
-    Auto,
+    /// Determine valoper setup method based on available chain functionality. Send options are used if the chain turns out to need `authz-send`
+    Auto(AuthzSendArgs),
 
     /// Use authz and set withdraw address
     AuthzWithdraw,
 
     /// Use authz and grant sending tokens
-    AuthzSend,
+    AuthzSend(AuthzSendArgs),
+}
+
+#[derive(Debug, Default, Args)]
+pub struct AuthzSendArgs {
+    /// Maximum amount the controller may send on behalf of the delegator, e.g. `1000000uosmo`. Can be topped up later with `renew-grants --top-up`
+    #[arg(
+        long = "spend-limit",
+        env = "COSMOS_WITHDRAWER_SEND_SPEND_LIMIT",
+        value_delimiter = ','
+    )]
+    pub spend_limit: Vec<StrCoin>,
+
+    /// Grant unrestricted `GenericAuthorization` for `MsgSend` instead of `SendAuthorization`. Only meant for chains which do not support `SendAuthorization` allow list (Cosmos SDK before v0.47)
+    #[arg(long, conflicts_with = "spend_limit")]
+    pub generic_authorization: bool,
+}
+
+impl SetupValoperMethod {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Auto(_) => "auto",
+            Self::AuthzWithdraw => "authz-withdraw",
+            Self::AuthzSend(_) => "authz-send",
+        }
+    }
 }
 
 #[derive(Debug, Args)]
@@ -45,9 +99,21 @@ pub struct AccountArgs {
         env = "COSMOS_WITHDRAWER_DELEGATOR_MNEMONIC",
         hide_env_values = true
     )]
-    pub delegator_mnemonic: Option<String>,
+    pub delegator_mnemonic: Option<SecretString>,
+
+    /// File containing delegator mnemonic phrase. Trailing newline is ignored
+    #[arg(long, env = "COSMOS_WITHDRAWER_DELEGATOR_MNEMONIC_FILE")]
+    pub delegator_mnemonic_file: Option<PathBuf>,
+
+    /// Inherited file descriptor to read delegator mnemonic phrase from, e.g. `3` when running with `3< mnemonic.txt`
+    #[arg(long, env = "COSMOS_WITHDRAWER_DELEGATOR_MNEMONIC_FD")]
+    pub delegator_mnemonic_fd: Option<RawFd>,
+
+    /// Prompt for delegator mnemonic phrase on the terminal
+    #[arg(long)]
+    pub delegator_mnemonic_prompt: bool,
 
-    /// Delegator address key type. Supported values are secp256k1, and eth_secp256k1.  Determined from the account info on chain by default.
+    /// Delegator address key type. Supported values are secp256k1, eth_secp256k1, and eth_secp256k1_injective for Injective, which uses its own public key type. Determined from the account info on chain by default.
     #[arg(long, env = "COSMOS_WITHDRAWER_DELEGATOR_ADDRESS_TYPE")]
     pub delegator_address_type: Option<WalletKeyType>,
 
@@ -59,6 +125,71 @@ pub struct AccountArgs {
     )]
     pub delegator_mnemonic_coin_type: u64,
 
+    /// Delegator HD derivation path, e.g. `m/44'/118'/1'/0/0`. Overrides coin type. Defaults to `m/44'/{coin_type}'/0'/0/0`
+    #[arg(long, env = "COSMOS_WITHDRAWER_DELEGATOR_HD_PATH")]
+    pub delegator_hd_path: Option<DerivationPath>,
+
+    /// Delegator mnemonic BIP39 passphrase, also known as 25th word
+    #[arg(
+        long,
+        env = "COSMOS_WITHDRAWER_DELEGATOR_MNEMONIC_PASSPHRASE",
+        hide_env_values = true,
+        conflicts_with = "delegator_mnemonic_passphrase_file"
+    )]
+    pub delegator_mnemonic_passphrase: Option<SecretString>,
+
+    /// File containing delegator mnemonic BIP39 passphrase. Trailing newline is ignored
+    #[arg(long, env = "COSMOS_WITHDRAWER_DELEGATOR_MNEMONIC_PASSPHRASE_FILE")]
+    pub delegator_mnemonic_passphrase_file: Option<PathBuf>,
+
+    /// Delegator Cosmos SDK keyring directory using `file` backend, e.g. `~/.osmosisd/keyring-file`. Alternative to delegator mnemonic
+    #[arg(long, env = "COSMOS_WITHDRAWER_DELEGATOR_KEYRING_DIR")]
+    pub delegator_keyring_dir: Option<PathBuf>,
+
+    /// Name of the delegator key in the keyring
+    #[arg(long, env = "COSMOS_WITHDRAWER_DELEGATOR_KEYRING_KEY_NAME")]
+    pub delegator_keyring_key_name: Option<String>,
+
+    /// Delegator keyring passphrase
+    #[arg(
+        long,
+        env = "COSMOS_WITHDRAWER_DELEGATOR_KEYRING_PASSPHRASE",
+        hide_env_values = true
+    )]
+    pub delegator_keyring_passphrase: Option<SecretString>,
+
+    /// Delegator hex encoded secp256k1 private key, e.g. from `keys export --unarmored-hex`. Alternative to delegator mnemonic
+    #[arg(
+        long,
+        env = "COSMOS_WITHDRAWER_DELEGATOR_PRIVATE_KEY",
+        hide_env_values = true
+    )]
+    pub delegator_private_key: Option<SecretString>,
+
+    /// File containing delegator ASCII armored private key, as exported by `keys export`. Alternative to delegator mnemonic
+    #[arg(long, env = "COSMOS_WITHDRAWER_DELEGATOR_ARMORED_KEY_FILE")]
+    pub delegator_armored_key_file: Option<PathBuf>,
+
+    /// Passphrase of the delegator armored private key
+    #[arg(
+        long,
+        env = "COSMOS_WITHDRAWER_DELEGATOR_ARMORED_KEY_PASSPHRASE",
+        hide_env_values = true
+    )]
+    pub delegator_armored_key_passphrase: Option<SecretString>,
+
+    /// URL of a remote signer service holding the delegator key. See README for the protocol. Alternative to delegator mnemonic
+    #[arg(long, env = "COSMOS_WITHDRAWER_DELEGATOR_REMOTE_SIGNER_URL")]
+    pub delegator_remote_signer_url: Option<String>,
+
+    /// Command signing with the delegator key, run with `sh -c`. See README for the protocol. Alternative to delegator mnemonic
+    #[arg(long, env = "COSMOS_WITHDRAWER_DELEGATOR_SIGNER_COMMAND")]
+    pub delegator_signer_command: Option<String>,
+
+    /// Name of the delegator key in the encrypted keystore, see `keys` subcommand. Alternative to delegator mnemonic
+    #[arg(long, env = "COSMOS_WITHDRAWER_DELEGATOR_KEY")]
+    pub delegator_key: Option<String>,
+
     /// Controller address, as in account which will execute transactions for withdrawal and sending
     #[arg(long, env = "COSMOS_WITHDRAWER_CONTROLLER_ADDRESS")]
     pub controller_address: AccountId,
@@ -69,7 +200,19 @@ pub struct AccountArgs {
         env = "COSMOS_WITHDRAWER_CONTROLLER_MNEMONIC",
         hide_env_values = true
     )]
-    pub controller_mnemonic: Option<String>,
+    pub controller_mnemonic: Option<SecretString>,
+
+    /// File containing controller mnemonic phrase. Trailing newline is ignored
+    #[arg(long, env = "COSMOS_WITHDRAWER_CONTROLLER_MNEMONIC_FILE")]
+    pub controller_mnemonic_file: Option<PathBuf>,
+
+    /// Inherited file descriptor to read controller mnemonic phrase from, e.g. `3` when running with `3< mnemonic.txt`
+    #[arg(long, env = "COSMOS_WITHDRAWER_CONTROLLER_MNEMONIC_FD")]
+    pub controller_mnemonic_fd: Option<RawFd>,
+
+    /// Prompt for controller mnemonic phrase on the terminal
+    #[arg(long)]
+    pub controller_mnemonic_prompt: bool,
 
     /// Controller mnemonic coin type. Defaults to 118, which is widely used by many Cosmos SDK based networks
     #[arg(
@@ -79,13 +222,111 @@ pub struct AccountArgs {
     )]
     pub controller_mnemonic_coin_type: u64,
 
-    /// Controller address key type. Supported values are secp256k1, and eth_secp256k1. Determined from the account info on chain by default.
+    /// Controller HD derivation path, e.g. `m/44'/118'/1'/0/0`. Overrides coin type. Defaults to `m/44'/{coin_type}'/0'/0/0`
+    #[arg(long, env = "COSMOS_WITHDRAWER_CONTROLLER_HD_PATH")]
+    pub controller_hd_path: Option<DerivationPath>,
+
+    /// Controller mnemonic BIP39 passphrase, also known as 25th word
+    #[arg(
+        long,
+        env = "COSMOS_WITHDRAWER_CONTROLLER_MNEMONIC_PASSPHRASE",
+        hide_env_values = true,
+        conflicts_with = "controller_mnemonic_passphrase_file"
+    )]
+    pub controller_mnemonic_passphrase: Option<SecretString>,
+
+    /// File containing controller mnemonic BIP39 passphrase. Trailing newline is ignored
+    #[arg(long, env = "COSMOS_WITHDRAWER_CONTROLLER_MNEMONIC_PASSPHRASE_FILE")]
+    pub controller_mnemonic_passphrase_file: Option<PathBuf>,
+
+    /// Controller Cosmos SDK keyring directory using `file` backend, e.g. `~/.osmosisd/keyring-file`. Alternative to controller mnemonic
+    #[arg(long, env = "COSMOS_WITHDRAWER_CONTROLLER_KEYRING_DIR")]
+    pub controller_keyring_dir: Option<PathBuf>,
+
+    /// Name of the controller key in the keyring
+    #[arg(long, env = "COSMOS_WITHDRAWER_CONTROLLER_KEYRING_KEY_NAME")]
+    pub controller_keyring_key_name: Option<String>,
+
+    /// Controller keyring passphrase
+    #[arg(
+        long,
+        env = "COSMOS_WITHDRAWER_CONTROLLER_KEYRING_PASSPHRASE",
+        hide_env_values = true
+    )]
+    pub controller_keyring_passphrase: Option<SecretString>,
+
+    /// Controller hex encoded secp256k1 private key, e.g. from `keys export --unarmored-hex`. Alternative to controller mnemonic
+    #[arg(
+        long,
+        env = "COSMOS_WITHDRAWER_CONTROLLER_PRIVATE_KEY",
+        hide_env_values = true
+    )]
+    pub controller_private_key: Option<SecretString>,
+
+    /// File containing controller ASCII armored private key, as exported by `keys export`. Alternative to controller mnemonic
+    #[arg(long, env = "COSMOS_WITHDRAWER_CONTROLLER_ARMORED_KEY_FILE")]
+    pub controller_armored_key_file: Option<PathBuf>,
+
+    /// Passphrase of the controller armored private key
+    #[arg(
+        long,
+        env = "COSMOS_WITHDRAWER_CONTROLLER_ARMORED_KEY_PASSPHRASE",
+        hide_env_values = true
+    )]
+    pub controller_armored_key_passphrase: Option<SecretString>,
+
+    /// URL of a remote signer service holding the controller key. See README for the protocol. Alternative to controller mnemonic
+    #[arg(long, env = "COSMOS_WITHDRAWER_CONTROLLER_REMOTE_SIGNER_URL")]
+    pub controller_remote_signer_url: Option<String>,
+
+    /// Command signing with the controller key, run with `sh -c`. See README for the protocol. Alternative to controller mnemonic
+    #[arg(long, env = "COSMOS_WITHDRAWER_CONTROLLER_SIGNER_COMMAND")]
+    pub controller_signer_command: Option<String>,
+
+    /// Name of the controller key in the encrypted keystore, see `keys` subcommand. Alternative to controller mnemonic
+    #[arg(long, env = "COSMOS_WITHDRAWER_CONTROLLER_KEY")]
+    pub controller_key: Option<String>,
+
+    /// Controller address key type. Supported values are secp256k1, eth_secp256k1, and eth_secp256k1_injective for Injective, which uses its own public key type. Determined from the account info on chain by default.
     #[arg(long, env = "COSMOS_WITHDRAWER_CONTROLLER_ADDRESS_TYPE")]
     pub controller_address_type: Option<WalletKeyType>,
 
     /// Reward address, as in account which will get the rewards. Optional - uses controller address if not set.
     #[arg(long, env = "COSMOS_WITHDRAWER_REWARD_ADDRESS")]
     pub reward_address: Option<AccountId>,
+
+    #[clap(flatten)]
+    pub keystore: KeystoreArgs,
+}
+
+#[derive(Clone, Debug, Default, Args)]
+pub struct KeystoreArgs {
+    /// Encrypted keystore file managed by `keys` subcommand. Defaults to `~/.cosmos-withdrawer/keystore.json`
+    #[arg(long, env = "COSMOS_WITHDRAWER_KEYSTORE")]
+    pub keystore: Option<PathBuf>,
+
+    /// Keystore passphrase
+    #[arg(
+        long,
+        env = "COSMOS_WITHDRAWER_KEYSTORE_PASSPHRASE",
+        hide_env_values = true
+    )]
+    pub keystore_passphrase: Option<SecretString>,
+}
+
+impl KeystoreArgs {
+    pub fn keystore(&self) -> eyre::Result<Keystore> {
+        match &self.keystore {
+            Some(path) => Ok(Keystore::new(path.clone())),
+            None => Ok(Keystore::new(Keystore::default_path()?)),
+        }
+    }
+
+    pub fn passphrase(&self) -> eyre::Result<&str> {
+        self.keystore_passphrase
+            .as_deref()
+            .wrap_err("keystore passphrase not set, use --keystore-passphrase")
+    }
 }
 
 impl AccountArgs {
@@ -146,13 +387,26 @@ impl AccountArgs {
                 .await?
                 .wrap_err("delegator account is not initialized")?;
 
-        let delegator_key_type = delegator_key_type
-            .wrap_err("delegation account does not have public key information")?
-            .override_type(self.delegator_address_type);
+        let delegator_multisig = delegator_account
+            .pub_key
+            .as_ref()
+            .filter(|pub_key| pub_key.type_url == LegacyAminoMultisig::TYPE_URL)
+            .map(LegacyAminoMultisig::try_from)
+            .transpose()
+            .wrap_err("failed to decode delegator multisig public key")?;
+
+        let delegator_key_type = match (delegator_key_type, &delegator_multisig) {
+            (Some(key_type), _) => key_type,
+            // Multisig members are plain secp256k1 keys
+            (None, Some(_)) => WalletKeyType::Secp256k1,
+            (None, None) => bail!("delegation account does not have public key information"),
+        }
+        .override_type(self.delegator_address_type);
 
         trace!(
             ?delegator_account,
             ?delegator_key_type,
+            ?delegator_multisig,
             "delegator account info"
         );
 
@@ -176,6 +430,7 @@ impl AccountArgs {
         Ok(ResolvedAccounts {
             delegator_account,
             delegator_key_type,
+            delegator_multisig,
             controller_account,
             controller_key_type,
         })
@@ -186,6 +441,8 @@ impl AccountArgs {
 pub struct ResolvedAccounts {
     pub delegator_account: BaseAccount,
     pub delegator_key_type: WalletKeyType,
+    /// Set if the delegator is a `LegacyAminoPubKey` multisig account
+    pub delegator_multisig: Option<LegacyAminoMultisig>,
     pub controller_account: BaseAccount,
     pub controller_key_type: WalletKeyType,
 }
@@ -218,6 +475,19 @@ pub struct TransactionArgs {
     #[arg(long, env = "COSMOS_WITHDRAWER_TX_GAS_PRICES", value_delimiter = ',')]
     pub gas_prices: Vec<FloatStrCoin>,
 
+    /// Transaction signing mode. Some older chains and external signers only support `amino-json`
+    #[arg(
+        long,
+        env = "COSMOS_WITHDRAWER_TX_SIGN_MODE",
+        value_enum,
+        default_value_t
+    )]
+    pub sign_mode: TxSignMode,
+
+    /// EVM chain id used in the EIP-712 domain. Defaults to the one in Ethermint chain id (e.g. 9001 for evmos_9001-2), or 1 on Injective
+    #[arg(long, env = "COSMOS_WITHDRAWER_TX_EIP712_CHAIN_ID")]
+    pub eip712_chain_id: Option<u64>,
+
     /// The sequence number of the signing account. Used as an escape hatch for unconventional Cosmos SDK transaction simulation
     #[arg(long)]
     pub sequence: Option<u64>,
@@ -233,6 +503,85 @@ pub struct TransactionArgs {
     /// Do everything but broadcast the transaction.
     #[arg(long)]
     pub dry_run: bool,
+
+    /// Generate the transaction without connecting to the chain. Chain id is taken from `--chain-id`, address prefixes from `--account-hrp` and `--valoper-hrp`, and the gas limit has to be set with `--gas`
+    #[arg(long, env = "COSMOS_WITHDRAWER_TX_OFFLINE", requires = "generate_only")]
+    pub offline: bool,
+
+    /// Chain id to generate the transaction for. Only used with `--offline`
+    #[arg(long, env = "COSMOS_WITHDRAWER_CHAIN_ID")]
+    pub chain_id: Option<String>,
+
+    /// Directory for account lock files, which prevent concurrent runs from using the same controller account. Defaults to `$XDG_RUNTIME_DIR/cosmos-withdrawer`, or `~/.cosmos-withdrawer/locks`
+    #[arg(long, env = "COSMOS_WITHDRAWER_LOCK_DIR")]
+    pub lock_dir: Option<PathBuf>,
+
+    /// How long to wait for another run holding the account lock to finish, e.g. `5m`. Fails immediately by default
+    #[arg(long, env = "COSMOS_WITHDRAWER_LOCK_WAIT")]
+    pub lock_wait: Option<DurationString>,
+}
+
+impl TransactionArgs {
+    /// Chain info taken from flags instead of querying the chain, for `--offline`
+    pub fn offline_chain_info(
+        &self,
+        account_hrp: Option<&String>,
+        valoper_hrp: Option<&String>,
+    ) -> eyre::Result<ChainInfo> {
+        let chain_id = self
+            .chain_id
+            .as_ref()
+            .wrap_err("--chain-id is required in offline mode")?;
+        let account_hrp = account_hrp.wrap_err("--account-hrp is required in offline mode")?;
+
+        Ok(ChainInfo {
+            id: chain_id.parse().wrap_err("invalid chain id")?,
+            // Cannot be queried offline, setup-valoper method has to be chosen explicitly instead
+            chain_supports_setting_withdrawal_address: true,
+            bech32: Bech32Prefixes::parse(account_hrp, valoper_hrp)?,
+        })
+    }
+
+    /// Locks controller account on given chain, if transactions are going to be broadcast
+    pub async fn lock_account(
+        &self,
+        chain_id: &str,
+        controller_address: &AccountId,
+    ) -> eyre::Result<Option<AccountLock>> {
+        if self.generate_only || self.dry_run {
+            return Ok(None);
+        }
+
+        AccountLock::acquire(
+            self.lock_dir.as_deref(),
+            chain_id,
+            controller_address,
+            self.lock_wait.map(Into::into),
+        )
+        .await
+        .map(Some)
+    }
+
+    /// Splits messages into transactions which can be signed with the chosen sign mode. EIP-712
+    /// typed data describes a single type of messages, so each type needs a transaction of its own.
+    /// Other sign modes keep all messages in one transaction.
+    pub fn split_msgs(
+        &self,
+        msgs: Vec<CosmosJsonSerializable>,
+    ) -> eyre::Result<Vec<Vec<CosmosJsonSerializable>>> {
+        if self.sign_mode != TxSignMode::Eip712 {
+            return Ok(vec![msgs]);
+        }
+
+        let batches = split_eip712_msgs(msgs)?;
+        if batches.len() > 1 && self.generate_only {
+            bail!(
+                "EIP-712 sign mode requires a separate transaction for each message type, but --generate-only produces a single transaction. Generate transactions for each message type separately, or use another sign mode"
+            );
+        }
+
+        Ok(batches)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -252,3 +601,5 @@ impl FromStr for GasOption {
         }
     }
 }
+
+deserialize_from_str!(GasOption);
@@ -0,0 +1,953 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use clap::Subcommand;
+use cosmrs::{
+    AccountId, proto::cosmos::distribution::v1beta1::QueryDelegationTotalRewardsRequest,
+    rpc::HttpClient,
+};
+use eyre::{Context, bail};
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use time::{OffsetDateTime, UtcDateTime, format_description::well_known::Rfc3339};
+use tracing::info;
+
+use crate::{
+    AccountArgs, TransactionArgs,
+    chain::{GrantInfo, get_grants, get_validator_commission, get_withdraw_address},
+    cmd::{
+        ResolvedAccounts,
+        sign::{ParsedTx, parse_unsigned_tx, read_json},
+        withdraw::{
+            CollectedCoins, Selection, Withdrawer, broadcast_tx, controller_signer,
+            record_in_ledger,
+        },
+    },
+    cosmos_sdk_extra::{
+        abci_query::{QueryDelegationTotalRewards, execute_abci_query},
+        dec::dec_to_base_units,
+        simulate::simulate_tx,
+        str_coin::StrCoin,
+        tx::generate_unsigned_tx_json,
+    },
+    ledger::Ledger,
+    metrics::{LAST_WITHDRAWAL_TIMESTAMP, METRICS},
+    report::{
+        CoinAmount, RewardDecision, TxKind, TxReport, ValidatorReport, WithdrawReport,
+        WithdrawStatus,
+    },
+    ser::MsgExecCustom,
+    wallet::{
+        SigningAccountType, TxSignMode, UnsignedTx, construct_transaction_body, prepare_transaction,
+    },
+};
+
+/// Version of the plan file format, bumped on incompatible changes. Version 2 records amounts in
+/// base units instead of Cosmos SDK Dec
+const PLAN_VERSION: u32 = 2;
+
+/// Fraction of a percent amount tolerance is precise to
+const TOLERANCE_SCALE: u32 = 1_000_000;
+
+#[derive(Debug, Subcommand)]
+pub enum WithdrawAction {
+    /// Write a plan of the withdrawal for review, without signing anything
+    Plan {
+        /// File to write the plan to. Printed to stdout by default
+        #[arg(long)]
+        plan_file: Option<PathBuf>,
+
+        /// How much withdrawn amounts may change between planning and applying, in percent
+        #[arg(
+            long,
+            env = "COSMOS_WITHDRAWER_PLAN_AMOUNT_TOLERANCE",
+            default_value_t = 10.0
+        )]
+        amount_tolerance: f64,
+    },
+    /// Sign & broadcast a plan written by `withdraw plan`, unless the chain state drifted from it
+    Apply {
+        /// Plan file written by `withdraw plan`
+        plan: PathBuf,
+    },
+}
+
+impl WithdrawAction {
+    /// Rejects withdraw options which the action would ignore. They are given to `withdraw` before
+    /// the action, so clap cannot tell that they conflict
+    pub fn check_withdraw_args(
+        &self,
+        thresholds: &[StrCoin],
+        validators: &[AccountId],
+        commission: bool,
+    ) -> eyre::Result<()> {
+        match self {
+            Self::Plan { .. } if commission => {
+                bail!(
+                    "--commission cannot be used with plan, commission is detected automatically when planning"
+                )
+            }
+            Self::Apply { .. } if !thresholds.is_empty() || !validators.is_empty() => {
+                bail!(
+                    "--threshold and --validator cannot be used with apply, the plan already selected what to withdraw"
+                )
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Withdrawal planned by `withdraw plan`, to be reviewed before `withdraw apply` signs it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WithdrawPlan {
+    pub version: u32,
+    pub created_at: String,
+    pub chain_id: String,
+    pub account_prefix: String,
+    pub valoper_prefix: String,
+    pub delegator_address: String,
+    pub controller_address: String,
+    /// Withdraw address of the delegator at the time of planning
+    pub withdraw_address: String,
+    /// Grants given by the delegator to the controller at the time of planning
+    pub grants: Vec<GrantInfo>,
+    pub validators: Vec<ValidatorReport>,
+    /// Expected amounts to be withdrawn, per denom
+    pub collected: Vec<CoinAmount>,
+    /// How much collected amounts may change until the plan is applied, in percent
+    pub amount_tolerance_percent: f64,
+    pub signer: PlannedSigner,
+    /// Transaction to be signed, in the same format as with `--generate-only`
+    pub unsigned_tx: Value,
+    /// Hex encoded SHA256 of the bytes the controller key is going to sign
+    pub sign_bytes_hash: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlannedSigner {
+    pub account_number: u64,
+    pub sequence: u64,
+    pub sign_mode: TxSignMode,
+}
+
+impl WithdrawPlan {
+    /// Writes the plan into given file, or to stdout if no file is given
+    pub fn write(&self, output: Option<&Path>) -> eyre::Result<()> {
+        let plan = serde_json::to_string_pretty(self)?;
+        match output {
+            Some(path) => {
+                std::fs::write(path, plan + "\n")
+                    .wrap_err_with(|| format!("failed to write plan '{}'", path.display()))?;
+                info!(path = %path.display(), "plan written");
+            }
+            None => println!("{plan}"),
+        }
+
+        Ok(())
+    }
+}
+
+/// Selects rewards & commissions to withdraw and builds the withdraw transaction, without signing it.
+/// The controller key is only loaded to derive the public key, which is part of the sign bytes.
+#[allow(clippy::too_many_arguments)]
+pub async fn withdraw_plan(
+    rpc_url: &str,
+    account_hrp: Option<&String>,
+    valoper_hrp: Option<&String>,
+    account: AccountArgs,
+    transaction_args: TransactionArgs,
+    thresholds: Vec<StrCoin>,
+    validators: Vec<AccountId>,
+    amount_tolerance: f64,
+) -> eyre::Result<WithdrawPlan> {
+    if transaction_args.generate_only {
+        bail!("plan needs the controller public key, --generate-only is not supported");
+    }
+    if transaction_args.sign_mode == TxSignMode::Eip712 {
+        // Rewards & commission would need separate transactions, while a plan covers exactly one
+        bail!("plans are not supported in EIP-712 sign mode");
+    }
+    if amount_tolerance.is_nan() || amount_tolerance < 0.0 {
+        bail!("amount tolerance must not be negative");
+    }
+
+    let mut withdrawer = Withdrawer::new(
+        rpc_url,
+        account_hrp,
+        valoper_hrp,
+        account,
+        transaction_args,
+        thresholds,
+        None,
+    )
+    .await?
+    .with_validators(validators);
+    if !withdrawer
+        .chain_info
+        .chain_supports_setting_withdrawal_address
+    {
+        // Rewards are sent to the reward address in a follow-up transaction, which cannot be planned
+        // before the withdrawal is committed
+        bail!("plans are not supported on chains which do not support setting withdraw address");
+    }
+
+    let Selection {
+        report,
+        authz_msgs,
+        controller_account,
+        controller_key_type,
+        ..
+    } = withdrawer.select().await?;
+    if authz_msgs.is_empty() {
+        bail!("nothing to withdraw yet, no plan was written");
+    }
+
+    let Withdrawer {
+        ref client,
+        ref chain_info,
+        ref gas_info,
+        ref account,
+        ref transaction_args,
+        signer: ref mut cached_signer,
+        ..
+    } = withdrawer;
+
+    let withdraw_address = get_withdraw_address(client, &account.delegator_address).await?;
+    let grants = get_grants(
+        client,
+        &account.delegator_address,
+        &account.controller_address,
+    )
+    .await?;
+
+    let msgs = vec![
+        MsgExecCustom {
+            grantee: account.controller_address.to_string(),
+            msgs: authz_msgs,
+        }
+        .into(),
+    ];
+
+    let account_number = transaction_args
+        .account_number
+        .unwrap_or(controller_account.account_number);
+    let sequence = transaction_args
+        .sequence
+        .unwrap_or(controller_account.sequence);
+    let signer = controller_signer(
+        cached_signer,
+        account,
+        chain_info,
+        transaction_args,
+        SigningAccountType::Controller {
+            key_type: controller_key_type,
+            account_number,
+            sequence,
+        },
+    )
+    .await?;
+
+    let fee = if let Some(fee) = gas_info.get_fee() {
+        fee
+    } else {
+        simulate_tx(
+            client,
+            chain_info,
+            gas_info,
+            signer,
+            construct_transaction_body(&transaction_args.memo, &msgs)?,
+        )
+        .await?
+    };
+
+    let unsigned_tx = prepare_transaction(
+        chain_info,
+        signer,
+        fee.clone(),
+        construct_transaction_body(&transaction_args.memo, &msgs)?,
+    )?;
+
+    Ok(WithdrawPlan {
+        version: PLAN_VERSION,
+        created_at: OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .wrap_err("failed to format timestamp")?,
+        chain_id: report.chain_id,
+        account_prefix: chain_info.bech32.account_prefix.to_string(),
+        valoper_prefix: chain_info.bech32.valoper_prefix.to_string(),
+        delegator_address: account.delegator_address.to_string(),
+        controller_address: account.controller_address.to_string(),
+        withdraw_address,
+        grants,
+        validators: report.validators,
+        collected: report.collected,
+        amount_tolerance_percent: amount_tolerance,
+        signer: PlannedSigner {
+            account_number,
+            sequence,
+            sign_mode: transaction_args.sign_mode,
+        },
+        unsigned_tx: generate_unsigned_tx_json(
+            msgs,
+            &transaction_args.memo,
+            fee.gas_limit,
+            fee.amount,
+        ),
+        sign_bytes_hash: sign_bytes_hash(&unsigned_tx),
+    })
+}
+
+/// Signs & broadcasts planned withdraw transaction. Refuses to proceed if the chain state drifted
+/// from the plan, or if the transaction would differ from the planned one.
+pub async fn withdraw_apply(
+    rpc_url: &str,
+    account_hrp: Option<&String>,
+    valoper_hrp: Option<&String>,
+    account: AccountArgs,
+    transaction_args: TransactionArgs,
+    ledger: Option<Ledger>,
+    plan_path: &Path,
+) -> eyre::Result<WithdrawReport> {
+    let plan: WithdrawPlan = serde_json::from_value(read_json(plan_path)?)
+        .wrap_err_with(|| format!("failed to parse plan '{}'", plan_path.display()))?;
+    if plan.version != PLAN_VERSION {
+        bail!(
+            "unsupported plan version {}, expected {PLAN_VERSION}",
+            plan.version
+        );
+    }
+    if transaction_args.generate_only {
+        bail!("--generate-only is not supported when applying a plan");
+    }
+
+    let mut withdrawer = Withdrawer::new(
+        rpc_url,
+        account_hrp,
+        valoper_hrp,
+        account,
+        transaction_args,
+        Vec::new(),
+        ledger,
+    )
+    .await?;
+    let Withdrawer {
+        ref client,
+        ref chain_info,
+        ref account,
+        ref transaction_args,
+        ref ledger,
+        signer: ref mut cached_signer,
+        ..
+    } = withdrawer;
+
+    let chain_id = chain_info.id.as_str();
+    if plan.chain_id != chain_id {
+        bail!(
+            "plan was made for chain '{}', but the node is on '{chain_id}'",
+            plan.chain_id
+        );
+    }
+    for (kind, planned, configured) in [
+        (
+            "delegator",
+            &plan.delegator_address,
+            &account.delegator_address,
+        ),
+        (
+            "controller",
+            &plan.controller_address,
+            &account.controller_address,
+        ),
+    ] {
+        if *planned != configured.to_string() {
+            bail!("plan was made for {kind} address '{planned}', but '{configured}' is configured");
+        }
+    }
+    if plan.signer.sign_mode != transaction_args.sign_mode {
+        bail!(
+            "plan was made with sign mode {:?}, but {:?} is configured",
+            plan.signer.sign_mode,
+            transaction_args.sign_mode
+        );
+    }
+
+    // Held until the withdrawal is done, so that concurrent runs do not reuse the same sequence
+    let _lock = transaction_args
+        .lock_account(chain_id, &account.controller_address)
+        .await?;
+
+    let ResolvedAccounts {
+        controller_account,
+        controller_key_type,
+        ..
+    } = account.get_account_details(client, chain_info).await?;
+
+    let withdraw_address = get_withdraw_address(client, &account.delegator_address).await?;
+    if withdraw_address != plan.withdraw_address {
+        bail!(
+            "withdraw address changed from '{}' to '{withdraw_address}' since planning",
+            plan.withdraw_address
+        );
+    }
+
+    let grants = get_grants(
+        client,
+        &account.delegator_address,
+        &account.controller_address,
+    )
+    .await?;
+    check_grants(&plan.grants, &grants, UtcDateTime::now())?;
+
+    let current = current_amounts(client, &account.delegator_address, &plan.validators).await?;
+    let collected = current.total();
+    check_amount_drift(&plan.collected, &collected, plan.amount_tolerance_percent)?;
+
+    let ParsedTx { msgs, memo, fee } =
+        parse_unsigned_tx(&plan.unsigned_tx).wrap_err("failed to parse planned transaction")?;
+
+    let account_number = transaction_args
+        .account_number
+        .unwrap_or(controller_account.account_number);
+    let sequence = transaction_args
+        .sequence
+        .unwrap_or(controller_account.sequence);
+    let signer = controller_signer(
+        cached_signer,
+        account,
+        chain_info,
+        transaction_args,
+        SigningAccountType::Controller {
+            key_type: controller_key_type,
+            account_number,
+            sequence,
+        },
+    )
+    .await?;
+
+    let unsigned_tx = prepare_transaction(
+        chain_info,
+        signer,
+        fee.clone(),
+        construct_transaction_body(&memo, &msgs)?,
+    )?;
+    if sign_bytes_hash(&unsigned_tx) != plan.sign_bytes_hash {
+        bail!(
+            "transaction does not match the plan, either the plan was modified or the controller account changed since planning (planned account number {} sequence {}, now {account_number} {sequence})",
+            plan.signer.account_number,
+            plan.signer.sequence
+        );
+    }
+
+    info!(
+        validators = plan.validators.len(),
+        "plan is up to date, withdrawing"
+    );
+
+    let mut report = WithdrawReport {
+        chain_id: chain_id.to_string(),
+        validators: plan.validators,
+        collected,
+        ..Default::default()
+    };
+
+    let signed_tx = unsigned_tx
+        .sign(signer)
+        .await
+        .wrap_err("failed to sign withdraw transaction")?;
+
+    if transaction_args.dry_run {
+        info!("dry run was requested, nothing was done");
+        report.status = WithdrawStatus::DryRun;
+        return Ok(report);
+    }
+
+    let reward_address = account
+        .reward_address
+        .as_ref()
+        .unwrap_or(&account.controller_address);
+
+    let tx_response = broadcast_tx(client, chain_info, signed_tx).await?;
+    info!(tx_hash = ?tx_response.hash, "transaction committed to chain, withdrawal done");
+    let tx_report = TxReport::new(TxKind::Withdraw, &fee, &tx_response);
+    record_in_ledger(
+        ledger.as_ref(),
+        chain_id,
+        &tx_report,
+        &msgs,
+        &current,
+        reward_address,
+    );
+    report.transactions.push(tx_report);
+
+    METRICS.set(
+        &LAST_WITHDRAWAL_TIMESTAMP,
+        &[("chain_id", chain_id)],
+        UtcDateTime::now().unix_timestamp() as f64,
+    );
+
+    report.status = WithdrawStatus::Withdrawn;
+    Ok(report)
+}
+
+fn sign_bytes_hash(unsigned_tx: &UnsignedTx) -> String {
+    hex::encode(Sha256::digest(unsigned_tx.sign_bytes()))
+}
+
+/// Fails if grants changed since planning, or if any of them has expired by now
+fn check_grants(
+    planned: &[GrantInfo],
+    current: &[GrantInfo],
+    now: UtcDateTime,
+) -> eyre::Result<()> {
+    let sorted = |grants: &[GrantInfo]| {
+        let mut grants = grants.to_vec();
+        grants.sort_by(|a, b| a.msg_type_url.cmp(&b.msg_type_url));
+        grants
+    };
+
+    if sorted(planned) != sorted(current) {
+        let type_urls = |grants: &[GrantInfo]| {
+            grants
+                .iter()
+                .map(|grant| grant.msg_type_url.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        bail!(
+            "grants from delegator to controller changed since planning, planned [{}], now [{}]",
+            type_urls(planned),
+            type_urls(current)
+        );
+    }
+
+    for grant in current {
+        if grant.is_expired(now) {
+            bail!("grant for '{}' has expired", grant.msg_type_url);
+        }
+    }
+
+    Ok(())
+}
+
+/// Amounts planned validators would pay out now. Only denoms selected at planning time are counted,
+/// same as when the plan was made.
+async fn current_amounts(
+    client: &HttpClient,
+    delegator_address: &AccountId,
+    validators: &[ValidatorReport],
+) -> eyre::Result<CollectedCoins> {
+    let delegation_total_rewards = execute_abci_query::<QueryDelegationTotalRewards>(
+        client,
+        QueryDelegationTotalRewardsRequest {
+            delegator_address: delegator_address.to_string(),
+        },
+    )
+    .await?;
+
+    let mut collected = CollectedCoins::default();
+    let collect = |collected_coins: &mut HashMap<String, BigUint>,
+                   denom: &str,
+                   amount: &str|
+     -> eyre::Result<()> {
+        let amount = dec_to_base_units(amount).wrap_err("failed to parse reward coin amount")?;
+        *collected_coins.entry(denom.to_string()).or_default() += amount;
+        Ok(())
+    };
+
+    for validator in validators {
+        if validator.withdraw_rewards {
+            let selected_denoms: Vec<&str> = validator
+                .rewards
+                .iter()
+                .filter(|reward| reward.decision == RewardDecision::Selected)
+                .map(|reward| reward.denom.as_str())
+                .collect();
+            let rewards = delegation_total_rewards
+                .rewards
+                .iter()
+                .filter(|reward| reward.validator_address == validator.validator_address)
+                .flat_map(|reward| reward.reward.iter());
+
+            for coin in rewards {
+                if selected_denoms.contains(&coin.denom.as_str()) {
+                    collect(&mut collected.rewards, &coin.denom, &coin.amount)?;
+                }
+            }
+        }
+
+        if validator.withdraw_commission {
+            let validator_address = AccountId::from_str(&validator.validator_address)
+                .wrap_err("failed to parse validator address")?;
+            let commission = get_validator_commission(client, &validator_address)
+                .await?
+                .unwrap_or_default();
+            for coin in commission {
+                collect(&mut collected.commission, &coin.denom, &coin.amount)?;
+            }
+        }
+    }
+
+    Ok(collected)
+}
+
+/// Fails if any of the planned amounts changed by more than `tolerance_percent`
+fn check_amount_drift(
+    planned: &[CoinAmount],
+    current: &[CoinAmount],
+    tolerance_percent: f64,
+) -> eyre::Result<()> {
+    // Amounts are compared in integers, as 18 decimal denoms do not fit into f64 precisely.
+    // Tolerance is kept up to millionths of a percent
+    let tolerance = BigUint::from((tolerance_percent * TOLERANCE_SCALE as f64).round() as u128);
+
+    let mut drifted = Vec::new();
+    for planned in planned {
+        let current_amount = current
+            .iter()
+            .find(|coin| coin.denom == planned.denom)
+            .map(|coin| coin.amount.as_str())
+            .unwrap_or("0");
+
+        let planned_value: BigUint = planned
+            .amount
+            .parse()
+            .wrap_err_with(|| format!("invalid planned amount '{}'", planned.amount))?;
+        let current_value: BigUint = current_amount
+            .parse()
+            .wrap_err_with(|| format!("invalid amount '{current_amount}'"))?;
+
+        // Compared without dividing, so that drift of exactly the tolerance is not rounded over it.
+        // Any amount drifts infinitely from zero
+        let difference = if current_value > planned_value {
+            &current_value - &planned_value
+        } else {
+            &planned_value - &current_value
+        };
+        if difference * 100u32 * TOLERANCE_SCALE > &tolerance * &planned_value {
+            drifted.push(format!(
+                "{}{denom} -> {current_amount}{denom}",
+                planned.amount,
+                denom = planned.denom
+            ));
+        }
+    }
+
+    if !drifted.is_empty() {
+        bail!(
+            "amounts drifted more than {tolerance_percent}% since planning: {}",
+            drifted.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use cosmrs::{AccountId, proto::Timestamp};
+    use time::UtcDateTime;
+
+    use super::{WithdrawAction, check_amount_drift, check_grants};
+    use crate::{chain::GrantInfo, report::CoinAmount};
+
+    const REWARD_ADDRESS: &str = "osmo176pmhxsfz0cpgr705y5m4hy4kdxq2eq2atl2fy";
+
+    /// Case name, planned grants, current grants, and expected error
+    type GrantsCase = (
+        &'static str,
+        Vec<GrantInfo>,
+        Vec<GrantInfo>,
+        Option<&'static str>,
+    );
+    /// Case name, planned amounts, current amounts, tolerance, and whether the check passes
+    type DriftCase = (
+        &'static str,
+        &'static [(&'static str, &'static str)],
+        &'static [(&'static str, &'static str)],
+        f64,
+        bool,
+    );
+
+    fn grant(msg_type_url: &str, expiration: Option<i64>) -> GrantInfo {
+        GrantInfo {
+            msg_type_url: msg_type_url.to_string(),
+            expiration: expiration.map(|seconds| Timestamp { seconds, nanos: 0 }),
+            spend_limit: Vec::new(),
+            allow_list: Vec::new(),
+        }
+    }
+
+    fn coins(coins: &[(&str, &str)]) -> Vec<CoinAmount> {
+        coins
+            .iter()
+            .map(|(amount, denom)| CoinAmount {
+                denom: denom.to_string(),
+                amount: amount.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_check_grants() {
+        let now = UtcDateTime::now();
+        let future = (now + Duration::from_secs(3600)).unix_timestamp();
+        let past = (now - Duration::from_secs(1)).unix_timestamp();
+
+        let reward = grant(
+            "/cosmos.distribution.v1beta1.MsgWithdrawDelegatorReward",
+            None,
+        );
+        let commission = grant(
+            "/cosmos.distribution.v1beta1.MsgWithdrawValidatorCommission",
+            Some(future),
+        );
+        let send = GrantInfo {
+            spend_limit: coins(&[("1000", "uosmo")]),
+            allow_list: vec![REWARD_ADDRESS.to_string()],
+            ..grant("/cosmos.bank.v1beta1.MsgSend", None)
+        };
+        let planned = vec![reward.clone(), commission.clone(), send.clone()];
+
+        let spent = GrantInfo {
+            spend_limit: coins(&[("10", "uosmo")]),
+            ..send.clone()
+        };
+        let renewed = GrantInfo {
+            expiration: Some(Timestamp {
+                seconds: future + 60,
+                nanos: 0,
+            }),
+            ..commission.clone()
+        };
+        let expired = GrantInfo {
+            expiration: Some(Timestamp {
+                seconds: past,
+                nanos: 0,
+            }),
+            ..commission.clone()
+        };
+
+        let cases: &[GrantsCase] = &[
+            ("unchanged", planned.clone(), planned.clone(), None),
+            (
+                "order does not matter",
+                planned.clone(),
+                vec![send.clone(), reward.clone(), commission.clone()],
+                None,
+            ),
+            ("no grants", Vec::new(), Vec::new(), None),
+            (
+                "grant added",
+                planned.clone(),
+                vec![
+                    reward.clone(),
+                    commission.clone(),
+                    send.clone(),
+                    grant("/cosmos.staking.v1beta1.MsgDelegate", None),
+                ],
+                Some("changed since planning"),
+            ),
+            (
+                "grant removed",
+                planned.clone(),
+                vec![reward.clone(), send.clone()],
+                Some("changed since planning"),
+            ),
+            (
+                "spend limit changed",
+                planned.clone(),
+                vec![reward.clone(), commission.clone(), spent],
+                Some("changed since planning"),
+            ),
+            (
+                "expiration changed",
+                planned.clone(),
+                vec![reward.clone(), renewed, send.clone()],
+                Some("changed since planning"),
+            ),
+            (
+                "expired since planning",
+                vec![reward.clone(), expired.clone()],
+                vec![reward.clone(), expired],
+                Some("has expired"),
+            ),
+        ];
+
+        for (name, planned, current, expected_err) in cases {
+            let result = check_grants(planned, current, now);
+            match expected_err {
+                None => assert!(result.is_ok(), "{name}: {result:?}"),
+                Some(expected_err) => {
+                    let err = result.unwrap_err().to_string();
+                    assert!(err.contains(expected_err), "{name}: {err}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_check_amount_drift() {
+        let cases: &[DriftCase] = &[
+            (
+                "unchanged",
+                &[("1000", "uosmo")],
+                &[("1000", "uosmo")],
+                0.0,
+                true,
+            ),
+            (
+                "grown within",
+                &[("1000", "uosmo")],
+                &[("1050", "uosmo")],
+                10.0,
+                true,
+            ),
+            (
+                "shrunk within",
+                &[("1000", "uosmo")],
+                &[("950", "uosmo")],
+                10.0,
+                true,
+            ),
+            (
+                "grown exactly at",
+                &[("1000", "uosmo")],
+                &[("1070", "uosmo")],
+                7.0,
+                true,
+            ),
+            (
+                "shrunk exactly at",
+                &[("1000", "uosmo")],
+                &[("930", "uosmo")],
+                7.0,
+                true,
+            ),
+            (
+                "grown just over",
+                &[("1000", "uosmo")],
+                &[("1071", "uosmo")],
+                7.0,
+                false,
+            ),
+            (
+                "shrunk just over",
+                &[("1000", "uosmo")],
+                &[("929", "uosmo")],
+                7.0,
+                false,
+            ),
+            (
+                "beyond f64 integer precision",
+                &[("1000000000000000000000000", "aevmos")],
+                &[("1100000000000000000000000", "aevmos")],
+                10.0,
+                true,
+            ),
+            (
+                "zero stays zero",
+                &[("0", "uosmo")],
+                &[("0", "uosmo")],
+                0.0,
+                true,
+            ),
+            (
+                "zero grows",
+                &[("0", "uosmo")],
+                &[("1", "uosmo")],
+                1000.0,
+                false,
+            ),
+            ("denom missing now", &[("1000", "uosmo")], &[], 99.0, false),
+            (
+                "denom missing, full tolerance",
+                &[("1000", "uosmo")],
+                &[],
+                100.0,
+                true,
+            ),
+            (
+                "one of denoms drifted",
+                &[("1000", "uosmo"), ("1000", "uion")],
+                &[("1000", "uosmo"), ("2000", "uion")],
+                10.0,
+                false,
+            ),
+            // Only planned denoms are withdrawn
+            (
+                "new denom is ignored",
+                &[("1000", "uosmo")],
+                &[("1000", "uosmo"), ("5", "uion")],
+                0.0,
+                true,
+            ),
+        ];
+
+        for (name, planned, current, tolerance, ok) in cases {
+            let result = check_amount_drift(&coins(planned), &coins(current), *tolerance);
+            assert_eq!(result.is_ok(), *ok, "{name}: {result:?}");
+            if let Err(err) = result {
+                assert!(
+                    err.to_string().contains("drifted more than"),
+                    "{name}: {err}"
+                );
+            }
+        }
+
+        let err = check_amount_drift(&coins(&[("1000", "uosmo")]), &[], 10.0).unwrap_err();
+        assert!(err.to_string().contains("1000uosmo -> 0uosmo"), "{err}");
+        assert!(
+            check_amount_drift(
+                &coins(&[("1000", "uosmo")]),
+                &coins(&[("1007", "uosmo")]),
+                0.7
+            )
+            .is_ok()
+        );
+        assert!(check_amount_drift(&coins(&[("1.5", "uosmo")]), &[], 10.0).is_err());
+        assert!(check_amount_drift(&coins(&[("abc", "uosmo")]), &[], 10.0).is_err());
+    }
+
+    #[test]
+    fn test_check_withdraw_args() {
+        let plan = WithdrawAction::Plan {
+            plan_file: None,
+            amount_tolerance: 10.0,
+        };
+        let apply = WithdrawAction::Apply {
+            plan: "plan.json".into(),
+        };
+        let thresholds = ["1000000uosmo".parse().unwrap()];
+        let validators = [AccountId::new("osmovaloper", &[1; 20]).unwrap()];
+
+        // Plan selects validators by thresholds, apply leaves commission to the plan
+        assert!(
+            plan.check_withdraw_args(&thresholds, &validators, false)
+                .is_ok()
+        );
+        assert!(apply.check_withdraw_args(&[], &[], false).is_ok());
+
+        assert!(
+            plan.check_withdraw_args(&[], &[], true)
+                .unwrap_err()
+                .to_string()
+                .starts_with("--commission cannot be used with plan")
+        );
+        for (thresholds, validators) in [(&thresholds[..], &[][..]), (&[], &validators[..])] {
+            assert!(
+                apply
+                    .check_withdraw_args(thresholds, validators, false)
+                    .unwrap_err()
+                    .to_string()
+                    .starts_with("--threshold and --validator cannot be used with apply")
+            );
+        }
+    }
+}
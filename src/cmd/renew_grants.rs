@@ -0,0 +1,239 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use cosmrs::{
+    proto::{
+        cosmos::{
+            authz::v1beta1::{Grant, MsgGrant},
+            bank::v1beta1::MsgSend,
+        },
+        prost::Name,
+    },
+    rpc::HttpClient,
+};
+use eyre::{Context, bail};
+use num_bigint::BigUint;
+use time::UtcDateTime;
+use tracing::info;
+
+use crate::{
+    AccountArgs, TransactionArgs,
+    chain::{get_chain_info, get_grants},
+    cmd::setup_valoper::{DelegatorTx, delegator_tx},
+    cosmos_sdk_extra::{gas::GasInfo, str_coin::StrCoin},
+    report::{CoinAmount, RenewGrantsReport, RenewGrantsStatus, TxKind},
+    ser::{CosmosJsonSerializable, TimestampStr},
+};
+
+/// Re-issues grants given by the delegator to the controller with a new expiration, and/or tops up
+/// the spend limit of `SendAuthorization`. Without new expiration, only the topped up grant is
+/// re-issued, keeping its expiration.
+#[allow(clippy::too_many_arguments)]
+pub async fn renew_grants(
+    rpc_url: &str,
+    account_hrp: Option<&String>,
+    valoper_hrp: Option<&String>,
+    account: AccountArgs,
+    transaction_args: TransactionArgs,
+    expiration: Option<&TimestampStr>,
+    top_up: &[StrCoin],
+    multisig_member_mnemonic_files: &[PathBuf],
+) -> eyre::Result<RenewGrantsReport> {
+    if transaction_args.offline {
+        bail!("renew-grants needs to query existing grants, --offline is not supported");
+    }
+
+    let expiration = expiration.map(|expiration| *expiration.as_ref());
+    if expiration.is_none() && top_up.is_empty() {
+        bail!("nothing to renew, set new expiration or spend limit top-up");
+    }
+    if expiration
+        .is_some_and(|expiration| expiration.seconds <= UtcDateTime::now().unix_timestamp())
+    {
+        bail!("new grant expiration has to be in the future");
+    }
+
+    let client = HttpClient::new(rpc_url)?;
+    let chain_info = get_chain_info(&client, account_hrp, valoper_hrp).await?;
+    let gas_info = GasInfo::determine_gas(&chain_info, &transaction_args)?;
+
+    info!(?chain_info, ?gas_info.denom, ?gas_info.price, "chain info");
+
+    // Held from before the chain state is queried until the transaction is committed, so that
+    // concurrent runs neither act on the same state nor race with withdrawals
+    let lock = transaction_args
+        .lock_account(chain_info.id.as_str(), &account.controller_address)
+        .await?;
+
+    let grants = get_grants(
+        &client,
+        &account.delegator_address,
+        &account.controller_address,
+    )
+    .await?;
+    if grants.is_empty() {
+        bail!("delegator has not given any grants to the controller, run `setup-valoper` first");
+    }
+
+    let mut report = RenewGrantsReport {
+        chain_id: chain_info.id.to_string(),
+        expiration,
+        ..Default::default()
+    };
+
+    // Granting the same message type again replaces the existing grant
+    let mut msgs: Vec<CosmosJsonSerializable> = Vec::new();
+    for mut grant in grants {
+        // SendAuthorization always has a spend limit, unlike GenericAuthorization for MsgSend
+        let top_up_grant = !top_up.is_empty()
+            && grant.msg_type_url == MsgSend::type_url()
+            && !grant.spend_limit.is_empty();
+        if top_up_grant {
+            grant.spend_limit = add_coins(&grant.spend_limit, top_up)?;
+            report.spend_limit = grant.spend_limit.clone();
+        } else if expiration.is_none() {
+            continue;
+        }
+
+        if expiration.is_none() && grant.is_expired(UtcDateTime::now()) {
+            bail!(
+                "grant for '{}' has expired, set new expiration",
+                grant.msg_type_url
+            );
+        }
+
+        msgs.push(
+            MsgGrant {
+                granter: account.delegator_address.to_string(),
+                grantee: account.controller_address.to_string(),
+                grant: Some(Grant {
+                    authorization: Some(grant.to_authorization()?),
+                    expiration: expiration.or(grant.expiration),
+                }),
+            }
+            .into(),
+        );
+        report.renewed.push(grant.msg_type_url);
+    }
+
+    if !top_up.is_empty() && report.spend_limit.is_empty() {
+        bail!(
+            "delegator has not given SendAuthorization to the controller, there is no spend limit to top up"
+        );
+    }
+
+    info!(renewed = ?report.renewed, ?expiration, spend_limit = ?report.spend_limit, "renewing grants");
+
+    match delegator_tx(
+        &client,
+        &chain_info,
+        &gas_info,
+        &account,
+        &transaction_args,
+        &lock,
+        TxKind::RenewGrants,
+        msgs,
+        multisig_member_mnemonic_files,
+    )
+    .await?
+    {
+        DelegatorTx::Generated(unsigned_tx) => {
+            report.status = RenewGrantsStatus::Generated;
+            report.unsigned_tx = Some(unsigned_tx);
+        }
+        DelegatorTx::DryRun => report.status = RenewGrantsStatus::DryRun,
+        DelegatorTx::Committed(tx_reports) => {
+            report.status = RenewGrantsStatus::Submitted;
+            report.transactions.extend(tx_reports);
+        }
+    }
+
+    Ok(report)
+}
+
+/// Adds coins to the spend limit, per denom
+fn add_coins(spend_limit: &[CoinAmount], top_up: &[StrCoin]) -> eyre::Result<Vec<CoinAmount>> {
+    let mut amounts: BTreeMap<String, BigUint> = BTreeMap::new();
+    for coin in spend_limit {
+        let amount: BigUint = coin
+            .amount
+            .parse()
+            .wrap_err("failed to parse spend limit amount")?;
+        *amounts.entry(coin.denom.clone()).or_default() += amount;
+    }
+    for coin in top_up {
+        *amounts.entry(coin.denom.to_string()).or_default() += BigUint::from(coin.amount);
+    }
+
+    Ok(amounts
+        .into_iter()
+        .map(|(denom, amount)| CoinAmount {
+            denom,
+            amount: amount.to_string(),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::add_coins;
+    use crate::{cosmos_sdk_extra::str_coin::StrCoin, report::CoinAmount};
+
+    fn coins(coins: &[(&str, &str)]) -> Vec<CoinAmount> {
+        coins
+            .iter()
+            .map(|(amount, denom)| CoinAmount {
+                denom: denom.to_string(),
+                amount: amount.to_string(),
+            })
+            .collect()
+    }
+
+    fn top_up(coins: &[&str]) -> Vec<StrCoin> {
+        coins.iter().map(|coin| coin.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_add_coins() {
+        // Existing denom, amounts beyond u128 do not overflow
+        assert_eq!(
+            add_coins(
+                &coins(&[
+                    ("1000", "uosmo"),
+                    ("340282366920938463463374607431768211455", "aevmos")
+                ]),
+                &top_up(&["500uosmo", "1aevmos"])
+            )
+            .unwrap(),
+            coins(&[
+                ("340282366920938463463374607431768211456", "aevmos"),
+                ("1500", "uosmo"),
+            ])
+        );
+
+        // Denom not in the spend limit yet is added to it
+        assert_eq!(
+            add_coins(&coins(&[("1000", "uosmo")]), &top_up(&["5uion"])).unwrap(),
+            coins(&[("5", "uion"), ("1000", "uosmo")])
+        );
+
+        // Same denom given twice is summed up
+        assert_eq!(
+            add_coins(&[], &top_up(&["5uosmo", "10uosmo"])).unwrap(),
+            coins(&[("15", "uosmo")])
+        );
+
+        // Spend limit reported by the chain has to be an unsigned integer
+        for amount in ["", "-1", "1.5", "abc"] {
+            assert!(
+                add_coins(&coins(&[(amount, "uosmo")]), &top_up(&["1uosmo"])).is_err(),
+                "{amount:?}"
+            );
+        }
+        // Invalid top-up amounts are rejected already when parsing arguments
+        for coin in ["-1uosmo", "uosmo"] {
+            assert!(coin.parse::<StrCoin>().is_err(), "{coin}");
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::{path::Path, time::Duration};
+
+use eyre::{Context, bail};
+use serde_json::json;
+use tracing::{Instrument, error, info, info_span};
+
+use crate::{
+    cmd::withdraw,
+    config::{ChainConfig, Config},
+    ledger::Ledger,
+    notify::Notifier,
+    report::{FailureReport, OutputFormat, WithdrawReport},
+};
+
+pub async fn run(
+    config_path: &Path,
+    only_chains: &[String],
+    output: OutputFormat,
+    grant_expiry_warning: Duration,
+    notifier: Option<&Notifier>,
+    ledger: Option<&Ledger>,
+) -> eyre::Result<()> {
+    let config = Config::load(config_path)?;
+
+    for name in only_chains {
+        if !config.chains.iter().any(|chain| &chain.name == name) {
+            bail!("chain '{name}' is not present in config file");
+        }
+    }
+
+    let chains = config
+        .chains
+        .into_iter()
+        .filter(|chain| only_chains.is_empty() || only_chains.contains(&chain.name))
+        .collect::<Vec<_>>();
+
+    info!(chains = chains.len(), "running withdrawals");
+
+    let mut results: Vec<(String, eyre::Result<WithdrawReport>)> = Vec::new();
+    for chain in chains {
+        let span = info_span!("chain", name = chain.name);
+        let result = run_chain(&chain, grant_expiry_warning, ledger)
+            .instrument(span)
+            .await;
+        if let Err(err) = &result {
+            // One failing chain must not prevent processing the rest
+            error!(name = chain.name, ?err, "chain withdrawal failed");
+        }
+
+        if let Some(notifier) = notifier {
+            notifier.notify(Some(&chain.name), &result).await;
+        }
+
+        results.push((chain.name, result));
+    }
+
+    print_results(&results, output)
+}
+
+/// Prints the result of every chain, and fails if any of them did, so that the process exits with
+/// [`EXIT_FAILURE`](crate::report::EXIT_FAILURE) even though the rest were processed
+fn print_results(
+    results: &[(String, eyre::Result<WithdrawReport>)],
+    output: OutputFormat,
+) -> eyre::Result<()> {
+    let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+    match output {
+        OutputFormat::Text => {
+            for (name, result) in results.iter() {
+                match result {
+                    Ok(report) => println!("{name}: {report}"),
+                    Err(err) => println!("{name}: failed: {err:#}"),
+                }
+            }
+        }
+        OutputFormat::Json => {
+            let chains = results
+                .iter()
+                .map(|(name, result)| match result {
+                    Ok(report) => json!({ "name": name, "report": report }),
+                    Err(err) => json!({ "name": name, "report": FailureReport::new(err) }),
+                })
+                .collect::<Vec<_>>();
+
+            println!("{}", json!({ "chains": chains }));
+        }
+    }
+
+    if failed > 0 {
+        bail!("{failed} of {} chains failed", results.len());
+    }
+
+    Ok(())
+}
+
+async fn run_chain(
+    chain: &ChainConfig,
+    grant_expiry_warning: Duration,
+    ledger: Option<&Ledger>,
+) -> eyre::Result<WithdrawReport> {
+    let account = chain
+        .to_account_args()
+        .wrap_err("failed to set up account arguments")?;
+
+    withdraw(
+        &chain.rpc_url,
+        chain.account_hrp.as_ref(),
+        chain.valoper_hrp.as_ref(),
+        account,
+        chain.transaction.to_transaction_args(),
+        chain.thresholds.clone(),
+        Vec::new(),
+        grant_expiry_warning,
+        ledger.cloned(),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    use eyre::eyre;
+    use pretty_assertions::assert_eq;
+
+    use super::print_results;
+    use crate::report::{EXIT_FAILURE, EXIT_SUCCESS, OutputFormat, WithdrawReport, exit_code};
+
+    #[test]
+    fn test_exit_code() {
+        let nothing_to_withdraw = || Ok(WithdrawReport::default());
+
+        // Nothing to withdraw is a successful result, not a failure
+        let results = vec![
+            ("osmosis".to_string(), nothing_to_withdraw()),
+            ("cosmoshub".to_string(), nothing_to_withdraw()),
+        ];
+        for output in [OutputFormat::Text, OutputFormat::Json] {
+            assert_eq!(exit_code(&print_results(&results, output)), EXIT_SUCCESS);
+        }
+
+        // One failing chain fails the whole run, after the others were reported
+        let results = vec![
+            ("osmosis".to_string(), nothing_to_withdraw()),
+            (
+                "cosmoshub".to_string(),
+                Err(eyre!("failed to query delegation rewards")),
+            ),
+        ];
+        for output in [OutputFormat::Text, OutputFormat::Json] {
+            let result = print_results(&results, output);
+            assert_eq!(exit_code(&result), EXIT_FAILURE);
+            assert_eq!(result.unwrap_err().to_string(), "1 of 2 chains failed");
+        }
+    }
+}
@@ -1,9 +1,13 @@
+use std::{path::PathBuf, time::Duration};
+
 use cosmrs::{
     Any,
+    crypto::LegacyAminoMultisig,
     proto::{
         cosmos::{
             authz::v1beta1::{GenericAuthorization, Grant, MsgGrant},
-            bank::v1beta1::MsgSend,
+            bank::v1beta1::{MsgSend, SendAuthorization},
+            base::v1beta1::Coin,
             distribution::v1beta1::{
                 MsgSetWithdrawAddress, MsgWithdrawDelegatorReward, MsgWithdrawValidatorCommission,
             },
@@ -14,22 +18,46 @@ use cosmrs::{
     rpc::{Client, HttpClient},
     tx::MessageExt,
 };
-use eyre::{Context, eyre};
+use eyre::{Context, ContextCompat, bail};
+use serde_json::Value;
+use time::UtcDateTime;
 use tracing::{info, warn};
 
 use crate::{
     AccountArgs, SetupValoperMethod, TransactionArgs,
-    chain::get_chain_info,
+    chain::{ChainInfo, GrantInfo, get_chain_info, get_grants, get_withdraw_address},
     cmd::ResolvedAccounts,
     cosmos_sdk_extra::{
         gas::GasInfo,
         simulate::simulate_tx,
         tx::{generate_unsigned_tx_json, poll_tx, print_tx_result},
     },
+    lock::AccountLock,
+    report::{SetupChange, SetupValoperReport, SetupValoperStatus, TxKind, TxReport},
     ser::{CosmosJsonSerializable, TimestampStr},
-    wallet::{SigningAccountType, construct_transaction_body, setup_signer, sign_transaction},
+    wallet::{
+        SigningAccountType, TxSigner, construct_transaction_body, load_multisig_signer,
+        setup_signer, sign_multisig_transaction, sign_transaction,
+    },
 };
 
+/// Delegator signs either with its own key, or with keys of its multisig members
+enum DelegatorSigner {
+    Single(TxSigner),
+    Multisig(LegacyAminoMultisig),
+}
+
+impl DelegatorSigner {
+    fn with_numbers(self, account_number: u64, sequence: u64) -> Self {
+        match self {
+            Self::Single(signer) => Self::Single(signer.with_numbers(account_number, sequence)),
+            // Numbers are given when member keys are loaded
+            Self::Multisig(multisig) => Self::Multisig(multisig),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn setup_valoper(
     rpc_url: &str,
     account_hrp: Option<&String>,
@@ -38,47 +66,39 @@ pub async fn setup_valoper(
     transaction_args: TransactionArgs,
     method: SetupValoperMethod,
     expiration: Option<&TimestampStr>,
-) -> eyre::Result<()> {
+    multisig_member_mnemonic_files: &[PathBuf],
+) -> eyre::Result<SetupValoperReport> {
     let client = HttpClient::new(rpc_url)?;
-    let chain_info = get_chain_info(&client, account_hrp, valoper_hrp).await?;
+    let chain_info = if transaction_args.offline {
+        let mut chain_info = transaction_args.offline_chain_info(account_hrp, valoper_hrp)?;
+        chain_info.chain_supports_setting_withdrawal_address = match method {
+            SetupValoperMethod::Auto(_) => {
+                bail!("setup method cannot be detected in offline mode, choose it explicitly")
+            }
+            SetupValoperMethod::AuthzSend(_) => false,
+            _ => true,
+        };
+        account.verify_accounts(&chain_info)?;
+        chain_info
+    } else {
+        get_chain_info(&client, account_hrp, valoper_hrp).await?
+    };
     let gas_info = GasInfo::determine_gas(&chain_info, &transaction_args)?;
 
     info!(?chain_info, ?gas_info.denom, ?gas_info.price, "chain info");
 
-    // Determine setup method
-    let setup_method = match (method, chain_info.chain_supports_setting_withdrawal_address) {
-        (SetupValoperMethod::Auto, true) => SetupValoperMethod::AuthzWithdraw,
-        (SetupValoperMethod::Auto, false) => SetupValoperMethod::AuthzSend,
-
-        // Invariants
-        (SetupValoperMethod::AuthzWithdraw, false) => {
-            return Err(eyre!(
-                "this chain does not support setting withdrawal address for distribution"
-            ));
-        }
-        (m @ SetupValoperMethod::AuthzSend, true) => {
-            warn!(
-                chain_id = ?chain_info.id,
-                "this chain supports setting withdrawal address, granting MsgSend has security implications"
-            );
-            m
-        }
-
-        // Pass-through
-        (method, _) => method,
-    };
+    // Held from before the chain state is queried until the transaction is committed, so that
+    // concurrent runs neither act on the same state nor race with withdrawals
+    let lock = transaction_args
+        .lock_account(chain_info.id.as_str(), &account.controller_address)
+        .await?;
 
-    // Ensure delegator & controller accounts are initialized
-    // Withdrawal address does not need to be initialized, as it'll only receive rewards
-    let ResolvedAccounts {
-        delegator_account,
-        delegator_key_type,
-        ..
-    } = account.get_account_details(&client, &chain_info).await?;
+    let setup_method =
+        resolve_method(method, chain_info.chain_supports_setting_withdrawal_address)?;
 
     let mut msgs: Vec<CosmosJsonSerializable> = Vec::new();
     info!(?setup_method, "setting up valoper account grants");
-    match setup_method {
+    match &setup_method {
         SetupValoperMethod::AuthzWithdraw => {
             let withdraw_address = account
                 .reward_address
@@ -115,8 +135,33 @@ pub async fn setup_valoper(
             msgs.push(msg_authz_withdraw_reward.into());
             msgs.push(msg_authz_withdraw_commission.into());
         }
-        SetupValoperMethod::AuthzSend => {
-            warn!("authz-send method does not work with --generate-only");
+        SetupValoperMethod::AuthzSend(args) => {
+            let send_authorization = if args.generic_authorization {
+                warn!(
+                    "granting unrestricted MsgSend, controller will be able to send all funds of the delegator"
+                );
+                Any::from_msg(&GenericAuthorization {
+                    msg: MsgSend::type_url(),
+                })?
+            } else {
+                // Withdrawn tokens are only ever sent to the reward address
+                let withdraw_address = account
+                    .reward_address
+                    .as_ref()
+                    .unwrap_or(&account.controller_address);
+                Any::from_msg(&SendAuthorization {
+                    spend_limit: args
+                        .spend_limit
+                        .iter()
+                        .map(|coin| Coin {
+                            denom: coin.denom.to_string(),
+                            amount: coin.amount.to_string(),
+                        })
+                        .collect(),
+                    allow_list: vec![withdraw_address.to_string()],
+                })?
+            };
+
             let msg_authz_withdraw_reward = MsgGrant {
                 granter: account.delegator_address.to_string(),
                 grantee: account.controller_address.to_string(),
@@ -124,7 +169,7 @@ pub async fn setup_valoper(
                     authorization: Some(Any::from_msg(&GenericAuthorization {
                         msg: MsgWithdrawDelegatorReward::type_url(),
                     })?),
-                    expiration: None,
+                    expiration: expiration.map(|e| *e.as_ref()),
                 }),
             };
             let msg_authz_withdraw_commission = MsgGrant {
@@ -134,17 +179,15 @@ pub async fn setup_valoper(
                     authorization: Some(Any::from_msg(&GenericAuthorization {
                         msg: MsgWithdrawValidatorCommission::type_url(),
                     })?),
-                    expiration: None,
+                    expiration: expiration.map(|e| *e.as_ref()),
                 }),
             };
             let msg_authz_send = MsgGrant {
                 granter: account.delegator_address.to_string(),
                 grantee: account.controller_address.to_string(),
                 grant: Some(Grant {
-                    authorization: Some(Any::from_msg(&GenericAuthorization {
-                        msg: MsgSend::type_url(),
-                    })?),
-                    expiration: None,
+                    authorization: Some(send_authorization),
+                    expiration: expiration.map(|e| *e.as_ref()),
                 }),
             };
 
@@ -155,65 +198,708 @@ pub async fn setup_valoper(
         _ => unreachable!(),
     }
 
-    // This transaction will be signed by the delegator account
-    let signer = setup_signer(
+    let mut report = SetupValoperReport {
+        chain_id: chain_info.id.to_string(),
+        method: setup_method.name().to_string(),
+        ..Default::default()
+    };
+
+    if transaction_args.offline {
+        let fee = gas_info
+            .get_fee()
+            .wrap_err("transactions cannot be simulated in offline mode, set --gas explicitly")?;
+        // Generated transaction cannot be split, mixed messages are rejected in EIP-712 sign mode
+        let msgs = transaction_args.split_msgs(msgs)?.remove(0);
+        report.messages = type_urls(&msgs)?;
+        report.status = SetupValoperStatus::Generated;
+        report.unsigned_tx = Some(generate_unsigned_tx_json(
+            msgs,
+            &transaction_args.memo,
+            fee.gas_limit,
+            fee.amount,
+        ));
+
+        return Ok(report);
+    }
+
+    let (msgs, changes) =
+        missing_changes(&client, &account, msgs, expiration_tolerance(expiration)).await?;
+    for change in changes.iter() {
+        info!(%change, "planned change");
+    }
+    report.changes = changes;
+    report.messages = type_urls(&msgs)?;
+
+    if msgs.is_empty() {
+        info!("delegator is already configured, nothing to do");
+        report.status = SetupValoperStatus::AlreadyConfigured;
+        return Ok(report);
+    }
+
+    match delegator_tx(
+        &client,
+        &chain_info,
+        &gas_info,
         &account,
-        &chain_info.bech32,
-        SigningAccountType::Delegator {
-            key_type: delegator_key_type,
-            account_number: transaction_args
-                .account_number
-                .unwrap_or(delegator_account.account_number),
-            sequence: transaction_args
-                .sequence
-                .unwrap_or(delegator_account.sequence),
-        },
-        transaction_args.generate_only,
-    )?;
+        &transaction_args,
+        &lock,
+        TxKind::SetupValoper,
+        msgs,
+        multisig_member_mnemonic_files,
+    )
+    .await?
+    {
+        DelegatorTx::Generated(unsigned_tx) => {
+            report.status = SetupValoperStatus::Generated;
+            report.unsigned_tx = Some(unsigned_tx);
+        }
+        DelegatorTx::DryRun => report.status = SetupValoperStatus::DryRun,
+        DelegatorTx::Committed(tx_reports) => {
+            report.status = SetupValoperStatus::Submitted;
+            report.transactions.extend(tx_reports);
+        }
+    }
+
+    Ok(report)
+}
+
+/// Determines setup method based on chain functionality, and checks that the method can be used
+fn resolve_method(
+    method: SetupValoperMethod,
+    chain_supports_setting_withdrawal_address: bool,
+) -> eyre::Result<SetupValoperMethod> {
+    let method = match (method, chain_supports_setting_withdrawal_address) {
+        (SetupValoperMethod::Auto(_), true) => SetupValoperMethod::AuthzWithdraw,
+        (SetupValoperMethod::Auto(args), false) => SetupValoperMethod::AuthzSend(args),
 
-    // Determine necessary fee for transaction execution
-    let fee = if let Some(fee) = gas_info.get_fee() {
-        fee
+        // Invariants
+        (SetupValoperMethod::AuthzWithdraw, false) => {
+            bail!("this chain does not support setting withdrawal address for distribution");
+        }
+        (m @ SetupValoperMethod::AuthzSend(_), true) => {
+            warn!(
+                "this chain supports setting withdrawal address, granting MsgSend has security implications"
+            );
+            m
+        }
+
+        // Pass-through
+        (method, _) => method,
+    };
+
+    if let SetupValoperMethod::AuthzSend(args) = &method {
+        if args.spend_limit.is_empty() && !args.generic_authorization {
+            bail!(
+                "this chain needs the authz-send method, and SendAuthorization requires a spend limit: set it with `--spend-limit` after `auto` or `authz-send` (e.g. `auto --spend-limit 1000000uosmo`), or grant unrestricted MsgSend with `--generic-authorization`"
+            );
+        }
+    }
+
+    Ok(method)
+}
+
+/// How much earlier than requested with `--expiration` an existing grant may expire to be kept.
+/// Relative expiration like `365d` resolves to a different time on every run, so a tenth of the
+/// duration is tolerated, while an RFC3339 timestamp has to match exactly
+fn expiration_tolerance(expiration: Option<&TimestampStr>) -> Duration {
+    expiration
+        .and_then(TimestampStr::duration)
+        .map(|duration| duration / 10)
+        .unwrap_or_default()
+}
+
+/// Leaves out messages which would not change anything on chain, and describes the rest
+async fn missing_changes(
+    client: &HttpClient,
+    account: &AccountArgs,
+    msgs: Vec<CosmosJsonSerializable>,
+    expiration_tolerance: Duration,
+) -> eyre::Result<(Vec<CosmosJsonSerializable>, Vec<SetupChange>)> {
+    let grants = get_grants(
+        client,
+        &account.delegator_address,
+        &account.controller_address,
+    )
+    .await?;
+    let withdraw_address = if msgs
+        .iter()
+        .any(|msg| matches!(msg, CosmosJsonSerializable::MsgSetWithdrawAddress(_)))
+    {
+        Some(get_withdraw_address(client, &account.delegator_address).await?)
     } else {
-        simulate_tx(
-            &client,
-            &chain_info,
-            &gas_info,
-            &signer,
-            construct_transaction_body(&transaction_args.memo, &msgs)?,
-        )
-        .await?
+        None
     };
 
-    if transaction_args.generate_only {
-        println!(
-            "{}",
-            generate_unsigned_tx_json(msgs, &transaction_args.memo, fee.gas_limit, fee.amount)
-        );
+    diff_changes(
+        msgs,
+        &grants,
+        withdraw_address,
+        UtcDateTime::now(),
+        expiration_tolerance,
+    )
+}
+
+/// Compares desired messages with existing grants and current withdraw address. Existing grant is
+/// kept only if it has not expired, authorizes the same, and expires at the time requested with
+/// `--expiration`, within `expiration_tolerance` - so re-running setup with a different expiration
+/// re-issues the grants. Withdraw address has to be given if messages set it
+fn diff_changes(
+    msgs: Vec<CosmosJsonSerializable>,
+    grants: &[GrantInfo],
+    withdraw_address: Option<String>,
+    now: UtcDateTime,
+    expiration_tolerance: Duration,
+) -> eyre::Result<(Vec<CosmosJsonSerializable>, Vec<SetupChange>)> {
+    let mut missing = Vec::new();
+    let mut changes = Vec::new();
+    for msg in msgs {
+        match &msg {
+            CosmosJsonSerializable::MsgSetWithdrawAddress(set_address) => {
+                let current = withdraw_address
+                    .clone()
+                    .wrap_err("current withdraw address is not known")?;
+                if current == set_address.withdraw_address {
+                    info!(
+                        withdraw_address = current,
+                        "withdraw address is already set"
+                    );
+                    continue;
+                }
 
-        return Ok(());
+                changes.push(SetupChange::WithdrawAddress {
+                    current,
+                    desired: set_address.withdraw_address.clone(),
+                });
+            }
+            CosmosJsonSerializable::MsgGrant(msg_grant) => {
+                let desired = msg_grant
+                    .grant
+                    .as_ref()
+                    .map(GrantInfo::from_grant)
+                    .transpose()?
+                    .flatten()
+                    .wrap_err("grant has unsupported authorization")?;
+                let existing = grants
+                    .iter()
+                    .find(|grant| grant.msg_type_url == desired.msg_type_url);
+                if existing.is_some_and(|grant| grant.fulfills(&desired, now, expiration_tolerance))
+                {
+                    info!(
+                        msg_type_url = desired.msg_type_url,
+                        "grant is already given"
+                    );
+                    continue;
+                }
+
+                changes.push(SetupChange::Grant {
+                    msg_type_url: desired.msg_type_url,
+                    replaces: existing.cloned(),
+                });
+            }
+            _ => {}
+        }
+
+        missing.push(msg);
     }
 
-    let signed_tx = sign_transaction(
-        &chain_info,
-        &signer,
-        fee,
-        construct_transaction_body(&transaction_args.memo, &msgs)?,
-    )
-    .wrap_err("failed to sign withdraw transaction")?;
+    Ok((missing, changes))
+}
+
+fn type_urls(msgs: &[CosmosJsonSerializable]) -> eyre::Result<Vec<String>> {
+    Ok(msgs
+        .iter()
+        .map(|msg| msg.to_any().map(|any| any.type_url))
+        .collect::<Result<_, _>>()?)
+}
+
+/// Outcome of a transaction signed by the delegator
+pub(super) enum DelegatorTx {
+    /// Unsigned transaction, when transaction generation was requested
+    Generated(Value),
+    /// Transaction was signed, but not broadcast
+    DryRun,
+    /// Messages which cannot be signed together are committed in separate transactions
+    Committed(Vec<TxReport>),
+}
+
+/// Signs transaction by the delegator, or by members of the delegator multisig, and broadcasts it.
+/// With `--generate-only`, unsigned transaction is returned instead.
+/// The caller holds the account lock, taken before it queried the state the messages are built from.
+#[allow(clippy::too_many_arguments)]
+pub(super) async fn delegator_tx(
+    client: &HttpClient,
+    chain_info: &ChainInfo,
+    gas_info: &GasInfo,
+    account: &AccountArgs,
+    transaction_args: &TransactionArgs,
+    _lock: &Option<AccountLock>,
+    kind: TxKind,
+    msgs: Vec<CosmosJsonSerializable>,
+    multisig_member_mnemonic_files: &[PathBuf],
+) -> eyre::Result<DelegatorTx> {
+    // Ensure delegator & controller accounts are initialized
+    // Withdrawal address does not need to be initialized, as it'll only receive rewards
+    let ResolvedAccounts {
+        delegator_account,
+        delegator_key_type,
+        delegator_multisig,
+        ..
+    } = account.get_account_details(client, chain_info).await?;
+
+    let account_number = transaction_args
+        .account_number
+        .unwrap_or(delegator_account.account_number);
+    let sequence = transaction_args
+        .sequence
+        .unwrap_or(delegator_account.sequence);
+
+    // This transaction will be signed by the delegator account, or by members of the delegator multisig
+    let mut signer = match delegator_multisig {
+        Some(multisig) => {
+            info!(
+                threshold = multisig.threshold,
+                members = multisig.public_keys.len(),
+                "delegator is a multisig account"
+            );
+            DelegatorSigner::Multisig(multisig)
+        }
+        None => DelegatorSigner::Single(
+            setup_signer(
+                account,
+                &chain_info.bech32,
+                SigningAccountType::Delegator {
+                    key_type: delegator_key_type,
+                    account_number,
+                    sequence,
+                },
+                transaction_args.generate_only,
+            )
+            .await?
+            .with_sign_mode(transaction_args.sign_mode)
+            .with_eip712_chain_id(transaction_args.eip712_chain_id),
+        ),
+    };
+
+    // Multisig members always sign amino JSON, which does not restrict the messages
+    let batches = match signer {
+        DelegatorSigner::Single(_) => transaction_args.split_msgs(msgs)?,
+        DelegatorSigner::Multisig(_) => vec![msgs],
+    };
+
+    let mut tx_reports = Vec::new();
+    for (i, msgs) in batches.into_iter().enumerate() {
+        // Transactions are committed one after another, so each of them uses the next sequence.
+        // Nothing is committed in a dry run, so all of them are simulated against the current one
+        let sequence = if transaction_args.dry_run {
+            sequence
+        } else {
+            sequence + i as u64
+        };
+        signer = signer.with_numbers(account_number, sequence);
+
+        // Determine necessary fee for transaction execution
+        let fee = match (gas_info.get_fee(), &signer) {
+            (Some(fee), _) => fee,
+            (None, DelegatorSigner::Single(signer)) => {
+                simulate_tx(
+                    client,
+                    chain_info,
+                    gas_info,
+                    signer,
+                    construct_transaction_body(&transaction_args.memo, &msgs)?,
+                )
+                .await?
+            }
+            (None, DelegatorSigner::Multisig(_)) => {
+                bail!("transactions of multisig accounts cannot be simulated, set --gas explicitly")
+            }
+        };
+
+        if transaction_args.generate_only {
+            return Ok(DelegatorTx::Generated(generate_unsigned_tx_json(
+                msgs,
+                &transaction_args.memo,
+                fee.gas_limit,
+                fee.amount,
+            )));
+        }
+
+        let body = construct_transaction_body(&transaction_args.memo, &msgs)?;
+        let signed_tx = match &signer {
+            DelegatorSigner::Single(signer) => {
+                sign_transaction(chain_info, signer, fee.clone(), body).await
+            }
+            DelegatorSigner::Multisig(multisig) => {
+                let signer = load_multisig_signer(
+                    account,
+                    multisig.clone(),
+                    multisig_member_mnemonic_files,
+                    account_number,
+                    sequence,
+                )?;
+                sign_multisig_transaction(chain_info, &signer, fee.clone(), body, &msgs).await
+            }
+        }
+        .wrap_err_with(|| format!("failed to sign {kind} transaction"))?;
+
+        if transaction_args.dry_run {
+            continue;
+        }
+
+        let tx_result = client
+            .broadcast_tx_sync(Tx::from(signed_tx).to_bytes()?)
+            .await?;
+
+        print_tx_result(&tx_result)?;
+        let tx_response = poll_tx(client, tx_result.hash).await?;
+        info!(tx_hash = ?tx_result.hash, %kind, "transaction committed to chain");
+
+        tx_reports.push(TxReport::new(kind, &fee, &tx_response));
+    }
 
     if transaction_args.dry_run {
         info!("dry run was requested, nothing was done");
-        return Ok(());
+        return Ok(DelegatorTx::DryRun);
     }
 
-    let tx_result = client
-        .broadcast_tx_sync(Tx::from(signed_tx).to_bytes()?)
-        .await?;
+    Ok(DelegatorTx::Committed(tx_reports))
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use clap::Parser;
+    use cosmrs::{
+        Any,
+        proto::{
+            Timestamp,
+            cosmos::{
+                authz::v1beta1::{GenericAuthorization, Grant, MsgGrant},
+                distribution::v1beta1::{
+                    MsgSetWithdrawAddress, MsgWithdrawDelegatorReward,
+                    MsgWithdrawValidatorCommission,
+                },
+            },
+            prost::Name,
+        },
+    };
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use time::UtcDateTime;
+
+    use super::{diff_changes, expiration_tolerance, resolve_method, setup_valoper, type_urls};
+    use crate::{
+        chain::GrantInfo,
+        cmd::{AccountArgs, AuthzSendArgs, SetupValoperMethod, TransactionArgs},
+        ser::{CosmosJsonSerializable, TimestampStr},
+    };
+
+    const DELEGATOR: &str = "osmo1e4n3yara98z8lsxwcj7740q0nku3lcd70s9s29";
+    const CONTROLLER: &str = "osmo176pmhxsfz0cpgr705y5m4hy4kdxq2eq2atl2fy";
+
+    fn authz_send_args(spend_limit: &[&str], generic_authorization: bool) -> AuthzSendArgs {
+        AuthzSendArgs {
+            spend_limit: spend_limit
+                .iter()
+                .map(|coin| coin.parse().unwrap())
+                .collect(),
+            generic_authorization,
+        }
+    }
 
-    print_tx_result(&tx_result)?;
-    poll_tx(&client, tx_result.hash).await?;
-    info!(tx_hash = ?tx_result.hash, "transaction committed to chain, valoper set up");
+    #[test]
+    fn test_resolve_method() {
+        let resolved = |method, chain_supports_setting_withdrawal_address| {
+            resolve_method(method, chain_supports_setting_withdrawal_address)
+                .map(|method| method.name())
+                .map_err(|err| err.to_string())
+        };
 
-    Ok(())
+        // Spend limit does not matter when withdraw address can be set
+        assert_eq!(
+            resolved(SetupValoperMethod::Auto(authz_send_args(&[], false)), true),
+            Ok("authz-withdraw")
+        );
+        assert_eq!(
+            resolved(
+                SetupValoperMethod::Auto(authz_send_args(&["1000000uosmo"], false)),
+                false
+            ),
+            Ok("authz-send")
+        );
+        assert_eq!(
+            resolved(SetupValoperMethod::Auto(authz_send_args(&[], true)), false),
+            Ok("authz-send")
+        );
+        assert_eq!(
+            resolved(
+                SetupValoperMethod::AuthzSend(authz_send_args(&["1000000uosmo"], false)),
+                true
+            ),
+            Ok("authz-send")
+        );
+        assert_eq!(
+            resolved(SetupValoperMethod::AuthzWithdraw, true),
+            Ok("authz-withdraw")
+        );
+
+        assert!(
+            resolved(SetupValoperMethod::AuthzWithdraw, false)
+                .unwrap_err()
+                .contains("does not support setting withdrawal address")
+        );
+        for method in [
+            SetupValoperMethod::Auto(authz_send_args(&[], false)),
+            SetupValoperMethod::AuthzSend(authz_send_args(&[], false)),
+        ] {
+            let err = resolved(method, false).unwrap_err();
+            assert!(
+                err.contains("--spend-limit") && err.contains("--generic-authorization"),
+                "{err}"
+            );
+        }
+
+        // Spend limit is carried over from auto
+        let Ok(SetupValoperMethod::AuthzSend(args)) = resolve_method(
+            SetupValoperMethod::Auto(authz_send_args(&["1000000uosmo"], false)),
+            false,
+        ) else {
+            panic!("expected authz-send");
+        };
+        assert_eq!(args.spend_limit[0].to_string(), "1000000uosmo");
+    }
+
+    fn grant_msg(msg_type_url: String, expiration: Option<i64>) -> CosmosJsonSerializable {
+        MsgGrant {
+            granter: DELEGATOR.to_string(),
+            grantee: CONTROLLER.to_string(),
+            grant: Some(Grant {
+                authorization: Some(
+                    Any::from_msg(&GenericAuthorization { msg: msg_type_url }).unwrap(),
+                ),
+                expiration: expiration.map(|seconds| Timestamp { seconds, nanos: 0 }),
+            }),
+        }
+        .into()
+    }
+
+    fn grant(msg_type_url: String, expiration: Option<i64>) -> GrantInfo {
+        GrantInfo {
+            msg_type_url,
+            expiration: expiration.map(|seconds| Timestamp { seconds, nanos: 0 }),
+            spend_limit: Vec::new(),
+            allow_list: Vec::new(),
+        }
+    }
+
+    /// Messages of `authz-withdraw` setup method
+    fn authz_withdraw_msgs(expiration: Option<i64>) -> Vec<CosmosJsonSerializable> {
+        vec![
+            MsgSetWithdrawAddress {
+                delegator_address: DELEGATOR.to_string(),
+                withdraw_address: CONTROLLER.to_string(),
+            }
+            .into(),
+            grant_msg(MsgWithdrawDelegatorReward::type_url(), expiration),
+            grant_msg(MsgWithdrawValidatorCommission::type_url(), expiration),
+        ]
+    }
+
+    #[test]
+    fn test_diff_changes() {
+        let now = UtcDateTime::now();
+        let future = (now + Duration::from_secs(3600)).unix_timestamp();
+        let past = (now - Duration::from_secs(3600)).unix_timestamp();
+        let diff = |expiration, grants: &[GrantInfo], withdraw_address: &str| {
+            let (msgs, changes) = diff_changes(
+                authz_withdraw_msgs(expiration),
+                grants,
+                Some(withdraw_address.to_string()),
+                now,
+                Duration::ZERO,
+            )
+            .unwrap();
+            (
+                type_urls(&msgs).unwrap(),
+                changes
+                    .iter()
+                    .map(|change| change.to_string())
+                    .collect::<Vec<_>>(),
+            )
+        };
+        let set_withdraw_address = MsgSetWithdrawAddress::type_url();
+        let grant_type_url = MsgGrant::type_url();
+
+        // Nothing is set up yet
+        assert_eq!(
+            diff(None, &[], DELEGATOR),
+            (
+                vec![
+                    set_withdraw_address.clone(),
+                    grant_type_url.clone(),
+                    grant_type_url.clone()
+                ],
+                vec![
+                    format!("set withdraw address {DELEGATOR} -> {CONTROLLER}"),
+                    format!("grant {}", MsgWithdrawDelegatorReward::type_url()),
+                    format!("grant {}", MsgWithdrawValidatorCommission::type_url()),
+                ]
+            )
+        );
+
+        // Everything is in place
+        let existing = [
+            grant(MsgWithdrawDelegatorReward::type_url(), Some(future)),
+            grant(MsgWithdrawValidatorCommission::type_url(), Some(future)),
+        ];
+        assert_eq!(
+            diff(Some(future), &existing, CONTROLLER),
+            (Vec::new(), Vec::new())
+        );
+
+        // Different expiration is requested, grants are re-issued
+        for expiration in [None, Some(future + 60)] {
+            let (msgs, changes) = diff(expiration, &existing, CONTROLLER);
+            assert_eq!(msgs, vec![grant_type_url.clone(), grant_type_url.clone()]);
+            assert_eq!(
+                changes[0],
+                format!("replace grant {}", MsgWithdrawDelegatorReward::type_url())
+            );
+        }
+
+        // Relative expiration resolves to a later time on every run, grants given by an earlier
+        // run are kept within the tolerance
+        let expiration: TimestampStr = "30d".parse().unwrap();
+        let requested = expiration.as_ref().seconds;
+        let tolerance = expiration_tolerance(Some(&expiration));
+        assert_eq!(tolerance, Duration::from_secs(3 * 24 * 3600));
+        assert_eq!(
+            expiration_tolerance(Some(&"2030-01-01T00:00:00Z".parse().unwrap())),
+            Duration::ZERO
+        );
+        let diff_relative = |existing_expiration: i64| {
+            let existing = [
+                grant(
+                    MsgWithdrawDelegatorReward::type_url(),
+                    Some(existing_expiration),
+                ),
+                grant(
+                    MsgWithdrawValidatorCommission::type_url(),
+                    Some(existing_expiration),
+                ),
+            ];
+            let (msgs, _) = diff_changes(
+                authz_withdraw_msgs(Some(requested)),
+                &existing,
+                Some(CONTROLLER.to_string()),
+                now,
+                tolerance,
+            )
+            .unwrap();
+            type_urls(&msgs).unwrap()
+        };
+        // Re-run a day later
+        assert_eq!(diff_relative(requested - 24 * 3600), Vec::<String>::new());
+        // Extended by renew-grants in the meantime
+        assert_eq!(diff_relative(requested + 24 * 3600), Vec::<String>::new());
+        // Grants given too long ago are re-issued
+        assert_eq!(
+            diff_relative(requested - 4 * 24 * 3600),
+            vec![grant_type_url.clone(), grant_type_url.clone()]
+        );
+
+        // Expired grant is replaced, grant authorizing something else is ignored
+        let existing = [
+            grant(MsgWithdrawDelegatorReward::type_url(), Some(past)),
+            grant(MsgWithdrawValidatorCommission::type_url(), None),
+            grant("/cosmos.staking.v1beta1.MsgDelegate".to_string(), None),
+        ];
+        assert_eq!(
+            diff(None, &existing, CONTROLLER),
+            (
+                vec![grant_type_url.clone()],
+                vec![format!(
+                    "replace grant {}",
+                    MsgWithdrawDelegatorReward::type_url()
+                )]
+            )
+        );
+
+        // Withdraw address has to be known when it is set
+        assert!(diff_changes(authz_withdraw_msgs(None), &[], None, now, Duration::ZERO).is_err());
+        assert!(
+            diff_changes(
+                vec![grant_msg(MsgWithdrawDelegatorReward::type_url(), None)],
+                &[],
+                None,
+                now,
+                Duration::ZERO
+            )
+            .is_ok()
+        );
+    }
+
+    #[derive(Parser)]
+    struct SetupValoperCli {
+        #[clap(flatten)]
+        account: AccountArgs,
+
+        #[clap(flatten)]
+        transaction_args: TransactionArgs,
+    }
+
+    #[tokio::test]
+    async fn test_setup_valoper_offline_expiration() {
+        let expiration: TimestampStr = "2030-01-01T00:00:00Z".parse().unwrap();
+        for method in [
+            SetupValoperMethod::AuthzWithdraw,
+            SetupValoperMethod::AuthzSend(authz_send_args(&["1000000uosmo"], false)),
+        ] {
+            let cli = SetupValoperCli::try_parse_from([
+                "setup-valoper",
+                "--delegator-address",
+                DELEGATOR,
+                "--controller-address",
+                CONTROLLER,
+                "--chain-id",
+                "osmosis-1",
+                "--gas",
+                "300000",
+                "--gas-prices",
+                "0.025uosmo",
+                "--generate-only",
+                "--offline",
+            ])
+            .unwrap();
+            let report = setup_valoper(
+                "http://localhost:26657",
+                Some(&"osmo".to_string()),
+                None,
+                cli.account,
+                cli.transaction_args,
+                method,
+                Some(&expiration),
+                &[],
+            )
+            .await
+            .unwrap();
+
+            // Every grant expires as requested, including MsgSend of authz-send
+            let messages = report.unsigned_tx.unwrap()["body"]["messages"].clone();
+            let expirations = messages
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter(|msg| msg["@type"] == MsgGrant::type_url())
+                .map(|msg| msg["grant"]["expiration"].clone())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                expirations,
+                vec![json!("2030-01-01T00:00:00Z"); 2 + (report.method == "authz-send") as usize],
+                "{}",
+                report.method
+            );
+        }
+    }
 }
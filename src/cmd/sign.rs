@@ -0,0 +1,882 @@
+use std::{io::Read, path::Path, path::PathBuf};
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use cosmrs::{
+    AccountId, Coin,
+    crypto::{LegacyAminoMultisig, PublicKey},
+    proto::cosmos::tx::v1beta1::Tx,
+    rpc::{Client, HttpClient},
+    tendermint::Hash,
+    tx::Fee,
+};
+use eyre::{Context, ContextCompat, bail};
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use crate::{
+    AccountArgs,
+    chain::{Bech32Prefixes, ChainInfo},
+    cosmos_sdk_extra::{
+        rpc::get_status,
+        tx::{poll_tx, print_tx_result},
+    },
+    report::BroadcastReport,
+    ser::{CosmosJsonSerializable, amino_sign_doc_bytes},
+    wallet::{
+        MemberSignature, SigningAccountType, TxSignMode, combine_multisig_signatures,
+        construct_transaction_body, setup_multisig_member_signer, setup_signer,
+        sign_multisig_member, sign_transaction, split_eip712_msgs,
+    },
+};
+
+#[derive(Debug, clap::Args)]
+pub struct SignArgs {
+    /// Unsigned transaction JSON file, as printed with `--generate-only`. Use `-` to read from stdin
+    pub file: PathBuf,
+
+    /// Chain id the transaction is signed for
+    #[arg(long, env = "COSMOS_WITHDRAWER_CHAIN_ID")]
+    pub chain_id: String,
+
+    /// Account number of the signing account
+    #[arg(long)]
+    pub account_number: u64,
+
+    /// Sequence number of the signing account
+    #[arg(long)]
+    pub sequence: u64,
+
+    /// Transaction signing mode. Some older chains and external signers only support `amino-json`
+    #[arg(
+        long,
+        env = "COSMOS_WITHDRAWER_TX_SIGN_MODE",
+        value_enum,
+        default_value_t
+    )]
+    pub sign_mode: TxSignMode,
+
+    /// EVM chain id used in the EIP-712 domain. Defaults to the one in Ethermint chain id (e.g. 9001 for evmos_9001-2), or 1 on Injective
+    #[arg(long, env = "COSMOS_WITHDRAWER_TX_EIP712_CHAIN_ID")]
+    pub eip712_chain_id: Option<u64>,
+
+    /// Sign as a member of the delegator multisig account, with the member key given by the delegator key options. Prints a partial signature to be combined with `multisign`, in the format of `tx sign --multisig`
+    #[arg(long, conflicts_with_all = ["sign_mode", "eip712_chain_id"])]
+    pub multisig: bool,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct MultisignArgs {
+    /// Unsigned transaction JSON file, as printed with `--generate-only`. Use `-` to read from stdin
+    pub file: PathBuf,
+
+    /// Partial signature files of multisig members, as printed by `sign --multisig`
+    #[arg(required = true)]
+    pub signature_files: Vec<PathBuf>,
+
+    /// Public key of the multisig account, the `pubkey` field of `keys show <multisig> --output json`, e.g. `{"@type":"/cosmos.crypto.multisig.LegacyAminoPubKey","threshold":2,"public_keys":[...]}`
+    #[arg(long)]
+    pub multisig_public_key: String,
+
+    /// Chain id the transaction is signed for
+    #[arg(long, env = "COSMOS_WITHDRAWER_CHAIN_ID")]
+    pub chain_id: String,
+
+    /// Account number of the multisig account
+    #[arg(long)]
+    pub account_number: u64,
+
+    /// Sequence number of the multisig account
+    #[arg(long)]
+    pub sequence: u64,
+}
+
+/// Signed transaction as printed by `sign` and read by `broadcast`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignedTx {
+    pub chain_id: String,
+    /// Hash of the transaction, as shown by block explorers
+    pub tx_hash: String,
+    /// Base64 encoded protobuf transaction bytes
+    pub tx_bytes: String,
+}
+
+/// Partial signatures of multisig members, in the format of `tx sign --multisig` of Cosmos SDK
+/// chains
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MultisigSignatures {
+    pub signatures: Vec<SignatureDescriptor>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignatureDescriptor {
+    pub public_key: PublicKey,
+    pub data: SignatureData,
+    pub sequence: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignatureData {
+    pub single: SingleSignature,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SingleSignature {
+    pub mode: String,
+    /// Base64 encoded signature
+    pub signature: String,
+}
+
+const SIGN_MODE_LEGACY_AMINO_JSON: &str = "SIGN_MODE_LEGACY_AMINO_JSON";
+
+/// Signs unsigned transaction generated with `--generate-only`, without connecting to the chain.
+/// The signing key is picked by the signer of the transaction messages, which has to be either the
+/// delegator or the controller account.
+pub async fn sign(
+    account_hrp: Option<&String>,
+    valoper_hrp: Option<&String>,
+    account: AccountArgs,
+    args: SignArgs,
+) -> eyre::Result<SignedTx> {
+    let unsigned_tx = read_json(&args.file)?;
+    // JSON output of `withdraw` and `setup-valoper` wraps the transaction into a report
+    let unsigned_tx = unsigned_tx.get("unsigned_tx").unwrap_or(&unsigned_tx);
+
+    let ParsedTx { msgs, memo, fee } = parse_unsigned_tx(unsigned_tx)?;
+    // Checked before any key is loaded, as external signers may prompt for confirmation
+    if args.sign_mode == TxSignMode::Eip712 && split_eip712_msgs(msgs.clone())?.len() > 1 {
+        bail!(
+            "EIP-712 sign mode requires messages of one type, generate a separate transaction for each message type"
+        );
+    }
+
+    let prefix = account_hrp
+        .map(String::as_str)
+        .unwrap_or(account.controller_address.prefix());
+    let bech32 = Bech32Prefixes::parse(prefix, valoper_hrp)?;
+
+    let signing_account_type = signing_account_type(&account, &msgs, &args)?;
+    info!(
+        account = signing_account_type.type_name(),
+        chain_id = args.chain_id,
+        "signing transaction"
+    );
+
+    let chain_info = ChainInfo {
+        id: args.chain_id.parse().wrap_err("invalid chain id")?,
+        // Not needed for signing
+        chain_supports_setting_withdrawal_address: true,
+        bech32,
+    };
+    let signer = setup_signer(&account, &chain_info.bech32, signing_account_type, false)
+        .await?
+        .with_sign_mode(args.sign_mode)
+        .with_eip712_chain_id(args.eip712_chain_id);
+
+    let signed_tx = sign_transaction(
+        &chain_info,
+        &signer,
+        fee,
+        construct_transaction_body(&memo, &msgs)?,
+    )
+    .await
+    .wrap_err("failed to sign transaction")?;
+    let tx_bytes = Tx::from(signed_tx).encode_to_vec();
+
+    Ok(SignedTx {
+        chain_id: args.chain_id,
+        tx_hash: tx_hash(&tx_bytes).to_string(),
+        tx_bytes: BASE64_STANDARD.encode(&tx_bytes),
+    })
+}
+
+/// Signs unsigned transaction of the delegator multisig account with a member key, without
+/// connecting to the chain. Members always sign amino JSON, as `SIGN_MODE_DIRECT` sign bytes would
+/// have to include the set of signing members.
+pub async fn sign_multisig(
+    account: AccountArgs,
+    args: SignArgs,
+) -> eyre::Result<MultisigSignatures> {
+    let unsigned_tx = read_json(&args.file)?;
+    // JSON output of `setup-valoper` and others wraps the transaction into a report
+    let unsigned_tx = unsigned_tx.get("unsigned_tx").unwrap_or(&unsigned_tx);
+
+    let ParsedTx { msgs, memo, fee } = parse_unsigned_tx(unsigned_tx)?;
+    let SigningAccountType::Delegator { .. } = signing_account_type(&account, &msgs, &args)? else {
+        bail!(
+            "only the delegator can be a multisig account, transaction is signed by the controller"
+        );
+    };
+    info!(
+        chain_id = args.chain_id,
+        "signing transaction as multisig member"
+    );
+
+    let sign_bytes = amino_sign_doc_bytes(
+        &args.chain_id,
+        args.account_number,
+        args.sequence,
+        &fee,
+        &memo,
+        &msgs,
+    )
+    .wrap_err("failed to create amino JSON sign doc")?;
+    let member = setup_multisig_member_signer(&account).await?;
+    let MemberSignature {
+        public_key,
+        signature,
+    } = sign_multisig_member(member.as_ref(), &sign_bytes)
+        .await
+        .wrap_err("failed to sign transaction")?;
+
+    Ok(MultisigSignatures {
+        signatures: vec![SignatureDescriptor {
+            public_key,
+            data: SignatureData {
+                single: SingleSignature {
+                    mode: SIGN_MODE_LEGACY_AMINO_JSON.to_string(),
+                    signature: BASE64_STANDARD.encode(signature),
+                },
+            },
+            sequence: args.sequence.to_string(),
+        }],
+    })
+}
+
+/// Combines partial signatures of multisig members, printed by `sign --multisig`, into a signed
+/// transaction for `broadcast`. Every signature is verified against the multisig public key.
+pub fn multisign(args: MultisignArgs) -> eyre::Result<SignedTx> {
+    let unsigned_tx = read_json(&args.file)?;
+    // JSON output of `setup-valoper` and others wraps the transaction into a report
+    let unsigned_tx = unsigned_tx.get("unsigned_tx").unwrap_or(&unsigned_tx);
+
+    let ParsedTx { msgs, memo, fee } = parse_unsigned_tx(unsigned_tx)?;
+    let multisig = parse_multisig_public_key(&args.multisig_public_key)?;
+
+    let mut member_signatures = Vec::new();
+    for path in args.signature_files.iter() {
+        let signatures: MultisigSignatures = serde_json::from_value(read_json(path)?)
+            .wrap_err_with(|| format!("failed to parse signature file '{}'", path.display()))?;
+
+        for SignatureDescriptor {
+            public_key,
+            data: SignatureData { single },
+            sequence,
+        } in signatures.signatures
+        {
+            if single.mode != SIGN_MODE_LEGACY_AMINO_JSON {
+                bail!(
+                    "signature in '{}' is made in {}, multisig members have to sign in {SIGN_MODE_LEGACY_AMINO_JSON}",
+                    path.display(),
+                    single.mode
+                );
+            }
+            if sequence != args.sequence.to_string() {
+                bail!(
+                    "signature in '{}' is made for sequence {sequence}, expected {}",
+                    path.display(),
+                    args.sequence
+                );
+            }
+
+            member_signatures.push(MemberSignature {
+                public_key,
+                signature: BASE64_STANDARD
+                    .decode(&single.signature)
+                    .wrap_err_with(|| {
+                        format!("failed to decode signature in '{}'", path.display())
+                    })?,
+            });
+        }
+    }
+
+    let sign_bytes = amino_sign_doc_bytes(
+        &args.chain_id,
+        args.account_number,
+        args.sequence,
+        &fee,
+        &memo,
+        &msgs,
+    )
+    .wrap_err("failed to create amino JSON sign doc")?;
+    let signed_tx = combine_multisig_signatures(
+        &multisig,
+        member_signatures,
+        &sign_bytes,
+        args.sequence,
+        fee,
+        construct_transaction_body(&memo, &msgs)?,
+    )?;
+    let tx_bytes = Tx::from(signed_tx).encode_to_vec();
+
+    Ok(SignedTx {
+        chain_id: args.chain_id,
+        tx_hash: tx_hash(&tx_bytes).to_string(),
+        tx_bytes: BASE64_STANDARD.encode(&tx_bytes),
+    })
+}
+
+/// Broadcasts transaction signed with `sign` and waits until it's committed to the chain
+pub async fn broadcast(rpc_url: &str, file: &Path) -> eyre::Result<BroadcastReport> {
+    let signed_tx: SignedTx = serde_json::from_value(read_json(file)?)
+        .wrap_err_with(|| format!("failed to parse signed transaction '{}'", file.display()))?;
+    let tx_bytes = BASE64_STANDARD
+        .decode(&signed_tx.tx_bytes)
+        .wrap_err("failed to decode transaction bytes")?;
+
+    let tx_hash = tx_hash(&tx_bytes);
+    if tx_hash.to_string() != signed_tx.tx_hash {
+        bail!(
+            "transaction hash {tx_hash} does not match '{}', signed transaction file is corrupted",
+            signed_tx.tx_hash
+        );
+    }
+
+    let client = HttpClient::new(rpc_url)?;
+    let status = get_status(&client)
+        .await
+        .wrap_err("failed to get chain status")?;
+    if status.node_info.network.as_str() != signed_tx.chain_id {
+        bail!(
+            "transaction is signed for chain '{}', but the node is on '{}'",
+            signed_tx.chain_id,
+            status.node_info.network
+        );
+    }
+
+    let tx_result = client.broadcast_tx_sync(tx_bytes).await?;
+    print_tx_result(&tx_result)?;
+
+    let tx_response = poll_tx(&client, tx_result.hash).await?;
+    info!(tx_hash = ?tx_response.hash, "transaction committed to chain");
+
+    Ok(BroadcastReport {
+        chain_id: signed_tx.chain_id,
+        tx_hash: tx_response.hash.to_string(),
+        height: tx_response.height.value(),
+        gas_wanted: tx_response.tx_result.gas_wanted,
+        gas_used: tx_response.tx_result.gas_used,
+    })
+}
+
+/// Messages, memo & fee of an unsigned transaction
+pub(super) struct ParsedTx {
+    pub msgs: Vec<CosmosJsonSerializable>,
+    pub memo: String,
+    pub fee: Fee,
+}
+
+/// Parses unsigned transaction JSON, as generated with `--generate-only`
+pub(super) fn parse_unsigned_tx(unsigned_tx: &Value) -> eyre::Result<ParsedTx> {
+    let body = unsigned_tx
+        .get("body")
+        .wrap_err("unsigned transaction has no body")?;
+    let msgs = body
+        .get("messages")
+        .and_then(Value::as_array)
+        .filter(|msgs| !msgs.is_empty())
+        .wrap_err("unsigned transaction has no messages")?
+        .iter()
+        .map(CosmosJsonSerializable::try_from)
+        .collect::<eyre::Result<Vec<_>>>()
+        .wrap_err("failed to parse unsigned transaction messages")?;
+    let memo = body
+        .get("memo")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+    let fee = parse_fee(unsigned_tx).wrap_err("failed to parse unsigned transaction fee")?;
+
+    Ok(ParsedTx { msgs, memo, fee })
+}
+
+pub(super) fn read_json(path: &Path) -> eyre::Result<Value> {
+    let data = if path == Path::new("-") {
+        let mut data = String::new();
+        std::io::stdin()
+            .read_to_string(&mut data)
+            .wrap_err("failed to read stdin")?;
+        data
+    } else {
+        std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("failed to read '{}'", path.display()))?
+    };
+
+    serde_json::from_str(&data).wrap_err_with(|| format!("failed to parse '{}'", path.display()))
+}
+
+fn parse_fee(unsigned_tx: &Value) -> eyre::Result<Fee> {
+    let fee = unsigned_tx
+        .get("auth_info")
+        .and_then(|auth_info| auth_info.get("fee"))
+        .wrap_err("fee is missing")?;
+
+    for field in ["payer", "granter"] {
+        if fee
+            .get(field)
+            .and_then(Value::as_str)
+            .is_some_and(|value| !value.is_empty())
+        {
+            bail!("fee {field} is not supported");
+        }
+    }
+
+    let amount = fee
+        .get("amount")
+        .and_then(Value::as_array)
+        .wrap_err("fee amount is missing")?
+        .iter()
+        .map(|coin| {
+            let denom = coin
+                .get("denom")
+                .and_then(Value::as_str)
+                .wrap_err("fee denom is missing")?;
+            let amount = coin
+                .get("amount")
+                .and_then(Value::as_str)
+                .wrap_err("fee amount is missing")?;
+
+            Ok(Coin {
+                denom: denom.parse()?,
+                amount: amount
+                    .parse()
+                    .wrap_err_with(|| format!("invalid fee amount '{amount}'"))?,
+            })
+        })
+        .collect::<eyre::Result<Vec<_>>>()?;
+    let gas_limit = fee
+        .get("gas_limit")
+        .and_then(Value::as_str)
+        .wrap_err("gas limit is missing")?
+        .parse()
+        .wrap_err("invalid gas limit")?;
+
+    Ok(Fee {
+        amount,
+        gas_limit,
+        payer: None,
+        granter: None,
+    })
+}
+
+fn signing_account_type(
+    account: &AccountArgs,
+    msgs: &[CosmosJsonSerializable],
+    args: &SignArgs,
+) -> eyre::Result<SigningAccountType> {
+    let mut signers = msgs.iter().filter_map(CosmosJsonSerializable::signer);
+    let signer = signers.next().wrap_err("transaction has no signer")?;
+    if let Some(other) = signers.find(|other| *other != signer) {
+        bail!("transactions with multiple signers are not supported, got '{signer}' and '{other}'");
+    }
+
+    // Commission is withdrawn with the valoper address, so only the address bytes are compared
+    let signer: AccountId = signer
+        .parse()
+        .wrap_err_with(|| format!("invalid signer address '{signer}'"))?;
+    if signer.to_bytes() == account.controller_address.to_bytes() {
+        Ok(SigningAccountType::Controller {
+            key_type: account.controller_address_type.unwrap_or_default(),
+            account_number: args.account_number,
+            sequence: args.sequence,
+        })
+    } else if signer.to_bytes() == account.delegator_address.to_bytes() {
+        Ok(SigningAccountType::Delegator {
+            key_type: account.delegator_address_type.unwrap_or_default(),
+            account_number: args.account_number,
+            sequence: args.sequence,
+        })
+    } else {
+        bail!("transaction signer '{signer}' is neither the delegator nor the controller address")
+    }
+}
+
+/// Parses `LegacyAminoPubKey` in the JSON format of `keys show --output json`
+fn parse_multisig_public_key(json: &str) -> eyre::Result<LegacyAminoMultisig> {
+    let value: Value = serde_json::from_str(json).wrap_err("invalid multisig public key JSON")?;
+    let type_url = value
+        .get("@type")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    if type_url != LegacyAminoMultisig::TYPE_URL {
+        bail!(
+            "multisig public key has to be '{}', got '{type_url}'",
+            LegacyAminoMultisig::TYPE_URL
+        );
+    }
+
+    let threshold = value
+        .get("threshold")
+        .and_then(Value::as_u64)
+        .wrap_err("multisig public key has no threshold")?;
+    let public_keys = value
+        .get("public_keys")
+        .and_then(Value::as_array)
+        .wrap_err("multisig public key has no member public keys")?
+        .iter()
+        .map(|public_key| {
+            serde_json::from_value(public_key.clone())
+                .wrap_err_with(|| format!("invalid member public key {public_key}"))
+        })
+        .collect::<eyre::Result<Vec<PublicKey>>>()?;
+
+    Ok(LegacyAminoMultisig {
+        threshold: threshold
+            .try_into()
+            .wrap_err("invalid multisig threshold")?,
+        public_keys,
+    })
+}
+
+/// Tendermint transaction hash, SHA256 of the transaction bytes
+fn tx_hash(tx_bytes: &[u8]) -> Hash {
+    Hash::Sha256(Sha256::digest(tx_bytes).into())
+}
+
+#[cfg(test)]
+mod test {
+    use base64::{Engine, prelude::BASE64_STANDARD};
+    use bip32::secp256k1::ecdsa::{Signature, SigningKey, VerifyingKey, signature::Verifier};
+    use clap::Parser;
+    use cosmrs::{
+        AccountId, Coin,
+        crypto::PublicKey,
+        proto::{
+            cosmos::{
+                crypto::multisig::v1beta1::{CompactBitArray, MultiSignature},
+                distribution::v1beta1::MsgWithdrawDelegatorReward,
+                tx::v1beta1::{Tx, mode_info},
+            },
+            prost::{Message, Name},
+        },
+        tx::Fee,
+    };
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::{MultisignArgs, SignArgs, multisign, sign, sign_multisig};
+    use crate::{
+        AccountArgs,
+        cosmos_sdk_extra::{
+            injective::{EthPubKey, ExtensionOptionsWeb3Tx},
+            tx::generate_unsigned_tx_json,
+        },
+        ser::{CosmosJsonSerializable, MsgExecCustom, amino_sign_doc_bytes},
+    };
+
+    #[derive(Parser)]
+    struct SignCli {
+        #[clap(flatten)]
+        account: AccountArgs,
+
+        #[clap(flatten)]
+        args: SignArgs,
+    }
+
+    #[derive(Parser)]
+    struct MultisignCli {
+        #[clap(flatten)]
+        args: MultisignArgs,
+    }
+
+    #[tokio::test]
+    async fn test_sign_multisig() {
+        let keys = [1u8, 2, 3].map(|byte| SigningKey::from_slice(&[byte; 32]).unwrap());
+        let public_keys = keys
+            .iter()
+            .map(|key| PublicKey::from(key.verifying_key()))
+            .collect::<Vec<_>>();
+        let multisig_public_key = json!({
+            "@type": "/cosmos.crypto.multisig.LegacyAminoPubKey",
+            "threshold": 2,
+            "public_keys": public_keys
+                .iter()
+                .map(|public_key| serde_json::to_value(public_key).unwrap())
+                .collect::<Vec<_>>(),
+        })
+        .to_string();
+        // Address of the multisig is not derived, chain rejects transactions with a different public key
+        let delegator = AccountId::new("cosmos", &[1; 20]).unwrap().to_string();
+        let controller = AccountId::new("cosmos", &[2; 20]).unwrap().to_string();
+
+        let msgs: Vec<CosmosJsonSerializable> = vec![
+            MsgWithdrawDelegatorReward {
+                delegator_address: delegator.clone(),
+                validator_address: AccountId::new("cosmosvaloper", &[3; 20])
+                    .unwrap()
+                    .to_string(),
+            }
+            .into(),
+        ];
+        let unsigned_tx = generate_unsigned_tx_json(
+            msgs.clone(),
+            "cosmos-withdrawer",
+            200_000,
+            vec![Coin {
+                denom: "uatom".parse().unwrap(),
+                amount: 5000,
+            }],
+        );
+        let dir = std::env::temp_dir().join(format!(
+            "cosmos-withdrawer-sign-multisig-test-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let unsigned_tx_path = dir.join("unsigned.json");
+        std::fs::write(&unsigned_tx_path, unsigned_tx.to_string()).unwrap();
+
+        let mut signature_paths = Vec::new();
+        for (member, sequence) in [(3u8, "7"), (1, "7"), (2, "8")] {
+            let cli = SignCli::try_parse_from([
+                "sign",
+                "--multisig",
+                "--delegator-address",
+                &delegator,
+                "--controller-address",
+                &controller,
+                "--delegator-private-key",
+                &hex::encode([member; 32]),
+                "--chain-id",
+                "cosmoshub-4",
+                "--account-number",
+                "42",
+                "--sequence",
+                sequence,
+                unsigned_tx_path.to_str().unwrap(),
+            ])
+            .unwrap();
+            let signatures = sign_multisig(cli.account, cli.args).await.unwrap();
+            assert_eq!(signatures.signatures.len(), 1);
+            assert_eq!(
+                signatures.signatures[0].data.single.mode,
+                "SIGN_MODE_LEGACY_AMINO_JSON"
+            );
+            assert_eq!(
+                signatures.signatures[0].public_key,
+                public_keys[member as usize - 1]
+            );
+
+            let path = dir.join(format!("member-{member}.json"));
+            std::fs::write(&path, serde_json::to_string(&signatures).unwrap()).unwrap();
+            signature_paths.push(path);
+        }
+
+        let combine = |signature_paths: &[std::path::PathBuf]| {
+            let mut args = vec![
+                "multisign".to_string(),
+                unsigned_tx_path.to_str().unwrap().to_string(),
+            ];
+            args.extend(
+                signature_paths
+                    .iter()
+                    .map(|path| path.to_str().unwrap().to_string()),
+            );
+            args.extend(
+                [
+                    "--multisig-public-key",
+                    &multisig_public_key,
+                    "--chain-id",
+                    "cosmoshub-4",
+                    "--account-number",
+                    "42",
+                    "--sequence",
+                    "7",
+                ]
+                .map(str::to_string),
+            );
+            multisign(MultisignCli::try_parse_from(args).unwrap().args)
+        };
+
+        // Not enough signatures
+        let err = combine(&signature_paths[..1]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "2-of-3 multisig requires 2 member signatures, got 1"
+        );
+
+        // Signature made for a different sequence
+        let err = combine(&signature_paths).unwrap_err();
+        assert!(
+            err.to_string()
+                .ends_with("is made for sequence 8, expected 7"),
+            "{err}"
+        );
+
+        let signed_tx = combine(&signature_paths[..2]).unwrap();
+        let tx = Tx::decode(
+            BASE64_STANDARD
+                .decode(signed_tx.tx_bytes)
+                .unwrap()
+                .as_slice(),
+        )
+        .unwrap();
+        let signer_info = &tx.auth_info.as_ref().unwrap().signer_infos[0];
+        assert_eq!(signer_info.sequence, 7);
+        let mode_info::Sum::Multi(multi) = signer_info.mode_info.clone().unwrap().sum.unwrap()
+        else {
+            panic!("expected multisig mode info");
+        };
+        assert_eq!(
+            multi.bitarray,
+            Some(CompactBitArray {
+                extra_bits_stored: 3,
+                elems: vec![0b1010_0000],
+            })
+        );
+
+        // Signatures are ordered by member index, whatever the order of signature files
+        let fee = Fee::from_amount_and_gas(
+            Coin {
+                denom: "uatom".parse().unwrap(),
+                amount: 5000,
+            },
+            200_000_u64,
+        );
+        let sign_bytes =
+            amino_sign_doc_bytes("cosmoshub-4", 42, 7, &fee, "cosmos-withdrawer", &msgs).unwrap();
+        let multi_signature = MultiSignature::decode(tx.signatures[0].as_slice()).unwrap();
+        assert_eq!(multi_signature.signatures.len(), 2);
+        for (signature, key) in multi_signature.signatures.iter().zip([&keys[0], &keys[2]]) {
+            VerifyingKey::from(key)
+                .verify(&sign_bytes, &Signature::from_slice(signature).unwrap())
+                .unwrap();
+        }
+
+        // Not a member
+        let cli = SignCli::try_parse_from([
+            "sign",
+            "--multisig",
+            "--delegator-address",
+            &delegator,
+            "--controller-address",
+            &controller,
+            "--delegator-private-key",
+            &hex::encode([4u8; 32]),
+            "--chain-id",
+            "cosmoshub-4",
+            "--account-number",
+            "42",
+            "--sequence",
+            "7",
+            unsigned_tx_path.to_str().unwrap(),
+        ])
+        .unwrap();
+        let signatures = sign_multisig(cli.account, cli.args).await.unwrap();
+        let outsider_path = dir.join("outsider.json");
+        std::fs::write(&outsider_path, serde_json::to_string(&signatures).unwrap()).unwrap();
+        let err = combine(&[signature_paths[0].clone(), outsider_path]).unwrap_err();
+        assert!(
+            err.to_string()
+                .ends_with("is not made by a member of the multisig"),
+            "{err}"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sign_injective() {
+        // Don't worry, it's not a real wallet
+        let mnemonic = "relief raise grow sketch turtle endless lens replace morning symptom short coin cousin hospital sauce foam stumble wife kind tortoise member heavy web render";
+        let controller = "inj19lhpj24vqtglud7kd7e4n3zj8z4lxkl7ex3uv0";
+        let delegator = "inj1e4n3yara98z8lsxwcj7740q0nku3lcd7dzpyw0";
+
+        let msgs: Vec<CosmosJsonSerializable> = vec![
+            MsgExecCustom {
+                grantee: controller.to_string(),
+                msgs: vec![
+                    MsgWithdrawDelegatorReward {
+                        delegator_address: delegator.to_string(),
+                        validator_address: "injvaloper1e4n3yara98z8lsxwcj7740q0nku3lcd76y5p0t"
+                            .to_string(),
+                    }
+                    .into(),
+                ],
+            }
+            .into(),
+        ];
+        let unsigned_tx = generate_unsigned_tx_json(
+            msgs,
+            "cosmos-withdrawer",
+            200_000,
+            vec![Coin {
+                denom: "inj".parse().unwrap(),
+                amount: 100_000_000_000_000,
+            }],
+        );
+        let path = std::env::temp_dir().join(format!(
+            "cosmos-withdrawer-sign-injective-test-{}.json",
+            std::process::id()
+        ));
+        std::fs::write(&path, unsigned_tx.to_string()).unwrap();
+
+        let signed_tx = |sign_mode: &str| {
+            let cli = SignCli::try_parse_from([
+                "sign",
+                "--delegator-address",
+                delegator,
+                "--controller-address",
+                controller,
+                "--controller-address-type",
+                "eth_secp256k1_injective",
+                "--controller-mnemonic",
+                mnemonic,
+                "--controller-mnemonic-coin-type",
+                "60",
+                "--chain-id",
+                "injective-1",
+                "--account-number",
+                "42",
+                "--sequence",
+                "7",
+                "--sign-mode",
+                sign_mode,
+                path.to_str().unwrap(),
+            ])
+            .unwrap();
+
+            async move {
+                let signed_tx = sign(None, None, cli.account, cli.args).await.unwrap();
+                Tx::decode(
+                    BASE64_STANDARD
+                        .decode(signed_tx.tx_bytes)
+                        .unwrap()
+                        .as_slice(),
+                )
+                .unwrap()
+            }
+        };
+
+        let direct = signed_tx("direct").await;
+        let eip712 = signed_tx("eip712").await;
+        std::fs::remove_file(&path).unwrap();
+
+        for tx in [&direct, &eip712] {
+            let public_key = tx.auth_info.as_ref().unwrap().signer_infos[0]
+                .public_key
+                .as_ref()
+                .unwrap();
+            assert_eq!(
+                public_key.type_url,
+                "/injective.crypto.v1beta1.ethsecp256k1.PubKey"
+            );
+            assert_eq!(
+                EthPubKey::decode(public_key.value.as_slice())
+                    .unwrap()
+                    .key
+                    .len(),
+                33
+            );
+        }
+
+        // Injective EIP-712 domain is used, with Ethereum mainnet chain id by default
+        let extension = &eip712.body.as_ref().unwrap().extension_options[0];
+        assert_eq!(extension.type_url, ExtensionOptionsWeb3Tx::type_url());
+        let extension = ExtensionOptionsWeb3Tx::decode(extension.value.as_slice()).unwrap();
+        assert_eq!(extension.typed_data_chain_id, 1);
+        assert_eq!(extension.fee_payer, controller);
+        assert_eq!(extension.fee_payer_sig.len(), 65);
+    }
+}
@@ -0,0 +1,257 @@
+use std::{str::FromStr, time::Duration};
+
+use cosmrs::{
+    AccountId,
+    proto::cosmos::{
+        distribution::v1beta1::QueryDelegationTotalRewardsRequest,
+        staking::v1beta1::QueryDelegatorDelegationsRequest,
+    },
+    rpc::HttpClient,
+};
+use eyre::Context;
+use serde::Serialize;
+use time::format_description::well_known::Rfc3339;
+use time::{OffsetDateTime, UtcDateTime};
+use tracing::{info, trace};
+
+use crate::{
+    AccountArgs,
+    chain::{
+        GrantInfo, expiring_grants, get_balances, get_chain_info, get_grants,
+        get_validator_commission, get_withdraw_address,
+    },
+    cosmos_sdk_extra::abci_query::{
+        QueryDelegationTotalRewards, QueryDelegatorDelegations, execute_abci_query,
+    },
+    report::{CoinAmount, OutputFormat},
+};
+
+#[derive(Debug, Serialize)]
+pub struct StatusReport {
+    pub chain_id: String,
+    pub delegator_address: String,
+    pub controller_address: String,
+    /// Address delegation rewards & commissions are withdrawn to
+    pub withdraw_address: String,
+    /// Grants given by the delegator to the controller
+    pub grants: Vec<GrantInfo>,
+    /// Grants which expire within the warning window, or have expired
+    pub expiring_grants: Vec<GrantInfo>,
+    /// Pending delegation rewards, per validator. Amounts are Cosmos SDK Dec, scaled by 10^18
+    pub rewards: Vec<ValidatorRewards>,
+    /// Pending commission, if the delegator is a validator operator. Amounts are Cosmos SDK Dec, scaled by 10^18
+    pub commission: Vec<CoinAmount>,
+    pub delegations: Vec<DelegationStatus>,
+    pub controller_balance: Vec<CoinAmount>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ValidatorRewards {
+    pub validator_address: String,
+    pub rewards: Vec<CoinAmount>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DelegationStatus {
+    pub validator_address: String,
+    pub shares: String,
+    pub balance: Option<CoinAmount>,
+}
+
+/// Shows grants, withdraw address, pending rewards & commissions, delegations, and controller
+/// balance. Does not need any keys, nor sends any transactions
+pub async fn status(
+    rpc_url: &str,
+    account_hrp: Option<&String>,
+    valoper_hrp: Option<&String>,
+    account: AccountArgs,
+    grant_expiry_warning: Duration,
+    output: OutputFormat,
+) -> eyre::Result<()> {
+    let client = HttpClient::new(rpc_url)?;
+    let chain_info = get_chain_info(&client, account_hrp, valoper_hrp).await?;
+    account.verify_accounts(&chain_info)?;
+
+    info!(?chain_info, "chain info");
+
+    let grants = get_grants(
+        &client,
+        &account.delegator_address,
+        &account.controller_address,
+    )
+    .await?;
+    let expiring_grants = expiring_grants(&grants, grant_expiry_warning);
+
+    let withdraw_address = get_withdraw_address(&client, &account.delegator_address).await?;
+
+    let delegation_total_rewards = execute_abci_query::<QueryDelegationTotalRewards>(
+        &client,
+        QueryDelegationTotalRewardsRequest {
+            delegator_address: account.delegator_address.to_string(),
+        },
+    )
+    .await
+    .wrap_err("failed to query delegation rewards")?;
+
+    trace!(?delegation_total_rewards, "available rewards");
+
+    let mut rewards = Vec::new();
+    let mut commission = Vec::new();
+    for reward in delegation_total_rewards.rewards {
+        let validator_address = AccountId::from_str(&reward.validator_address)
+            .wrap_err("failed to parse validator address")?;
+
+        // Only the validator operator has commissions
+        if validator_address.to_bytes() == account.delegator_address.to_bytes() {
+            commission = get_validator_commission(&client, &validator_address)
+                .await?
+                .unwrap_or_default()
+                .into_iter()
+                .map(|coin| CoinAmount {
+                    denom: coin.denom,
+                    amount: coin.amount,
+                })
+                .collect();
+        }
+
+        rewards.push(ValidatorRewards {
+            validator_address: reward.validator_address,
+            rewards: reward
+                .reward
+                .into_iter()
+                .map(|coin| CoinAmount {
+                    denom: coin.denom,
+                    amount: coin.amount,
+                })
+                .collect(),
+        });
+    }
+
+    let delegations = execute_abci_query::<QueryDelegatorDelegations>(
+        &client,
+        QueryDelegatorDelegationsRequest {
+            delegator_addr: account.delegator_address.to_string(),
+            pagination: None,
+        },
+    )
+    .await
+    .wrap_err("failed to query delegations")?
+    .delegation_responses
+    .into_iter()
+    .filter_map(|response| {
+        let delegation = response.delegation?;
+        Some(DelegationStatus {
+            validator_address: delegation.validator_address,
+            shares: delegation.shares,
+            balance: response.balance.map(|coin| CoinAmount {
+                denom: coin.denom,
+                amount: coin.amount,
+            }),
+        })
+    })
+    .collect();
+
+    let controller_balance = get_balances(&client, &account.controller_address)
+        .await?
+        .into_iter()
+        .map(|coin| CoinAmount {
+            denom: coin.denom,
+            amount: coin.amount,
+        })
+        .collect();
+
+    let report = StatusReport {
+        chain_id: chain_info.id.to_string(),
+        delegator_address: account.delegator_address.to_string(),
+        controller_address: account.controller_address.to_string(),
+        withdraw_address,
+        grants,
+        expiring_grants,
+        rewards,
+        commission,
+        delegations,
+        controller_balance,
+    };
+
+    match output {
+        OutputFormat::Text => print_text(&report),
+        OutputFormat::Json => output.print_report(&report, None)?,
+    }
+
+    Ok(())
+}
+
+fn print_text(report: &StatusReport) {
+    let format_coins = |coins: &[CoinAmount]| {
+        if coins.is_empty() {
+            return "-".to_string();
+        }
+
+        coins
+            .iter()
+            .map(|coin| format!("{}{}", coin.amount, coin.denom))
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+
+    println!("chain id:           {}", report.chain_id);
+    println!("delegator address:  {}", report.delegator_address);
+    println!("controller address: {}", report.controller_address);
+    println!("withdraw address:   {}", report.withdraw_address);
+    println!(
+        "controller balance: {}",
+        format_coins(&report.controller_balance)
+    );
+    println!("commission:         {}", format_coins(&report.commission));
+
+    println!();
+    println!(
+        "{:<60} {:<25} {:<10} SPEND LIMIT",
+        "GRANT", "EXPIRATION", "STATUS"
+    );
+    let now = UtcDateTime::now();
+    for grant in report.grants.iter() {
+        let expiration = grant
+            .expiration
+            .as_ref()
+            .and_then(|expiration| OffsetDateTime::from_unix_timestamp(expiration.seconds).ok())
+            .and_then(|expiration| expiration.format(&Rfc3339).ok())
+            .unwrap_or_else(|| "never".to_string());
+        let status = if grant.is_expired(now) {
+            "expired"
+        } else if report.expiring_grants.contains(grant) {
+            "expiring"
+        } else {
+            "ok"
+        };
+        let spend_limit = match grant.spend_limit.as_slice() {
+            [] => "-".to_string(),
+            spend_limit => format_coins(spend_limit),
+        };
+        println!(
+            "{:<60} {:<25} {:<10} {}",
+            grant.msg_type_url, expiration, status, spend_limit
+        );
+    }
+
+    println!();
+    println!("{:<60} REWARDS", "VALIDATOR");
+    for reward in report.rewards.iter() {
+        println!(
+            "{:<60} {}",
+            reward.validator_address,
+            format_coins(&reward.rewards)
+        );
+    }
+
+    println!();
+    println!("{:<60} {:<40} SHARES", "VALIDATOR", "DELEGATION");
+    for delegation in report.delegations.iter() {
+        println!(
+            "{:<60} {:<40} {}",
+            delegation.validator_address,
+            format_coins(delegation.balance.as_slice()),
+            delegation.shares
+        );
+    }
+}
@@ -0,0 +1,282 @@
+use std::path::PathBuf;
+
+use cosmrs::{
+    AccountId,
+    proto::cosmos::{authz::v1beta1::MsgRevoke, distribution::v1beta1::MsgSetWithdrawAddress},
+    rpc::HttpClient,
+};
+use eyre::bail;
+use time::UtcDateTime;
+use tracing::{debug, info};
+
+use crate::{
+    AccountArgs, TransactionArgs,
+    chain::{GrantInfo, get_chain_info, get_grants, get_withdraw_address},
+    cmd::setup_valoper::{DelegatorTx, delegator_tx},
+    cosmos_sdk_extra::gas::GasInfo,
+    report::{TeardownReport, TeardownStatus, TxKind},
+    ser::CosmosJsonSerializable,
+};
+
+/// Undoes `setup-valoper`: revokes grants given by the delegator to the controller, and resets the
+/// withdraw address back to the delegator
+pub async fn teardown(
+    rpc_url: &str,
+    account_hrp: Option<&String>,
+    valoper_hrp: Option<&String>,
+    account: AccountArgs,
+    transaction_args: TransactionArgs,
+    multisig_member_mnemonic_files: &[PathBuf],
+) -> eyre::Result<TeardownReport> {
+    if transaction_args.offline {
+        bail!("teardown needs to query existing grants, --offline is not supported");
+    }
+
+    let client = HttpClient::new(rpc_url)?;
+    let chain_info = get_chain_info(&client, account_hrp, valoper_hrp).await?;
+    let gas_info = GasInfo::determine_gas(&chain_info, &transaction_args)?;
+
+    info!(?chain_info, ?gas_info.denom, ?gas_info.price, "chain info");
+
+    // Held from before the chain state is queried until the transaction is committed, so that
+    // concurrent runs neither act on the same state nor race with withdrawals
+    let lock = transaction_args
+        .lock_account(chain_info.id.as_str(), &account.controller_address)
+        .await?;
+
+    let mut report = TeardownReport {
+        chain_id: chain_info.id.to_string(),
+        ..Default::default()
+    };
+
+    let grants = get_grants(
+        &client,
+        &account.delegator_address,
+        &account.controller_address,
+    )
+    .await?;
+    let withdraw_address = if chain_info.chain_supports_setting_withdrawal_address {
+        Some(get_withdraw_address(&client, &account.delegator_address).await?)
+    } else {
+        None
+    };
+
+    let msgs = teardown_msgs(
+        &account.delegator_address,
+        &account.controller_address,
+        grants,
+        withdraw_address,
+        UtcDateTime::now(),
+        &mut report,
+    );
+
+    if msgs.is_empty() {
+        info!("no grants to revoke, and withdraw address is already the delegator");
+        report.status = TeardownStatus::NothingToTeardown;
+        return Ok(report);
+    }
+
+    info!(
+        revoked = ?report.revoked,
+        previous_withdraw_address = ?report.previous_withdraw_address,
+        "tearing down valoper account setup"
+    );
+
+    match delegator_tx(
+        &client,
+        &chain_info,
+        &gas_info,
+        &account,
+        &transaction_args,
+        &lock,
+        TxKind::Teardown,
+        msgs,
+        multisig_member_mnemonic_files,
+    )
+    .await?
+    {
+        DelegatorTx::Generated(unsigned_tx) => {
+            report.status = TeardownStatus::Generated;
+            report.unsigned_tx = Some(unsigned_tx);
+        }
+        DelegatorTx::DryRun => report.status = TeardownStatus::DryRun,
+        DelegatorTx::Committed(tx_reports) => {
+            report.status = TeardownStatus::Submitted;
+            report.transactions.extend(tx_reports);
+        }
+    }
+
+    Ok(report)
+}
+
+/// Builds messages revoking given grants, and resetting the withdraw address back to the
+/// delegator. Withdraw address is `None` on chains which do not support setting it
+fn teardown_msgs(
+    delegator_address: &AccountId,
+    controller_address: &AccountId,
+    grants: Vec<GrantInfo>,
+    withdraw_address: Option<String>,
+    now: UtcDateTime,
+    report: &mut TeardownReport,
+) -> Vec<CosmosJsonSerializable> {
+    let mut msgs: Vec<CosmosJsonSerializable> = Vec::new();
+    for grant in grants {
+        // Expired grants cannot be revoked, they are pruned by the chain
+        if grant.is_expired(now) {
+            debug!(msg_type_url = grant.msg_type_url, "skipping expired grant");
+            continue;
+        }
+
+        report.revoked.push(grant.msg_type_url.clone());
+        msgs.push(
+            MsgRevoke {
+                granter: delegator_address.to_string(),
+                grantee: controller_address.to_string(),
+                msg_type_url: grant.msg_type_url,
+            }
+            .into(),
+        );
+    }
+
+    if let Some(withdraw_address) =
+        withdraw_address.filter(|address| *address != delegator_address.to_string())
+    {
+        msgs.push(
+            MsgSetWithdrawAddress {
+                delegator_address: delegator_address.to_string(),
+                withdraw_address: delegator_address.to_string(),
+            }
+            .into(),
+        );
+        report.previous_withdraw_address = Some(withdraw_address);
+    }
+
+    msgs
+}
+
+#[cfg(test)]
+mod test {
+    use std::{str::FromStr, time::Duration};
+
+    use cosmrs::{
+        AccountId,
+        proto::{
+            Timestamp,
+            cosmos::{bank::v1beta1::MsgSend, distribution::v1beta1::MsgWithdrawDelegatorReward},
+            prost::Name,
+        },
+    };
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use time::UtcDateTime;
+
+    use super::teardown_msgs;
+    use crate::{
+        chain::GrantInfo,
+        report::{CoinAmount, TeardownReport},
+        ser::ToCosmosJson,
+    };
+
+    const DELEGATOR: &str = "osmo1e4n3yara98z8lsxwcj7740q0nku3lcd70s9s29";
+    const CONTROLLER: &str = "osmo176pmhxsfz0cpgr705y5m4hy4kdxq2eq2atl2fy";
+
+    fn grant(msg_type_url: String, expiration: Option<UtcDateTime>) -> GrantInfo {
+        GrantInfo {
+            msg_type_url,
+            expiration: expiration.map(|expiration| Timestamp {
+                seconds: expiration.unix_timestamp(),
+                nanos: 0,
+            }),
+            spend_limit: Vec::new(),
+            allow_list: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_teardown_msgs() {
+        let delegator = AccountId::from_str(DELEGATOR).unwrap();
+        let controller = AccountId::from_str(CONTROLLER).unwrap();
+        let now = UtcDateTime::now();
+
+        let mut send_grant = grant(MsgSend::type_url(), Some(now + Duration::from_secs(60)));
+        send_grant.spend_limit = vec![CoinAmount {
+            denom: "uosmo".to_string(),
+            amount: "1000".to_string(),
+        }];
+        let grants = vec![
+            grant(MsgWithdrawDelegatorReward::type_url(), None),
+            send_grant,
+            // Expired grants cannot be revoked
+            grant(
+                "/cosmos.staking.v1beta1.MsgDelegate".to_string(),
+                Some(now - Duration::from_secs(60)),
+            ),
+        ];
+
+        let mut report = TeardownReport::default();
+        let msgs = teardown_msgs(
+            &delegator,
+            &controller,
+            grants.clone(),
+            Some(CONTROLLER.to_string()),
+            now,
+            &mut report,
+        );
+        let revoke = |msg_type_url: String| {
+            json!({
+                "@type": "/cosmos.authz.v1beta1.MsgRevoke",
+                "granter": DELEGATOR,
+                "grantee": CONTROLLER,
+                "msg_type_url": msg_type_url,
+            })
+        };
+        assert_eq!(
+            msgs.iter().map(|msg| msg.to_value()).collect::<Vec<_>>(),
+            vec![
+                revoke(MsgWithdrawDelegatorReward::type_url()),
+                revoke(MsgSend::type_url()),
+                json!({
+                    "@type": "/cosmos.distribution.v1beta1.MsgSetWithdrawAddress",
+                    "delegator_address": DELEGATOR,
+                    "withdraw_address": DELEGATOR,
+                }),
+            ]
+        );
+        assert_eq!(
+            report.revoked,
+            vec![MsgWithdrawDelegatorReward::type_url(), MsgSend::type_url()]
+        );
+        assert_eq!(
+            report.previous_withdraw_address.as_deref(),
+            Some(CONTROLLER)
+        );
+
+        // Withdraw address is left alone if it is the delegator already, or cannot be set
+        for withdraw_address in [Some(DELEGATOR.to_string()), None] {
+            let mut report = TeardownReport::default();
+            let msgs = teardown_msgs(
+                &delegator,
+                &controller,
+                grants.clone(),
+                withdraw_address,
+                now,
+                &mut report,
+            );
+            assert_eq!(msgs.len(), 2);
+            assert_eq!(report.previous_withdraw_address, None);
+        }
+
+        // Nothing to tear down
+        let mut report = TeardownReport::default();
+        let msgs = teardown_msgs(
+            &delegator,
+            &controller,
+            Vec::new(),
+            Some(DELEGATOR.to_string()),
+            now,
+            &mut report,
+        );
+        assert!(msgs.is_empty());
+        assert!(report.revoked.is_empty());
+    }
+}
@@ -1,41 +1,62 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap},
     str::FromStr,
+    time::Duration,
 };
 
 use cosmrs::{
     AccountId,
     proto::cosmos::{
+        auth::v1beta1::BaseAccount,
         bank::v1beta1::MsgSend,
-        base::v1beta1::Coin,
+        base::v1beta1::{Coin, DecCoin},
         distribution::v1beta1::{
             MsgWithdrawDelegatorReward, MsgWithdrawValidatorCommission,
             QueryDelegationTotalRewardsRequest,
         },
         tx::v1beta1::Tx,
     },
+    rpc::endpoint::tx::Response as TxResponse,
     rpc::{Client, HttpClient},
     tx::MessageExt,
 };
-use eyre::Context;
+use eyre::{Context, ContextCompat, bail};
 use num_bigint::BigUint;
-use tracing::{debug, info, trace, warn};
+use time::{OffsetDateTime, UtcDateTime, format_description::well_known::Rfc3339};
+use tracing::{debug, error, info, trace, warn};
 
 use crate::{
     AccountArgs, TransactionArgs,
-    chain::{get_chain_info, get_validator_commission},
+    chain::{
+        ChainInfo, expiring_grants, get_balances, get_chain_info, get_grants,
+        get_validator_commission,
+    },
     cmd::ResolvedAccounts,
     cosmos_sdk_extra::{
         abci_query::{QueryDelegationTotalRewards, execute_abci_query},
+        dec::dec_to_base_units,
         gas::GasInfo,
         simulate::simulate_tx,
         str_coin::StrCoin,
-        tx::{generate_unsigned_tx_json, poll_tx, print_tx_result},
+        tx::{check_tx_response, generate_unsigned_tx_json, print_tx_result, wait_for_tx},
+    },
+    ledger::{LEDGER_VERSION, Ledger, LedgerEntry},
+    metrics::{
+        CONTROLLER_BALANCE, FAILED_BROADCASTS, FEES_SPENT, LAST_WITHDRAWAL_TIMESTAMP, METRICS,
+        PENDING_COMMISSION, PENDING_REWARDS, dec_amount_to_f64,
+    },
+    report::{
+        CoinAmount, RewardDecision, RewardReport, TxKind, TxReport, ValidatorReport,
+        WithdrawReport, WithdrawStatus,
+    },
+    ser::{CosmosJsonSerializable, MsgExecCustom, ToCosmosJson},
+    wallet::{
+        SigningAccountType, TxSigner, WalletKeyType, construct_transaction_body, setup_signer,
+        sign_transaction,
     },
-    ser::{CosmosJsonSerializable, MsgExecCustom},
-    wallet::{SigningAccountType, construct_transaction_body, setup_signer, sign_transaction},
 };
 
+#[allow(clippy::too_many_arguments)]
 pub async fn withdraw(
     rpc_url: &str,
     account_hrp: Option<&String>,
@@ -43,280 +64,1108 @@ pub async fn withdraw(
     account: AccountArgs,
     transaction_args: TransactionArgs,
     thresholds: Vec<StrCoin>,
-) -> eyre::Result<()> {
-    let client = HttpClient::new(rpc_url)?;
-    let chain_info = get_chain_info(&client, account_hrp, valoper_hrp).await?;
+    validators: Vec<AccountId>,
+    grant_expiry_warning: Duration,
+    ledger: Option<Ledger>,
+) -> eyre::Result<WithdrawReport> {
+    Withdrawer::new(
+        rpc_url,
+        account_hrp,
+        valoper_hrp,
+        account,
+        transaction_args,
+        thresholds,
+        ledger,
+    )
+    .await?
+    .with_validators(validators)
+    .with_grant_expiry_warning(grant_expiry_warning)
+    .withdraw()
+    .await
+}
+
+/// Generates withdraw transaction without connecting to the chain. As reward amounts are not known,
+/// rewards from all given validators are withdrawn regardless of thresholds.
+pub fn withdraw_offline(
+    account_hrp: Option<&String>,
+    valoper_hrp: Option<&String>,
+    account: AccountArgs,
+    transaction_args: TransactionArgs,
+    validators: &[AccountId],
+    withdraw_commission: bool,
+) -> eyre::Result<WithdrawReport> {
+    let chain_info = transaction_args.offline_chain_info(account_hrp, valoper_hrp)?;
+    account.verify_accounts(&chain_info)?;
     let gas_info = GasInfo::determine_gas(&chain_info, &transaction_args)?;
+    let fee = gas_info
+        .get_fee()
+        .wrap_err("transactions cannot be simulated in offline mode, set --gas explicitly")?;
 
-    info!(?chain_info, ?gas_info.denom, ?gas_info.price, "chain info");
+    info!(?chain_info, ?gas_info.denom, ?gas_info.price, "offline chain info");
 
-    // Ensure delegator & controller accounts are initialized
-    // Withdrawal address does not need to be initialized, as it'll only receive rewards
-    let ResolvedAccounts {
-        controller_account,
-        controller_key_type,
-        ..
-    } = account.get_account_details(&client, &chain_info).await?;
+    if validators.is_empty() && !withdraw_commission {
+        bail!(
+            "validators to withdraw rewards from have to be set with --validator in offline mode"
+        );
+    }
 
-    let delegation_total_rewards = execute_abci_query::<QueryDelegationTotalRewards>(
-        &client,
-        QueryDelegationTotalRewardsRequest {
-            delegator_address: account.delegator_address.to_string(),
-        },
-    )
-    .await?;
+    let mut report = WithdrawReport {
+        status: WithdrawStatus::Generated,
+        chain_id: chain_info.id.to_string(),
+        ..Default::default()
+    };
+    let mut authz_msgs: Vec<CosmosJsonSerializable> = Vec::new();
+    for validator_address in validators {
+        if validator_address.prefix() != chain_info.bech32.valoper_prefix.as_str() {
+            bail!(
+                "provided validator address prefix does not match with chain: {} != {}",
+                validator_address.prefix(),
+                chain_info.bech32.valoper_prefix.as_str()
+            );
+        }
 
-    trace!(?delegation_total_rewards, "available rewards");
+        authz_msgs.push(
+            MsgWithdrawDelegatorReward {
+                delegator_address: account.delegator_address.to_string(),
+                validator_address: validator_address.to_string(),
+            }
+            .into(),
+        );
+        report.validators.push(ValidatorReport {
+            validator_address: validator_address.to_string(),
+            withdraw_rewards: true,
+            rewards: Vec::new(),
+            withdraw_commission: false,
+            commission: Vec::new(),
+        });
+    }
 
-    let thresholds_by_denom: HashMap<String, BigUint> = thresholds
-        .iter()
-        .map(|coin| (coin.denom.to_string(), BigUint::from(coin.amount)))
-        .collect();
-
-    let mut withdraw_self_valoper: Option<String> = None;
-    let mut withdraw_validators: HashSet<String> = HashSet::new();
-    let mut collected_coins: HashMap<String, BigUint> = HashMap::new();
-
-    for reward in delegation_total_rewards.rewards.iter() {
-        let validator_address = AccountId::from_str(&reward.validator_address)
-            .wrap_err("failed to parse validator address")?;
-
-        // Check if we can withdraw commissions
-        if validator_address.to_bytes() == account.delegator_address.to_bytes() {
-            debug!(?validator_address, delegator_address = ?account.delegator_address, "delegator is also a validator, checking for commissions");
-            if let Some(commission) = get_validator_commission(&client, &validator_address).await? {
-                trace!(?commission, "validator commissions");
-                for coin in commission {
-                    let amount: BigUint = coin
-                        .amount
-                        .parse()
-                        .wrap_err("failed to parse reward coin amount")?;
-
-                    *collected_coins.entry(coin.denom.to_string()).or_default() += amount;
-                }
+    if withdraw_commission {
+        // Commission can only be withdrawn from the validator operated by the delegator
+        let validator_address = AccountId::new(
+            chain_info.bech32.valoper_prefix.as_str(),
+            &account.delegator_address.to_bytes(),
+        )?
+        .to_string();
+
+        authz_msgs.push(
+            MsgWithdrawValidatorCommission {
+                validator_address: validator_address.clone(),
+            }
+            .into(),
+        );
+        match report
+            .validators
+            .iter_mut()
+            .find(|validator| validator.validator_address == validator_address)
+        {
+            Some(validator) => validator.withdraw_commission = true,
+            None => report.validators.push(ValidatorReport {
+                validator_address,
+                withdraw_rewards: false,
+                rewards: Vec::new(),
+                withdraw_commission: true,
+                commission: Vec::new(),
+            }),
+        }
+    }
 
-                withdraw_self_valoper = Some(reward.validator_address.clone());
+    // Generated transaction cannot be split, mixed messages are rejected in EIP-712 sign mode
+    let msgs = transaction_args
+        .split_msgs(vec![
+            MsgExecCustom {
+                grantee: account.controller_address.to_string(),
+                msgs: authz_msgs,
             }
+            .into(),
+        ])?
+        .remove(0);
+    report.unsigned_tx = Some(generate_unsigned_tx_json(
+        msgs,
+        &transaction_args.memo,
+        fee.gas_limit,
+        fee.amount,
+    ));
+
+    Ok(report)
+}
+
+/// Withdrawer holds everything which does not change between withdraw runs, so that long-running
+/// processes don't need to query chain info and derive keys over and over again.
+pub struct Withdrawer {
+    pub(super) client: HttpClient,
+    pub(super) chain_info: ChainInfo,
+    pub(super) gas_info: GasInfo,
+    pub(super) account: AccountArgs,
+    pub(super) transaction_args: TransactionArgs,
+    thresholds: Vec<StrCoin>,
+    /// Only withdraw rewards from these validators. Rewards from all validators are withdrawn if empty
+    validators: Vec<AccountId>,
+    /// Warn about grants expiring within this window. Grants are not checked if zero
+    grant_expiry_warning: Duration,
+    /// Ledger to record committed transactions in
+    pub(super) ledger: Option<Ledger>,
+    /// Controller signer, set up lazily on first withdrawal
+    pub(super) signer: Option<TxSigner>,
+}
+
+/// Rewards & commissions selected for withdrawal by [`Withdrawer::select`]
+pub(super) struct Selection {
+    pub report: WithdrawReport,
+    /// Messages to be executed by the controller on behalf of the delegator
+    pub authz_msgs: Vec<CosmosJsonSerializable>,
+    pub collected_coins: HashMap<String, BigUint>,
+    pub controller_account: BaseAccount,
+    pub controller_key_type: WalletKeyType,
+}
+
+impl Withdrawer {
+    pub async fn new(
+        rpc_url: &str,
+        account_hrp: Option<&String>,
+        valoper_hrp: Option<&String>,
+        account: AccountArgs,
+        transaction_args: TransactionArgs,
+        thresholds: Vec<StrCoin>,
+        ledger: Option<Ledger>,
+    ) -> eyre::Result<Self> {
+        let client = HttpClient::new(rpc_url)?;
+        let chain_info = get_chain_info(&client, account_hrp, valoper_hrp).await?;
+        let gas_info = GasInfo::determine_gas(&chain_info, &transaction_args)?;
+
+        info!(?chain_info, ?gas_info.denom, ?gas_info.price, "chain info");
+
+        Ok(Self {
+            client,
+            chain_info,
+            gas_info,
+            account,
+            transaction_args,
+            thresholds,
+            validators: Vec::new(),
+            grant_expiry_warning: Duration::ZERO,
+            ledger,
+            signer: None,
+        })
+    }
+
+    pub fn with_validators(mut self, validators: Vec<AccountId>) -> Self {
+        self.validators = validators;
+        self
+    }
+
+    pub fn with_grant_expiry_warning(mut self, window: Duration) -> Self {
+        self.grant_expiry_warning = window;
+        self
+    }
+
+    pub async fn withdraw(&mut self) -> eyre::Result<WithdrawReport> {
+        // Held until the withdrawal is done, so that concurrent runs do not reuse the same sequence
+        let _lock = self
+            .transaction_args
+            .lock_account(
+                self.chain_info.id.as_str(),
+                &self.account.controller_address,
+            )
+            .await?;
+
+        let Selection {
+            mut report,
+            authz_msgs,
+            collected_coins,
+            controller_account,
+            controller_key_type,
+        } = self.select().await?;
+
+        if authz_msgs.is_empty() {
+            info!("nothing to withdraw yet");
+            report.status = WithdrawStatus::NothingToWithdraw;
+            return Ok(report);
         }
 
-        for coin in reward.reward.iter() {
-            let amount: BigUint = coin
-                .amount
-                .parse()
-                .wrap_err("failed to parse reward coin amount")?;
+        let Self {
+            ref client,
+            ref chain_info,
+            ref gas_info,
+            ref account,
+            ref transaction_args,
+            ref ledger,
+            signer: ref mut cached_signer,
+            ..
+        } = *self;
+        let chain_id = chain_info.id.as_str();
 
-            let Some(threshold) = thresholds_by_denom.get(&coin.denom) else {
-                debug!(?coin, "not interested in reward due to configuration");
-                continue;
+        if !chain_info.chain_supports_setting_withdrawal_address && transaction_args.generate_only {
+            // Due to the way how cosmos transactions work, you cannot stack multiple messages on top of each other - MsgSend won't know about updated balance before
+            // the transaction has been committed on the chain. If transaction is executed within the tool, then we can easily wait until withdraw succeeds, and then
+            // construct a new transaction.
+            warn!(
+                "as this chain requires using MsgSend for withdrawing rewards, and --generate-only was requested, you need to construct authz transaction yourself"
+            );
+        }
+
+        let batches = transaction_args.split_msgs(vec![
+            MsgExecCustom {
+                grantee: account.controller_address.to_string(),
+                msgs: authz_msgs,
+            }
+            .into(),
+        ])?;
+
+        let withdraw_address = account
+            .reward_address
+            .as_ref()
+            .unwrap_or(&account.controller_address);
+        let account_number = transaction_args
+            .account_number
+            .unwrap_or(controller_account.account_number);
+        let sequence = transaction_args
+            .sequence
+            .unwrap_or(controller_account.sequence);
+
+        for (i, msgs) in batches.into_iter().enumerate() {
+            // Transactions are committed one after another, so each of them uses the next sequence.
+            // Nothing is committed in a dry run, so all of them are simulated against the current one
+            let sequence = if transaction_args.dry_run {
+                sequence
+            } else {
+                sequence + i as u64
+            };
+
+            // This transaction will be signed by the controller account
+            let signer = controller_signer(
+                cached_signer,
+                account,
+                chain_info,
+                transaction_args,
+                SigningAccountType::Controller {
+                    key_type: controller_key_type,
+                    account_number,
+                    sequence,
+                },
+            )
+            .await?;
+
+            let fee = if let Some(fee) = gas_info.get_fee() {
+                fee
+            } else {
+                simulate_tx(
+                    client,
+                    chain_info,
+                    gas_info,
+                    signer,
+                    construct_transaction_body(&transaction_args.memo, &msgs)?,
+                )
+                .await?
             };
 
-            if amount < *threshold {
-                debug!(
-                    ?coin,
-                    ?amount,
-                    ?threshold,
-                    "not interested in reward due to threshold"
-                );
+            if transaction_args.generate_only {
+                report.status = WithdrawStatus::Generated;
+                report.unsigned_tx = Some(generate_unsigned_tx_json(
+                    msgs,
+                    &transaction_args.memo,
+                    fee.gas_limit,
+                    fee.amount,
+                ));
+
+                return Ok(report);
+            }
+
+            let signed_tx = sign_transaction(
+                chain_info,
+                signer,
+                fee.clone(),
+                construct_transaction_body(&transaction_args.memo, &msgs)?,
+            )
+            .await
+            .wrap_err("failed to sign withdraw transaction")?;
+
+            if transaction_args.dry_run {
                 continue;
             }
 
-            withdraw_validators.insert(reward.validator_address.clone());
-            *collected_coins.entry(coin.denom.to_string()).or_default() += amount;
+            let tx_response = broadcast_tx(client, chain_info, signed_tx).await?;
+            info!(tx_hash = ?tx_response.hash, "transaction committed to chain, withdrawal done");
+            let tx_report = TxReport::new(TxKind::Withdraw, &fee, &tx_response);
+            record_in_ledger(
+                ledger.as_ref(),
+                chain_id,
+                &tx_report,
+                &msgs,
+                &collected_by(&report, &msgs)?,
+                withdraw_address,
+            );
+            report.transactions.push(tx_report);
         }
-    }
 
-    if withdraw_validators.is_empty() && withdraw_self_valoper.is_none() {
-        info!("nothing to withdraw yet");
-        return Ok(());
+        if transaction_args.dry_run {
+            info!("dry run was requested, nothing was done");
+            report.status = WithdrawStatus::DryRun;
+            return Ok(report);
+        }
+
+        // Handle AuthzSend scenario
+        if !chain_info.chain_supports_setting_withdrawal_address {
+            debug!("refreshing account data");
+            let ResolvedAccounts {
+                controller_account,
+                controller_key_type,
+                ..
+            } = account.get_account_details(client, chain_info).await?;
+
+            let signer = controller_signer(
+                cached_signer,
+                account,
+                chain_info,
+                transaction_args,
+                SigningAccountType::Controller {
+                    key_type: controller_key_type,
+                    account_number: transaction_args
+                        .account_number
+                        .unwrap_or(controller_account.account_number),
+                    sequence: transaction_args
+                        .sequence
+                        // If we have sequence override, skip the withdraw transactions here blindly
+                        .map(|seq| seq + report.transactions.len() as u64)
+                        .unwrap_or(controller_account.sequence),
+                },
+            )
+            .await?;
+
+            info!("sending withdrawn tokens");
+
+            let mut authz_msgs: Vec<CosmosJsonSerializable> = Vec::new();
+            let amount = collected_coins
+                .into_iter()
+                .map(|(denom, amount)| Coin {
+                    amount: amount.to_string(),
+                    denom,
+                })
+                .collect::<Vec<_>>();
+
+            debug!(?amount, "tokens to send to reward address");
+
+            authz_msgs.push(
+                MsgSend {
+                    from_address: account.delegator_address.to_string(),
+                    to_address: withdraw_address.to_string(),
+                    amount,
+                }
+                .into(),
+            );
+
+            let msgs = vec![
+                MsgExecCustom {
+                    grantee: account.controller_address.to_string(),
+                    msgs: authz_msgs,
+                }
+                .into(),
+            ];
+
+            let fee = if let Some(fee) = gas_info.get_fee() {
+                fee
+            } else {
+                simulate_tx(
+                    client,
+                    chain_info,
+                    gas_info,
+                    signer,
+                    construct_transaction_body(&transaction_args.memo, &msgs)?,
+                )
+                .await?
+            };
+
+            let signed_tx = sign_transaction(
+                chain_info,
+                signer,
+                fee.clone(),
+                construct_transaction_body(&transaction_args.memo, &msgs)?,
+            )
+            .await
+            .wrap_err("failed to sign send transaction")?;
+
+            let tx_response = broadcast_tx(client, chain_info, signed_tx).await?;
+            info!(tx_hash = ?tx_response.hash, "transaction committed to chain, send done");
+            let tx_report = TxReport::new(TxKind::Send, &fee, &tx_response);
+            // Coins were already accounted for by the withdraw transaction
+            record_in_ledger(
+                ledger.as_ref(),
+                chain_id,
+                &tx_report,
+                &msgs,
+                &CollectedCoins::default(),
+                withdraw_address,
+            );
+            report.transactions.push(tx_report);
+        }
+
+        METRICS.set(
+            &LAST_WITHDRAWAL_TIMESTAMP,
+            &[("chain_id", chain_id)],
+            UtcDateTime::now().unix_timestamp() as f64,
+        );
+
+        report.status = WithdrawStatus::Withdrawn;
+        Ok(report)
     }
 
-    info!(
-        ?withdraw_validators,
-        withdraw_commissions = withdraw_self_valoper.is_some(),
-        "withdrawing"
-    );
+    /// Queries rewards & commissions, and selects the ones reaching configured thresholds.
+    /// Returned authz messages are empty if there is nothing to withdraw.
+    pub(super) async fn select(&self) -> eyre::Result<Selection> {
+        let Self {
+            ref client,
+            ref chain_info,
+            ref account,
+            ref thresholds,
+            ref validators,
+            grant_expiry_warning,
+            ..
+        } = *self;
 
-    let mut authz_msgs: Vec<CosmosJsonSerializable> = Vec::new();
-    for validator_address in withdraw_validators {
-        authz_msgs.push(
-            MsgWithdrawDelegatorReward {
+        // Ensure delegator & controller accounts are initialized
+        // Withdrawal address does not need to be initialized, as it'll only receive rewards
+        let ResolvedAccounts {
+            controller_account,
+            controller_key_type,
+            ..
+        } = account.get_account_details(client, chain_info).await?;
+
+        let delegation_total_rewards = execute_abci_query::<QueryDelegationTotalRewards>(
+            client,
+            QueryDelegationTotalRewardsRequest {
                 delegator_address: account.delegator_address.to_string(),
-                validator_address,
+            },
+        )
+        .await?;
+
+        trace!(?delegation_total_rewards, "available rewards");
+
+        let chain_id = chain_info.id.as_str();
+        let mut report = WithdrawReport {
+            chain_id: chain_id.to_string(),
+            ..Default::default()
+        };
+
+        if !grant_expiry_warning.is_zero() {
+            // Grants are only checked to warn about them, failure must not prevent the withdrawal
+            match get_grants(
+                client,
+                &account.delegator_address,
+                &account.controller_address,
+            )
+            .await
+            {
+                Ok(grants) => {
+                    report.expiring_grants = expiring_grants(&grants, grant_expiry_warning);
+                }
+                Err(err) => warn!(?err, "failed to query grants for expiry check"),
             }
-            .into(),
+        }
+
+        METRICS.replace_denoms(
+            &PENDING_REWARDS,
+            chain_id,
+            delegation_total_rewards
+                .total
+                .iter()
+                .map(|coin| (coin.denom.clone(), dec_amount_to_f64(&coin.amount))),
         );
-    }
 
-    if let Some(validator_address) = withdraw_self_valoper {
-        authz_msgs.push(MsgWithdrawValidatorCommission { validator_address }.into());
-    }
+        if METRICS.enabled() {
+            // Only needed for the metrics, must not prevent the withdrawal
+            match get_balances(client, &account.controller_address).await {
+                Ok(balances) => METRICS.replace_denoms(
+                    &CONTROLLER_BALANCE,
+                    chain_id,
+                    balances
+                        .into_iter()
+                        .map(|coin| (coin.denom, coin.amount.parse::<f64>().unwrap_or(f64::NAN))),
+                ),
+                Err(err) => warn!(?err, "failed to query controller balance for metrics"),
+            }
+        }
+
+        let thresholds_by_denom: HashMap<String, BigUint> = thresholds
+            .iter()
+            .map(|coin| (coin.denom.to_string(), BigUint::from(coin.amount)))
+            .collect();
+
+        let mut withdraw_self_valoper: Option<String> = None;
+        let mut withdraw_validators: BTreeSet<String> = BTreeSet::new();
+        let mut collected_coins: HashMap<String, BigUint> = HashMap::new();
+        // Commission is only known if the delegator's own validator is selected. Otherwise the
+        // gauge is cleared, so that it does not keep showing commission which may be withdrawn
+        let mut pending_commission: Vec<(String, f64)> = Vec::new();
+
+        for reward in delegation_total_rewards.rewards.iter() {
+            let validator_address = AccountId::from_str(&reward.validator_address)
+                .wrap_err("failed to parse validator address")?;
+
+            if !validators.is_empty() && !validators.contains(&validator_address) {
+                debug!(?validator_address, "skipping validator not in the list");
+                continue;
+            }
+
+            let mut validator_report = ValidatorReport {
+                validator_address: reward.validator_address.clone(),
+                withdraw_rewards: false,
+                rewards: Vec::new(),
+                withdraw_commission: false,
+                commission: Vec::new(),
+            };
+
+            // Check if we can withdraw commissions
+            if validator_address.to_bytes() == account.delegator_address.to_bytes() {
+                debug!(?validator_address, delegator_address = ?account.delegator_address, "delegator is also a validator, checking for commissions");
+                if let Some(commission) =
+                    get_validator_commission(client, &validator_address).await?
+                {
+                    trace!(?commission, "validator commissions");
+                    pending_commission = commission
+                        .iter()
+                        .map(|coin| (coin.denom.clone(), dec_amount_to_f64(&coin.amount)))
+                        .collect();
+
+                    for coin in commission {
+                        let amount = dec_to_base_units(&coin.amount)
+                            .wrap_err("failed to parse commission coin amount")?;
+
+                        *collected_coins.entry(coin.denom.to_string()).or_default() +=
+                            amount.clone();
+                        validator_report.commission.push(CoinAmount {
+                            denom: coin.denom,
+                            amount: amount.to_string(),
+                        });
+                    }
+
+                    withdraw_self_valoper = Some(reward.validator_address.clone());
+                    validator_report.withdraw_commission = true;
+                }
+            }
+
+            for coin in reward.reward.iter() {
+                let (reward_report, amount) = reward_decision(coin, &thresholds_by_denom)?;
+                match reward_report.decision {
+                    RewardDecision::NotConfigured => {
+                        debug!(?coin, "not interested in reward due to configuration");
+                    }
+                    RewardDecision::BelowThreshold => {
+                        debug!(
+                            ?coin,
+                            ?amount,
+                            threshold = reward_report.threshold,
+                            "not interested in reward due to threshold"
+                        );
+                    }
+                    RewardDecision::Selected => {
+                        withdraw_validators.insert(reward.validator_address.clone());
+                        *collected_coins.entry(coin.denom.to_string()).or_default() += amount;
+                        validator_report.withdraw_rewards = true;
+                    }
+                }
+                validator_report.rewards.push(reward_report);
+            }
+
+            report.validators.push(validator_report);
+        }
+
+        METRICS.replace_denoms(&PENDING_COMMISSION, chain_id, pending_commission);
+        report.collected = collected_amounts(&collected_coins);
+
+        if withdraw_validators.is_empty() && withdraw_self_valoper.is_none() {
+            return Ok(Selection {
+                report,
+                authz_msgs: Vec::new(),
+                collected_coins,
+                controller_account,
+                controller_key_type,
+            });
+        }
 
-    if !chain_info.chain_supports_setting_withdrawal_address && transaction_args.generate_only {
-        // Due to the way how cosmos transactions work, you cannot stack multiple messages on top of each other - MsgSend won't know about updated balance before
-        // the transaction has been committed on the chain. If transaction is executed within the tool, then we can easily wait until withdraw succeeds, and then
-        // construct a new transaction.
-        warn!(
-            "as this chain requires using MsgSend for withdrawing rewards, and --generate-only was requested, you need to construct authz transaction yourself"
+        info!(
+            ?withdraw_validators,
+            withdraw_commissions = withdraw_self_valoper.is_some(),
+            "withdrawing"
         );
-    }
 
-    let msgs = vec![
-        MsgExecCustom {
-            grantee: account.controller_address.to_string(),
-            msgs: authz_msgs,
+        let mut authz_msgs: Vec<CosmosJsonSerializable> = Vec::new();
+        for validator_address in withdraw_validators {
+            authz_msgs.push(
+                MsgWithdrawDelegatorReward {
+                    delegator_address: account.delegator_address.to_string(),
+                    validator_address,
+                }
+                .into(),
+            );
         }
-        .into(),
-    ];
-
-    // This transaction will be signed by the controller account
-    let signer = setup_signer(
-        &account,
-        &chain_info.bech32,
-        SigningAccountType::Controller {
-            key_type: controller_key_type,
-            account_number: transaction_args
-                .account_number
-                .unwrap_or(controller_account.account_number),
-            sequence: transaction_args
-                .sequence
-                .unwrap_or(controller_account.sequence),
-        },
-        transaction_args.generate_only,
-    )?;
-
-    let fee = if let Some(fee) = gas_info.get_fee() {
-        fee
-    } else {
-        simulate_tx(
-            &client,
-            &chain_info,
-            &gas_info,
-            &signer,
-            construct_transaction_body(&transaction_args.memo, &msgs)?,
-        )
-        .await?
+
+        if let Some(validator_address) = withdraw_self_valoper {
+            authz_msgs.push(MsgWithdrawValidatorCommission { validator_address }.into());
+        }
+
+        Ok(Selection {
+            report,
+            authz_msgs,
+            collected_coins,
+            controller_account,
+            controller_key_type,
+        })
+    }
+}
+
+/// Decides whether pending reward is withdrawn, returning its amount in base units. Rewards are
+/// Cosmos SDK Dec scaled by 10^18 on chain, they are rounded down to base units before comparing
+/// with the threshold, which is given in base units too (e.g. `1000000uosmo` is 1 OSMO)
+fn reward_decision(
+    coin: &DecCoin,
+    thresholds_by_denom: &HashMap<String, BigUint>,
+) -> eyre::Result<(RewardReport, BigUint)> {
+    let amount = dec_to_base_units(&coin.amount).wrap_err("failed to parse reward coin amount")?;
+    let threshold = thresholds_by_denom.get(&coin.denom);
+    let decision = match threshold {
+        None => RewardDecision::NotConfigured,
+        Some(threshold) if amount < *threshold => RewardDecision::BelowThreshold,
+        Some(_) => RewardDecision::Selected,
     };
 
-    if transaction_args.generate_only {
-        println!(
-            "{}",
-            generate_unsigned_tx_json(msgs, &transaction_args.memo, fee.gas_limit, fee.amount)
-        );
+    Ok((
+        RewardReport {
+            denom: coin.denom.clone(),
+            amount: amount.to_string(),
+            threshold: threshold.map(|threshold| threshold.to_string()),
+            decision,
+        },
+        amount,
+    ))
+}
 
-        return Ok(());
+/// Collected amounts sorted by denom
+pub(super) fn collected_amounts(collected_coins: &HashMap<String, BigUint>) -> Vec<CoinAmount> {
+    collected_coins
+        .iter()
+        .collect::<BTreeMap<_, _>>()
+        .into_iter()
+        .map(|(denom, amount)| CoinAmount {
+            denom: denom.clone(),
+            amount: amount.to_string(),
+        })
+        .collect()
+}
+
+/// Coins collected by withdraw messages, per denom, split into delegation rewards and commission
+#[derive(Debug, Default)]
+pub(super) struct CollectedCoins {
+    pub rewards: HashMap<String, BigUint>,
+    pub commission: HashMap<String, BigUint>,
+}
+
+impl CollectedCoins {
+    /// Rewards and commission summed up, sorted by denom
+    pub fn total(&self) -> Vec<CoinAmount> {
+        let mut total = self.rewards.clone();
+        for (denom, amount) in self.commission.iter() {
+            *total.entry(denom.clone()).or_default() += amount;
+        }
+
+        collected_amounts(&total)
     }
+}
 
-    let signed_tx = sign_transaction(
-        &chain_info,
-        &signer,
-        fee,
-        construct_transaction_body(&transaction_args.memo, &msgs)?,
-    )
-    .wrap_err("failed to sign withdraw transaction")?;
+/// Amounts collected by given withdraw messages, as selected in the report
+fn collected_by(
+    report: &WithdrawReport,
+    msgs: &[CosmosJsonSerializable],
+) -> eyre::Result<CollectedCoins> {
+    let mut collected = CollectedCoins::default();
+    for msg in msgs {
+        let CosmosJsonSerializable::MsgExec(msg_exec) = msg else {
+            continue;
+        };
+
+        for msg in msg_exec.msgs.iter() {
+            let (collected_coins, coins): (_, Vec<(&str, &str)>) = match msg {
+                CosmosJsonSerializable::MsgWithdrawDelegatorReward(msg) => (
+                    &mut collected.rewards,
+                    report
+                        .validators
+                        .iter()
+                        .filter(|validator| validator.validator_address == msg.validator_address)
+                        .flat_map(|validator| validator.rewards.iter())
+                        .filter(|reward| reward.decision == RewardDecision::Selected)
+                        .map(|reward| (reward.denom.as_str(), reward.amount.as_str()))
+                        .collect(),
+                ),
+                CosmosJsonSerializable::MsgWithdrawValidatorCommission(msg) => (
+                    &mut collected.commission,
+                    report
+                        .validators
+                        .iter()
+                        .filter(|validator| validator.validator_address == msg.validator_address)
+                        .flat_map(|validator| validator.commission.iter())
+                        .map(|coin| (coin.denom.as_str(), coin.amount.as_str()))
+                        .collect(),
+                ),
+                _ => continue,
+            };
 
-    if transaction_args.dry_run {
-        info!("dry run was requested, nothing was done");
-        return Ok(());
+            for (denom, amount) in coins {
+                *collected_coins.entry(denom.to_string()).or_default() += amount
+                    .parse::<BigUint>()
+                    .wrap_err("failed to parse collected amount")?;
+            }
+        }
     }
 
-    let tx_result = client
-        .broadcast_tx_sync(Tx::from(signed_tx).to_bytes()?)
-        .await?;
+    Ok(collected)
+}
 
-    print_tx_result(&tx_result)?;
-    poll_tx(&client, tx_result.hash).await?;
-    info!(tx_hash = ?tx_result.hash, "transaction committed to chain, withdrawal done");
+/// Broadcasts signed transaction and waits until it's committed to the chain
+pub(super) async fn broadcast_tx(
+    client: &HttpClient,
+    chain_info: &ChainInfo,
+    signed_tx: cosmrs::Tx,
+) -> eyre::Result<TxResponse> {
+    let chain_id = chain_info.id.as_str();
+    let fee = signed_tx.auth_info.fee.amount.clone();
 
-    // Handle AuthzSend scenario
-    if !chain_info.chain_supports_setting_withdrawal_address {
-        debug!("refreshing account data");
-        let ResolvedAccounts {
-            controller_account,
-            controller_key_type,
-            ..
-        } = account.get_account_details(&client, &chain_info).await?;
+    let result: eyre::Result<TxResponse> = async {
+        let tx_result = client
+            .broadcast_tx_sync(Tx::from(signed_tx).to_bytes()?)
+            .await?;
+
+        print_tx_result(&tx_result)?;
+        let tx_response = wait_for_tx(client, tx_result.hash).await?;
+
+        // Fee is paid once the transaction is committed, even if its execution failed
+        for coin in fee {
+            METRICS.add(
+                &FEES_SPENT,
+                &[("chain_id", chain_id), ("denom", coin.denom.as_ref())],
+                coin.amount as f64,
+            );
+        }
+
+        check_tx_response(&tx_response)?;
+        Ok(tx_response)
+    }
+    .await;
+
+    if result.is_err() {
+        METRICS.add(&FAILED_BROADCASTS, &[("chain_id", chain_id)], 1.0);
+    }
+
+    result
+}
+
+/// Records committed transaction in the ledger. Failures are only logged, as the transaction
+/// is already on chain and the rest of the withdrawal must proceed regardless.
+pub(super) fn record_in_ledger(
+    ledger: Option<&Ledger>,
+    chain_id: &str,
+    tx: &TxReport,
+    msgs: &[CosmosJsonSerializable],
+    collected: &CollectedCoins,
+    reward_address: &AccountId,
+) {
+    let Some(ledger) = ledger else {
+        return;
+    };
 
-        let signer = setup_signer(
-            &account,
+    let result = OffsetDateTime::now_utc()
+        .format(&Rfc3339)
+        .wrap_err("failed to format timestamp")
+        .and_then(|timestamp| {
+            ledger.append(&LedgerEntry {
+                version: LEDGER_VERSION,
+                timestamp,
+                chain_id: chain_id.to_string(),
+                height: tx.height,
+                tx_hash: tx.tx_hash.clone(),
+                kind: tx.kind,
+                messages: msgs.iter().map(|msg| msg.to_value()).collect(),
+                collected: collected.total(),
+                rewards: collected_amounts(&collected.rewards),
+                commission: collected_amounts(&collected.commission),
+                fee: tx.fee.clone(),
+                reward_address: reward_address.to_string(),
+            })
+        });
+
+    if let Err(err) = result {
+        error!(
+            ?err,
+            tx_hash = tx.tx_hash,
+            "failed to record transaction in ledger"
+        );
+    }
+}
+
+/// Returns controller signer with updated account number & sequence. Signing key is derived only once
+/// and kept in `cached_signer` for subsequent runs.
+pub(super) async fn controller_signer<'a>(
+    cached_signer: &'a mut Option<TxSigner>,
+    account: &AccountArgs,
+    chain_info: &ChainInfo,
+    transaction_args: &TransactionArgs,
+    signing_account_type: SigningAccountType,
+) -> eyre::Result<&'a TxSigner> {
+    let SigningAccountType::Controller {
+        key_type,
+        account_number,
+        sequence,
+    } = signing_account_type
+    else {
+        unreachable!("withdrawals are always signed by the controller");
+    };
+
+    let signer = match cached_signer.take() {
+        Some(signer) if signer.key_type() == key_type => {
+            signer.with_numbers(account_number, sequence)
+        }
+        _ => setup_signer(
+            account,
             &chain_info.bech32,
-            SigningAccountType::Controller {
-                key_type: controller_key_type,
-                account_number: transaction_args
-                    .account_number
-                    .unwrap_or(controller_account.account_number),
-                sequence: transaction_args
-                    .sequence
-                    // If we have sequence override, increment sequence by 1 here blindly
-                    .map(|seq| seq + 1)
-                    .unwrap_or(controller_account.sequence),
-            },
+            signing_account_type,
             transaction_args.generate_only,
-        )?;
+        )
+        .await?
+        .with_sign_mode(transaction_args.sign_mode)
+        .with_eip712_chain_id(transaction_args.eip712_chain_id),
+    };
 
-        info!("sending withdrawn tokens");
+    Ok(cached_signer.insert(signer))
+}
 
-        let mut authz_msgs: Vec<CosmosJsonSerializable> = Vec::new();
-        let withdraw_address = account
-            .reward_address
-            .as_ref()
-            .unwrap_or(&account.controller_address);
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use clap::Parser;
+    use cosmrs::{
+        AccountId,
+        proto::cosmos::{
+            base::v1beta1::DecCoin,
+            distribution::v1beta1::{MsgWithdrawDelegatorReward, MsgWithdrawValidatorCommission},
+        },
+    };
+    use num_bigint::BigUint;
+    use pretty_assertions::assert_eq;
+
+    use super::{collected_amounts, collected_by, reward_decision, withdraw_offline};
+    use crate::{
+        AccountArgs, TransactionArgs,
+        report::{
+            CoinAmount, RewardDecision, RewardReport, ValidatorReport, WithdrawReport,
+            WithdrawStatus,
+        },
+        ser::{CosmosJsonSerializable, MsgExecCustom},
+    };
+
+    const DELEGATOR: &str = "evmos16nf0mht68937d27cwrqqdtwv9y2alm06nevstf";
+    const CONTROLLER: &str = "evmos1c9rjzdd3f3mu30he3eelwqsgxf06ph83xd26eg";
+    const VALIDATOR: &str = "evmosvaloper16nf0mht68937d27cwrqqdtwv9y2alm067hrq25";
+    const OTHER_VALIDATOR: &str = "evmosvaloper1my5c5yx3kpe4sd7uf0v9mtryrv8neme8casswk";
+
+    #[derive(Parser)]
+    struct WithdrawCli {
+        #[clap(flatten)]
+        account: AccountArgs,
 
-        let amount = collected_coins
-            .into_iter()
-            .map(|(denom, amount)| Coin {
+        #[clap(flatten)]
+        transaction_args: TransactionArgs,
+    }
+
+    fn coins(coins: &[(&str, &str)]) -> Vec<CoinAmount> {
+        coins
+            .iter()
+            .map(|(denom, amount)| CoinAmount {
+                denom: denom.to_string(),
                 amount: amount.to_string(),
-                denom,
             })
-            .collect::<Vec<_>>();
+            .collect()
+    }
 
-        debug!(?amount, "tokens to send to reward address");
+    #[test]
+    fn test_reward_decision() {
+        // `--threshold 1000000uosmo`, i.e. 1 OSMO
+        let thresholds = HashMap::from([("uosmo".to_string(), BigUint::from(1_000_000u32))]);
+        let decide = |denom: &str, amount: &str| {
+            let (report, amount) = reward_decision(
+                &DecCoin {
+                    denom: denom.to_string(),
+                    amount: amount.to_string(),
+                },
+                &thresholds,
+            )
+            .unwrap();
+            assert_eq!(report.amount, amount.to_string());
+            (report.decision, report.amount)
+        };
 
-        authz_msgs.push(
-            MsgSend {
-                from_address: account.delegator_address.to_string(),
-                to_address: withdraw_address.to_string(),
-                amount,
-            }
-            .into(),
+        // Dec amounts are scaled by 10^18 on chain, 999999.999999999999999999uosmo is rounded
+        // down and does not reach the threshold
+        assert_eq!(
+            decide("uosmo", "999999999999999999999999"),
+            (RewardDecision::BelowThreshold, "999999".to_string())
+        );
+        assert_eq!(
+            decide("uosmo", "1000000000000000000000000"),
+            (RewardDecision::Selected, "1000000".to_string())
+        );
+        assert_eq!(
+            decide("uosmo", "2500000500000000000000000"),
+            (RewardDecision::Selected, "2500000".to_string())
+        );
+        assert_eq!(
+            decide("uion", "5000000000000000000000000"),
+            (RewardDecision::NotConfigured, "5000000".to_string())
         );
 
-        let msgs = vec![
-            MsgExecCustom {
-                grantee: account.controller_address.to_string(),
-                msgs: authz_msgs,
+        let (report, _) = reward_decision(
+            &DecCoin {
+                denom: "uosmo".to_string(),
+                amount: "1".to_string(),
+            },
+            &thresholds,
+        )
+        .unwrap();
+        assert_eq!(report.threshold.as_deref(), Some("1000000"));
+    }
+
+    #[test]
+    fn test_collected_by() {
+        let reward = |denom: &str, amount: &str, decision: RewardDecision| RewardReport {
+            denom: denom.to_string(),
+            amount: amount.to_string(),
+            threshold: None,
+            decision,
+        };
+        let report = WithdrawReport {
+            validators: vec![
+                ValidatorReport {
+                    validator_address: VALIDATOR.to_string(),
+                    withdraw_rewards: true,
+                    rewards: vec![
+                        reward("aevmos", "1000", RewardDecision::Selected),
+                        reward("uatom", "5", RewardDecision::NotConfigured),
+                    ],
+                    withdraw_commission: true,
+                    commission: coins(&[("aevmos", "300")]),
+                },
+                ValidatorReport {
+                    validator_address: OTHER_VALIDATOR.to_string(),
+                    withdraw_rewards: true,
+                    rewards: vec![reward("aevmos", "20", RewardDecision::Selected)],
+                    withdraw_commission: false,
+                    commission: Vec::new(),
+                },
+            ],
+            ..Default::default()
+        };
+        let exec = |msgs: Vec<CosmosJsonSerializable>| -> Vec<CosmosJsonSerializable> {
+            vec![
+                MsgExecCustom {
+                    grantee: CONTROLLER.to_string(),
+                    msgs,
+                }
+                .into(),
+            ]
+        };
+        let reward_msg = |validator_address: &str| -> CosmosJsonSerializable {
+            MsgWithdrawDelegatorReward {
+                delegator_address: DELEGATOR.to_string(),
+                validator_address: validator_address.to_string(),
             }
-            .into(),
-        ];
-
-        let fee = if let Some(fee) = gas_info.get_fee() {
-            fee
-        } else {
-            simulate_tx(
-                &client,
-                &chain_info,
-                &gas_info,
-                &signer,
-                construct_transaction_body(&transaction_args.memo, &msgs)?,
-            )
-            .await?
+            .into()
+        };
+        let commission_msg = || -> CosmosJsonSerializable {
+            MsgWithdrawValidatorCommission {
+                validator_address: VALIDATOR.to_string(),
+            }
+            .into()
         };
 
-        let signed_tx = sign_transaction(
-            &chain_info,
-            &signer,
-            fee,
-            construct_transaction_body(&transaction_args.memo, &msgs)?,
+        // Everything in one transaction, rewards and commission are kept apart
+        let collected = collected_by(
+            &report,
+            &exec(vec![
+                reward_msg(VALIDATOR),
+                reward_msg(OTHER_VALIDATOR),
+                commission_msg(),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(collected.total(), coins(&[("aevmos", "1320")]));
+        assert_eq!(
+            collected_amounts(&collected.rewards),
+            coins(&[("aevmos", "1020")])
+        );
+        assert_eq!(
+            collected_amounts(&collected.commission),
+            coins(&[("aevmos", "300")])
+        );
+        // Rewards & commission split into separate transactions in EIP-712 sign mode
+        let collected = collected_by(
+            &report,
+            &exec(vec![reward_msg(VALIDATOR), reward_msg(OTHER_VALIDATOR)]),
         )
-        .wrap_err("failed to sign send transaction")?;
+        .unwrap();
+        assert_eq!(collected.total(), coins(&[("aevmos", "1020")]));
+        assert!(collected.commission.is_empty());
+        let collected = collected_by(&report, &exec(vec![commission_msg()])).unwrap();
+        assert_eq!(collected.total(), coins(&[("aevmos", "300")]));
+        assert!(collected.rewards.is_empty());
+    }
 
-        let tx_result = client
-            .broadcast_tx_sync(Tx::from(signed_tx).to_bytes()?)
-            .await?;
+    #[test]
+    fn test_withdraw_offline_eip712() {
+        let withdraw = |sign_mode: &str, withdraw_commission: bool| {
+            let cli = WithdrawCli::try_parse_from([
+                "withdraw",
+                "--delegator-address",
+                DELEGATOR,
+                "--controller-address",
+                CONTROLLER,
+                "--chain-id",
+                "evmos_9001-2",
+                "--gas",
+                "300000",
+                "--gas-prices",
+                "80000000000aevmos",
+                "--sign-mode",
+                sign_mode,
+                "--generate-only",
+                "--offline",
+            ])
+            .unwrap();
+            let validators = [VALIDATOR, OTHER_VALIDATOR].map(|v| v.parse::<AccountId>().unwrap());
 
-        print_tx_result(&tx_result)?;
-        poll_tx(&client, tx_result.hash).await?;
-        info!(tx_hash = ?tx_result.hash, "transaction committed to chain, send done");
-    }
+            withdraw_offline(
+                Some(&"evmos".to_string()),
+                None,
+                cli.account,
+                cli.transaction_args,
+                &validators,
+                withdraw_commission,
+            )
+        };
+        let message_count = |report: WithdrawReport| {
+            assert_eq!(report.status, WithdrawStatus::Generated);
+            report.unsigned_tx.unwrap()["body"]["messages"][0]["msgs"]
+                .as_array()
+                .unwrap()
+                .len()
+        };
+
+        assert_eq!(message_count(withdraw("direct", true).unwrap()), 3);
+        assert_eq!(message_count(withdraw("eip712", false).unwrap()), 2);
 
-    Ok(())
+        // Generated transaction cannot be split, rejected before anything is signed
+        let err = withdraw("eip712", true).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("EIP-712 sign mode requires a separate transaction"),
+            "{err}"
+        );
+    }
 }
@@ -0,0 +1,341 @@
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use bip32::DerivationPath;
+
+use cosmrs::AccountId;
+use duration_string::DurationString;
+use eyre::{Context, bail};
+use serde::Deserialize;
+
+use crate::{
+    cmd::{AccountArgs, GasOption, KeystoreArgs, TransactionArgs},
+    cosmos_sdk_extra::str_coin::{FloatStrCoin, StrCoin},
+    secret::SecretString,
+    wallet::{TxSignMode, WalletKeyType},
+};
+
+/// Multi-chain configuration file used by `run` subcommand
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Chains to process, in the order of appearance
+    #[serde(rename = "chain", default)]
+    pub chains: Vec<ChainConfig>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> eyre::Result<Self> {
+        let data = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("failed to read config file '{}'", path.display()))?;
+
+        let config: Self = toml::from_str(&data)
+            .wrap_err_with(|| format!("failed to parse config file '{}'", path.display()))?;
+
+        for (idx, chain) in config.chains.iter().enumerate() {
+            if config.chains[..idx]
+                .iter()
+                .any(|other| other.name == chain.name)
+            {
+                bail!("duplicate chain name '{}' in config file", chain.name);
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChainConfig {
+    /// Human readable name, used in logs and run summary
+    pub name: String,
+
+    /// Cosmos RPC URL (Tendermint RPC)
+    pub rpc_url: String,
+
+    /// Network account address prefix
+    pub account_hrp: Option<String>,
+
+    /// Network valoper address prefix
+    pub valoper_hrp: Option<String>,
+
+    pub delegator_address: AccountId,
+    pub delegator_address_type: Option<WalletKeyType>,
+
+    pub controller_address: AccountId,
+    pub controller_address_type: Option<WalletKeyType>,
+    /// Controller mnemonic phrase. Prefer `controller_mnemonic_env` instead of storing secrets in the config file
+    pub controller_mnemonic: Option<SecretString>,
+    /// Name of the environment variable holding controller mnemonic phrase
+    pub controller_mnemonic_env: Option<String>,
+    /// File containing controller mnemonic phrase
+    pub controller_mnemonic_file: Option<PathBuf>,
+    #[serde(default = "default_coin_type")]
+    pub controller_mnemonic_coin_type: u64,
+    /// Controller HD derivation path, e.g. `m/44'/118'/1'/0/0`. Overrides coin type
+    pub controller_hd_path: Option<String>,
+    /// Name of the environment variable holding controller mnemonic BIP39 passphrase
+    pub controller_mnemonic_passphrase_env: Option<String>,
+    /// File containing controller mnemonic BIP39 passphrase
+    pub controller_mnemonic_passphrase_file: Option<PathBuf>,
+    /// Controller Cosmos SDK keyring directory using `file` backend. Alternative to controller mnemonic
+    pub controller_keyring_dir: Option<PathBuf>,
+    pub controller_keyring_key_name: Option<String>,
+    /// Controller keyring passphrase. Prefer `controller_keyring_passphrase_env` instead of storing secrets in the config file
+    pub controller_keyring_passphrase: Option<SecretString>,
+    /// Name of the environment variable holding controller keyring passphrase
+    pub controller_keyring_passphrase_env: Option<String>,
+    /// Name of the environment variable holding controller hex encoded private key
+    pub controller_private_key_env: Option<String>,
+    /// File containing controller ASCII armored private key
+    pub controller_armored_key_file: Option<PathBuf>,
+    /// Name of the environment variable holding controller armored private key passphrase
+    pub controller_armored_key_passphrase_env: Option<String>,
+    /// URL of a remote signer service holding the controller key
+    pub controller_remote_signer_url: Option<String>,
+    /// Command signing with the controller key
+    pub controller_signer_command: Option<String>,
+    /// Name of the controller key in the encrypted keystore
+    pub controller_key: Option<String>,
+    /// Encrypted keystore file. Defaults to `~/.cosmos-withdrawer/keystore.json`
+    pub keystore: Option<PathBuf>,
+    /// Name of the environment variable holding keystore passphrase
+    pub keystore_passphrase_env: Option<String>,
+
+    pub reward_address: Option<AccountId>,
+
+    #[serde(default)]
+    pub transaction: TransactionConfig,
+
+    /// Token thresholds for withdrawal. Format: 1234denom
+    #[serde(default)]
+    pub thresholds: Vec<StrCoin>,
+}
+
+impl ChainConfig {
+    pub fn to_account_args(&self) -> eyre::Result<AccountArgs> {
+        let controller_mnemonic = read_secret(
+            "controller_mnemonic",
+            &self.controller_mnemonic,
+            &self.controller_mnemonic_env,
+        )?;
+        let controller_mnemonic_passphrase = read_secret(
+            "controller_mnemonic_passphrase",
+            &None,
+            &self.controller_mnemonic_passphrase_env,
+        )?;
+        let controller_hd_path = self
+            .controller_hd_path
+            .as_deref()
+            .map(DerivationPath::from_str)
+            .transpose()
+            .wrap_err("failed to parse controller_hd_path")?;
+        let controller_keyring_passphrase = read_secret(
+            "controller_keyring_passphrase",
+            &self.controller_keyring_passphrase,
+            &self.controller_keyring_passphrase_env,
+        )?;
+
+        Ok(AccountArgs {
+            delegator_address: self.delegator_address.clone(),
+            delegator_mnemonic: None,
+            delegator_mnemonic_file: None,
+            delegator_mnemonic_fd: None,
+            delegator_mnemonic_prompt: false,
+            delegator_address_type: self.delegator_address_type,
+            delegator_mnemonic_coin_type: default_coin_type(),
+            delegator_hd_path: None,
+            delegator_mnemonic_passphrase: None,
+            delegator_mnemonic_passphrase_file: None,
+            delegator_keyring_dir: None,
+            delegator_keyring_key_name: None,
+            delegator_keyring_passphrase: None,
+            delegator_private_key: None,
+            delegator_armored_key_file: None,
+            delegator_armored_key_passphrase: None,
+            delegator_remote_signer_url: None,
+            delegator_signer_command: None,
+            delegator_key: None,
+            controller_address: self.controller_address.clone(),
+            controller_mnemonic,
+            controller_mnemonic_file: self.controller_mnemonic_file.clone(),
+            controller_mnemonic_fd: None,
+            controller_mnemonic_prompt: false,
+            controller_mnemonic_coin_type: self.controller_mnemonic_coin_type,
+            controller_hd_path,
+            controller_mnemonic_passphrase,
+            controller_mnemonic_passphrase_file: self.controller_mnemonic_passphrase_file.clone(),
+            controller_keyring_dir: self.controller_keyring_dir.clone(),
+            controller_keyring_key_name: self.controller_keyring_key_name.clone(),
+            controller_keyring_passphrase,
+            controller_private_key: read_secret(
+                "controller_private_key",
+                &None,
+                &self.controller_private_key_env,
+            )?,
+            controller_armored_key_file: self.controller_armored_key_file.clone(),
+            controller_remote_signer_url: self.controller_remote_signer_url.clone(),
+            controller_signer_command: self.controller_signer_command.clone(),
+            controller_key: self.controller_key.clone(),
+            controller_armored_key_passphrase: read_secret(
+                "controller_armored_key_passphrase",
+                &None,
+                &self.controller_armored_key_passphrase_env,
+            )?,
+            controller_address_type: self.controller_address_type,
+            reward_address: self.reward_address.clone(),
+            keystore: KeystoreArgs {
+                keystore: self.keystore.clone(),
+                keystore_passphrase: read_secret(
+                    "keystore_passphrase",
+                    &None,
+                    &self.keystore_passphrase_env,
+                )?,
+            },
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TransactionConfig {
+    pub memo: String,
+    pub gas: GasOption,
+    pub gas_adjustment: f64,
+    pub gas_prices: Vec<FloatStrCoin>,
+    pub sign_mode: TxSignMode,
+    pub eip712_chain_id: Option<u64>,
+    pub dry_run: bool,
+    pub lock_dir: Option<PathBuf>,
+    pub lock_wait: Option<DurationString>,
+}
+
+impl Default for TransactionConfig {
+    fn default() -> Self {
+        // Keep in sync with TransactionArgs defaults
+        Self {
+            memo: "cosmos-withdrawer".to_string(),
+            gas: GasOption::Auto,
+            gas_adjustment: 1.25,
+            gas_prices: Vec::new(),
+            sign_mode: TxSignMode::Direct,
+            eip712_chain_id: None,
+            dry_run: false,
+            lock_dir: None,
+            lock_wait: None,
+        }
+    }
+}
+
+impl TransactionConfig {
+    pub fn to_transaction_args(&self) -> TransactionArgs {
+        TransactionArgs {
+            memo: self.memo.clone(),
+            gas: self.gas.clone(),
+            gas_adjustment: self.gas_adjustment,
+            gas_prices: self.gas_prices.clone(),
+            sign_mode: self.sign_mode,
+            eip712_chain_id: self.eip712_chain_id,
+            sequence: None,
+            account_number: None,
+            generate_only: false,
+            dry_run: self.dry_run,
+            offline: false,
+            chain_id: None,
+            lock_dir: self.lock_dir.clone(),
+            lock_wait: self.lock_wait,
+        }
+    }
+}
+
+/// Reads secret either directly from the config file, or from the environment variable named by `{field}_env`
+fn read_secret(
+    field: &str,
+    value: &Option<SecretString>,
+    env: &Option<String>,
+) -> eyre::Result<Option<SecretString>> {
+    match (value, env) {
+        (Some(_), Some(_)) => bail!("only one of {field} and {field}_env can be set"),
+        (Some(value), None) => Ok(Some(value.clone())),
+        (None, Some(env)) => {
+            let value = std::env::var(env)
+                .wrap_err_with(|| format!("failed to read environment variable '{env}'"))?;
+            Ok(Some(value.into()))
+        }
+        (None, None) => Ok(None),
+    }
+}
+
+fn default_coin_type() -> u64 {
+    118
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::Config;
+    use crate::cmd::GasOption;
+
+    #[test]
+    fn test_parse_config() {
+        let config: Config = toml::from_str(
+            r#"
+            [[chain]]
+            name = "osmosis"
+            rpc_url = "https://rpc.osmosis.zone"
+            delegator_address = "osmo176pmhxsfz0cpgr705y5m4hy4kdxq2eq2atl2fy"
+            controller_address = "osmo1e4n3yara98z8lsxwcj7740q0nku3lcd70s9s29"
+            controller_mnemonic_env = "OSMOSIS_CONTROLLER_MNEMONIC"
+            thresholds = ["1000000uosmo"]
+
+            [chain.transaction]
+            gas = "250000"
+            gas_prices = ["0.025uosmo"]
+
+            [[chain]]
+            name = "injective"
+            rpc_url = "https://rpc.injective.network"
+            account_hrp = "inj"
+            delegator_address = "inj19lhpj24vqtglud7kd7e4n3zj8z4lxkl7ex3uv0"
+            delegator_address_type = "eth_secp256k1"
+            controller_address = "inj19lhpj24vqtglud7kd7e4n3zj8z4lxkl7ex3uv0"
+            controller_mnemonic_coin_type = 60
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.chains.len(), 2);
+
+        let osmosis = &config.chains[0];
+        assert_eq!(osmosis.name, "osmosis");
+        assert_eq!(osmosis.thresholds[0].amount, 1000000);
+        assert!(matches!(osmosis.transaction.gas, GasOption::Amount(250000)));
+        assert_eq!(osmosis.transaction.gas_adjustment, 1.25);
+        assert_eq!(osmosis.transaction.memo, "cosmos-withdrawer");
+
+        let injective = &config.chains[1];
+        assert_eq!(injective.account_hrp.as_deref(), Some("inj"));
+        assert_eq!(injective.controller_mnemonic_coin_type, 60);
+        assert!(injective.thresholds.is_empty());
+    }
+
+    #[test]
+    fn test_parse_config_unknown_field() {
+        let result = toml::from_str::<Config>(
+            r#"
+            [[chain]]
+            name = "osmosis"
+            rpc_url = "https://rpc.osmosis.zone"
+            rpc = "typo"
+            delegator_address = "osmo176pmhxsfz0cpgr705y5m4hy4kdxq2eq2atl2fy"
+            controller_address = "osmo1e4n3yara98z8lsxwcj7740q0nku3lcd70s9s29"
+            "#,
+        );
+
+        assert!(result.is_err());
+    }
+}
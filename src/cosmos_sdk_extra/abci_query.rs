@@ -6,8 +6,10 @@ use cosmrs::{
                 QueryAccountResponse,
             },
             authz::v1beta1::{QueryGrantsRequest, QueryGrantsResponse},
+            bank::v1beta1::{QueryAllBalancesRequest, QueryAllBalancesResponse},
             distribution::v1beta1::{
                 QueryDelegationTotalRewardsRequest, QueryDelegationTotalRewardsResponse,
+                QueryDelegatorWithdrawAddressRequest, QueryDelegatorWithdrawAddressResponse,
                 QueryParamsRequest as QueryDistributionParamsRequest,
                 QueryParamsResponse as QueryDistributionParamsResponse,
                 QueryValidatorCommissionRequest, QueryValidatorCommissionResponse,
@@ -79,10 +81,15 @@ pub trait CosmosABCIQuery {
 define_query!("/cosmos.auth.v1beta1.Query/Account", QueryAccount);
 define_query!("/cosmos.auth.v1beta1.Query/Bech32Prefix", Bech32Prefix);
 define_query!("/cosmos.authz.v1beta1.Query/Grants", QueryGrants);
+define_query!("/cosmos.bank.v1beta1.Query/AllBalances", QueryAllBalances);
 define_query!(
     "/cosmos.distribution.v1beta1.Query/DelegationTotalRewards",
     QueryDelegationTotalRewards
 );
+define_query!(
+    "/cosmos.distribution.v1beta1.Query/DelegatorWithdrawAddress",
+    QueryDelegatorWithdrawAddress,
+);
 define_query!(
     "/cosmos.distribution.v1beta1.Query/Params",
     QueryDistributionParams,
@@ -0,0 +1,63 @@
+use eyre::{Context, bail};
+use num_bigint::BigUint;
+
+/// Number of decimal places of Cosmos SDK `LegacyDec`
+const DEC_PRECISION: u32 = 18;
+
+/// Converts Cosmos SDK Dec amount to base units, rounding down. In protobuf, Dec is encoded as an
+/// integer scaled by 10^18, but amounts with a decimal point (as in JSON) are accepted as well.
+pub fn dec_to_base_units(amount: &str) -> eyre::Result<BigUint> {
+    let (integer, scaled) = match amount.split_once('.') {
+        Some((integer, fraction)) => {
+            if !fraction.chars().all(|c| c.is_ascii_digit()) {
+                bail!("invalid decimal amount '{amount}'");
+            }
+            (integer, false)
+        }
+        None => (amount, true),
+    };
+
+    let integer: BigUint = integer
+        .parse()
+        .wrap_err_with(|| format!("invalid decimal amount '{amount}'"))?;
+    if !scaled {
+        return Ok(integer);
+    }
+
+    Ok(integer / BigUint::from(10u32).pow(DEC_PRECISION))
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::dec_to_base_units;
+
+    #[test]
+    fn test_dec_to_base_units() {
+        for (dec, expected) in [
+            ("0", "0"),
+            ("999999999999999999", "0"),
+            ("1000000000000000000", "1"),
+            ("1500000000000000000", "1"),
+            ("1234000000000000000000", "1234"),
+            (
+                "123456789012345678901234567890123456789",
+                "123456789012345678901",
+            ),
+            ("1234.567800000000000000", "1234"),
+            ("0.999999999999999999", "0"),
+            ("12.", "12"),
+        ] {
+            assert_eq!(
+                dec_to_base_units(dec).unwrap().to_string(),
+                expected,
+                "{dec}"
+            );
+        }
+
+        for invalid in ["", "-1000000000000000000", "1e18", "1.5x", ".5", "abc"] {
+            assert!(dec_to_base_units(invalid).is_err(), "{invalid}");
+        }
+    }
+}
@@ -19,6 +19,28 @@ impl Name for EthAccount {
     }
 }
 
+/// Manually rolled structure for /ethermint.types.v1.ExtensionOptionsWeb3Tx
+#[derive(Message)]
+pub struct ExtensionOptionsWeb3Tx {
+    /// Chain id of the EIP-712 domain
+    #[prost(uint64, tag = "1")]
+    pub typed_data_chain_id: u64,
+    #[prost(string, tag = "2")]
+    pub fee_payer: String,
+    /// EIP-712 signature of the fee payer
+    #[prost(bytes = "vec", tag = "3")]
+    pub fee_payer_sig: Vec<u8>,
+}
+
+impl Name for ExtensionOptionsWeb3Tx {
+    const NAME: &'static str = "ExtensionOptionsWeb3Tx";
+    const PACKAGE: &'static str = "ethermint.types.v1";
+
+    fn full_name() -> String {
+        format!("{}.{}", Self::PACKAGE, Self::NAME)
+    }
+}
+
 /// Manually rolled structure for /ethermint.crypto.v1.ethsecp256k1.PubKey
 #[derive(Message)]
 pub struct EthPubKey {
@@ -0,0 +1,40 @@
+use cosmrs::Any;
+use prost::{Message, Name};
+
+/// Manually rolled structure for /cosmos.crypto.keyring.v1.Record. Oneof `item` is represented as
+/// separate optional fields, which is equivalent on the wire.
+#[derive(Message)]
+pub struct Record {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(message, optional, tag = "2")]
+    pub pub_key: Option<Any>,
+    #[prost(message, optional, tag = "3")]
+    pub local: Option<RecordLocal>,
+    #[prost(message, optional, tag = "4")]
+    pub ledger: Option<RecordEmpty>,
+    #[prost(message, optional, tag = "5")]
+    pub multi: Option<RecordEmpty>,
+    #[prost(message, optional, tag = "6")]
+    pub offline: Option<RecordEmpty>,
+}
+
+impl Name for Record {
+    const NAME: &'static str = "Record";
+    const PACKAGE: &'static str = "cosmos.crypto.keyring.v1";
+
+    fn full_name() -> String {
+        format!("{}.{}", Self::PACKAGE, Self::NAME)
+    }
+}
+
+/// Manually rolled structure for /cosmos.crypto.keyring.v1.Record.Local
+#[derive(Message)]
+pub struct RecordLocal {
+    #[prost(message, optional, tag = "1")]
+    pub priv_key: Option<Any>,
+}
+
+/// Placeholder for record items which carry no private key. Their contents are not needed
+#[derive(Message)]
+pub struct RecordEmpty {}
@@ -1,4 +1,5 @@
 pub mod abci_query;
+pub mod dec;
 pub mod gas;
 pub mod rpc;
 pub mod simulate;
@@ -7,3 +8,4 @@ pub mod tx;
 
 pub mod ethermint;
 pub mod injective;
+pub mod keyring;
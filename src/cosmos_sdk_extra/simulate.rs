@@ -37,7 +37,8 @@ pub async fn simulate_tx(
         signer,
         simulation_fee(gas_info.denom.clone()),
         body,
-    )?;
+    )
+    .await?;
 
     #[allow(deprecated)]
     let SimulateResponse {
@@ -3,6 +3,8 @@ use std::{ops::Deref, str::FromStr};
 use cosmrs::{Coin, Denom};
 use eyre::Context;
 
+use crate::ser::deserialize_from_str;
+
 #[derive(Clone, Debug)]
 pub struct StrCoin(pub Coin);
 
@@ -64,6 +66,8 @@ impl FromStr for FloatStrCoin {
     }
 }
 
+deserialize_from_str!(StrCoin, FloatStrCoin);
+
 #[cfg(test)]
 mod test {
     use std::str::FromStr;
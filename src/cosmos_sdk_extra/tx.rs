@@ -1,6 +1,10 @@
 use std::time::Duration;
 
-use cosmrs::{Coin, Tx, rpc::HttpClient, tendermint::Hash};
+use cosmrs::{
+    Coin,
+    rpc::{Client, HttpClient, endpoint::tx::Response as TxResponse},
+    tendermint::Hash,
+};
 use eyre::bail;
 use serde_json::{Value, json};
 use tokio::time::sleep;
@@ -54,11 +58,20 @@ pub fn print_tx_result(
     Ok(())
 }
 
-pub async fn poll_tx(client: &HttpClient, tx_hash: Hash) -> eyre::Result<Tx> {
+/// Polls for transaction until it's committed. Fails if the transaction got committed, but its execution failed
+pub async fn poll_tx(client: &HttpClient, tx_hash: Hash) -> eyre::Result<TxResponse> {
+    let response = wait_for_tx(client, tx_hash).await?;
+    check_tx_response(&response)?;
+
+    Ok(response)
+}
+
+/// Polls for transaction until it's committed, regardless of whether its execution succeeded
+pub async fn wait_for_tx(client: &HttpClient, tx_hash: Hash) -> eyre::Result<TxResponse> {
     for attempt in 0..5 {
         trace!(?tx_hash, attempt, "polling for transaction");
-        match Tx::find_by_hash(client, tx_hash).await {
-            Ok(tx) => return Ok(tx),
+        match client.tx(tx_hash, false).await {
+            Ok(response) => return Ok(response),
             Err(err) => {
                 trace!(?err, ?tx_hash, "poll failed, sleeping");
             }
@@ -69,3 +82,18 @@ pub async fn poll_tx(client: &HttpClient, tx_hash: Hash) -> eyre::Result<Tx> {
 
     bail!("polling for tx timed out: {tx_hash:?}")
 }
+
+/// Fails if execution of the committed transaction failed
+pub fn check_tx_response(response: &TxResponse) -> eyre::Result<()> {
+    if response.tx_result.code.is_err() {
+        bail!(
+            "transaction {} failed: codespace = {} code = {} log = {}",
+            response.hash,
+            response.tx_result.codespace,
+            response.tx_result.code.value(),
+            response.tx_result.log
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,391 @@
+use std::{
+    fs::{DirBuilder, OpenOptions},
+    io::{ErrorKind, Write},
+    os::unix::fs::{DirBuilderExt, OpenOptionsExt},
+    path::{Path, PathBuf},
+};
+
+use aes_gcm::{
+    Aes256Gcm, KeyInit, Nonce,
+    aead::{Aead, Payload},
+};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use bip32::secp256k1::{
+    ecdsa::SigningKey,
+    elliptic_curve::rand_core::{OsRng, RngCore},
+};
+use eyre::{Context, ContextCompat, bail, eyre};
+use serde::{Deserialize, Serialize};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+use zeroize::Zeroizing;
+
+use crate::wallet::WalletKeyType;
+
+const KEYSTORE_VERSION: u32 = 1;
+const KDF_ARGON2ID: &str = "argon2id";
+const CIPHER_AES_256_GCM: &str = "aes-256-gcm";
+
+/// Keystore is a JSON file holding private keys, each encrypted with a passphrase. Public parts of
+/// the keys are stored in plain text, so that keys can be listed without the passphrase.
+#[derive(Clone, Debug)]
+pub struct Keystore {
+    path: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct KeystoreFile {
+    version: u32,
+    keys: Vec<KeystoreEntry>,
+}
+
+impl Default for KeystoreFile {
+    fn default() -> Self {
+        Self {
+            version: KEYSTORE_VERSION,
+            keys: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KeystoreEntry {
+    pub name: String,
+    /// Key type used for deriving addresses
+    pub key_type: WalletKeyType,
+    /// Hex encoded compressed secp256k1 public key
+    pub public_key: String,
+    /// RFC3339 timestamp of when the key was added
+    pub created_at: String,
+    crypto: EncryptedKey,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct EncryptedKey {
+    kdf: String,
+    kdf_params: KdfParams,
+    cipher: String,
+    /// Base64 encoded nonce
+    nonce: String,
+    /// Base64 encoded encrypted private key, including authentication tag
+    ciphertext: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct KdfParams {
+    /// Memory size in KiB
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    /// Base64 encoded salt
+    salt: String,
+}
+
+impl KdfParams {
+    /// OWASP recommended Argon2id parameters with a random salt
+    fn generate() -> Self {
+        let mut salt = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+
+        Self {
+            m_cost: 19 * 1024,
+            t_cost: 2,
+            p_cost: 1,
+            salt: BASE64_STANDARD.encode(salt),
+        }
+    }
+
+    fn derive_key(&self, passphrase: &str) -> eyre::Result<Zeroizing<[u8; 32]>> {
+        let salt = BASE64_STANDARD
+            .decode(&self.salt)
+            .wrap_err("failed to decode keystore salt")?;
+        let params = Params::new(self.m_cost, self.t_cost, self.p_cost, Some(32))
+            .map_err(|err| eyre!("invalid keystore kdf parameters: {err}"))?;
+
+        let mut key = Zeroizing::new([0u8; 32]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &salt, key.as_mut())
+            .map_err(|err| eyre!("failed to derive keystore encryption key: {err}"))?;
+
+        Ok(key)
+    }
+}
+
+impl Keystore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Returns `~/.cosmos-withdrawer/keystore.json`
+    pub fn default_path() -> eyre::Result<PathBuf> {
+        let home = std::env::var_os("HOME").wrap_err(
+            "could not determine home directory for the default keystore, use --keystore",
+        )?;
+
+        Ok(PathBuf::from(home)
+            .join(".cosmos-withdrawer")
+            .join("keystore.json"))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn list(&self) -> eyre::Result<Vec<KeystoreEntry>> {
+        Ok(self.read()?.keys)
+    }
+
+    pub fn get(&self, name: &str) -> eyre::Result<KeystoreEntry> {
+        self.read()?
+            .keys
+            .into_iter()
+            .find(|entry| entry.name == name)
+            .wrap_err_with(|| {
+                format!(
+                    "key '{name}' not found in keystore '{}'",
+                    self.path.display()
+                )
+            })
+    }
+
+    /// Encrypts the key with given passphrase and adds it to the keystore
+    pub fn add(
+        &self,
+        name: &str,
+        key: &SigningKey,
+        key_type: WalletKeyType,
+        passphrase: &str,
+    ) -> eyre::Result<KeystoreEntry> {
+        if name.is_empty() {
+            bail!("key name cannot be empty");
+        }
+
+        let mut file = self.read()?;
+        if file.keys.iter().any(|entry| entry.name == name) {
+            bail!(
+                "key '{name}' already exists in keystore '{}'",
+                self.path.display()
+            );
+        }
+
+        let kdf_params = KdfParams::generate();
+        let mut nonce = [0u8; 12];
+        OsRng.fill_bytes(&mut nonce);
+
+        // Name is authenticated, so that entries cannot be swapped
+        let ciphertext = Aes256Gcm::new((&*kdf_params.derive_key(passphrase)?).into())
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &Zeroizing::new(key.to_bytes()),
+                    aad: name.as_bytes(),
+                },
+            )
+            .map_err(|_| eyre!("failed to encrypt key"))?;
+
+        let entry = KeystoreEntry {
+            name: name.to_string(),
+            key_type,
+            public_key: hex::encode(key.verifying_key().to_sec1_bytes()),
+            created_at: OffsetDateTime::now_utc().format(&Rfc3339)?,
+            crypto: EncryptedKey {
+                kdf: KDF_ARGON2ID.to_string(),
+                kdf_params,
+                cipher: CIPHER_AES_256_GCM.to_string(),
+                nonce: BASE64_STANDARD.encode(nonce),
+                ciphertext: BASE64_STANDARD.encode(ciphertext),
+            },
+        };
+
+        file.keys.push(entry.clone());
+        self.write(&file)?;
+
+        Ok(entry)
+    }
+
+    /// Decrypts the private key of given entry
+    pub fn load_key(&self, name: &str, passphrase: &str) -> eyre::Result<SigningKey> {
+        let entry = self.get(name)?;
+        let crypto = &entry.crypto;
+        if crypto.kdf != KDF_ARGON2ID || crypto.cipher != CIPHER_AES_256_GCM {
+            bail!(
+                "unsupported keystore kdf '{}' with cipher '{}'",
+                crypto.kdf,
+                crypto.cipher
+            );
+        }
+
+        let nonce = BASE64_STANDARD
+            .decode(&crypto.nonce)
+            .wrap_err("failed to decode keystore nonce")?;
+        if nonce.len() != 12 {
+            bail!("unexpected keystore nonce length {}", nonce.len());
+        }
+        let ciphertext = BASE64_STANDARD
+            .decode(&crypto.ciphertext)
+            .wrap_err("failed to decode keystore ciphertext")?;
+
+        let key_bytes = Zeroizing::new(
+            Aes256Gcm::new((&*crypto.kdf_params.derive_key(passphrase)?).into())
+                .decrypt(
+                    Nonce::from_slice(&nonce),
+                    Payload {
+                        msg: &ciphertext,
+                        aad: name.as_bytes(),
+                    },
+                )
+                .map_err(|_| eyre!("invalid passphrase"))?,
+        );
+
+        SigningKey::from_slice(&key_bytes).wrap_err("invalid secp256k1 private key")
+    }
+
+    pub fn delete(&self, name: &str) -> eyre::Result<()> {
+        let mut file = self.read()?;
+        let len = file.keys.len();
+        file.keys.retain(|entry| entry.name != name);
+        if file.keys.len() == len {
+            bail!(
+                "key '{name}' not found in keystore '{}'",
+                self.path.display()
+            );
+        }
+
+        self.write(&file)
+    }
+
+    /// Reads the keystore file. Missing file is treated as empty keystore
+    fn read(&self) -> eyre::Result<KeystoreFile> {
+        let data = match std::fs::read_to_string(&self.path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(KeystoreFile::default()),
+            Err(err) => {
+                return Err(err).wrap_err_with(|| {
+                    format!("failed to read keystore '{}'", self.path.display())
+                });
+            }
+        };
+
+        let file: KeystoreFile = serde_json::from_str(&data)
+            .wrap_err_with(|| format!("failed to parse keystore '{}'", self.path.display()))?;
+        if file.version != KEYSTORE_VERSION {
+            bail!("unsupported keystore version {}", file.version);
+        }
+
+        Ok(file)
+    }
+
+    /// Replaces the keystore file atomically, readable only by the owner
+    fn write(&self, file: &KeystoreFile) -> eyre::Result<()> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(dir)
+                .wrap_err_with(|| {
+                    format!("failed to create keystore directory '{}'", dir.display())
+                })?;
+        }
+
+        let data = serde_json::to_vec_pretty(file)?;
+        let tmp_path = self.path.with_extension("tmp");
+        let _ = std::fs::remove_file(&tmp_path);
+
+        let mut tmp = OpenOptions::new()
+            .create_new(true)
+            .write(true)
+            .mode(0o600)
+            .open(&tmp_path)
+            .wrap_err_with(|| format!("failed to create '{}'", tmp_path.display()))?;
+        tmp.write_all(&data)
+            .and_then(|_| tmp.sync_all())
+            .wrap_err_with(|| format!("failed to write '{}'", tmp_path.display()))?;
+
+        std::fs::rename(&tmp_path, &self.path)
+            .wrap_err_with(|| format!("failed to write keystore '{}'", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bip32::secp256k1::ecdsa::SigningKey;
+    use pretty_assertions::assert_eq;
+
+    use super::Keystore;
+    use crate::wallet::WalletKeyType;
+
+    #[test]
+    fn test_keystore() {
+        let dir = std::env::temp_dir().join(format!(
+            "cosmos-withdrawer-keystore-test-{}",
+            std::process::id()
+        ));
+        let keystore = Keystore::new(dir.join("keystore.json"));
+        assert!(keystore.list().unwrap().is_empty());
+
+        let key = SigningKey::from_slice(&[7; 32]).unwrap();
+        let entry = keystore
+            .add(
+                "controller",
+                &key,
+                WalletKeyType::Secp256k1,
+                "testpassphrase",
+            )
+            .unwrap();
+        assert_eq!(
+            entry.public_key,
+            hex::encode(key.verifying_key().to_sec1_bytes())
+        );
+        assert!(
+            keystore
+                .add(
+                    "controller",
+                    &key,
+                    WalletKeyType::Secp256k1,
+                    "testpassphrase"
+                )
+                .is_err()
+        );
+
+        // Secrets are not stored in plain text
+        let data = std::fs::read_to_string(keystore.path()).unwrap();
+        assert!(!data.contains(&hex::encode(key.to_bytes())));
+
+        let loaded = keystore.load_key("controller", "testpassphrase").unwrap();
+        assert_eq!(loaded.to_bytes(), key.to_bytes());
+
+        let err = keystore.load_key("controller", "wrong").unwrap_err();
+        assert_eq!(err.to_string(), "invalid passphrase");
+
+        keystore
+            .add(
+                "other",
+                &SigningKey::from_slice(&[8; 32]).unwrap(),
+                WalletKeyType::EthermintSecp256k1 { injective: false },
+                "otherpassphrase",
+            )
+            .unwrap();
+        assert_eq!(
+            keystore
+                .list()
+                .unwrap()
+                .iter()
+                .map(|entry| (entry.name.as_str(), entry.key_type))
+                .collect::<Vec<_>>(),
+            vec![
+                ("controller", WalletKeyType::Secp256k1),
+                (
+                    "other",
+                    WalletKeyType::EthermintSecp256k1 { injective: false }
+                ),
+            ]
+        );
+
+        keystore.delete("controller").unwrap();
+        assert!(keystore.get("controller").is_err());
+        assert!(keystore.delete("controller").is_err());
+        assert!(keystore.get("other").is_ok());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,472 @@
+use std::{
+    collections::BTreeMap,
+    fs::OpenOptions,
+    io::{BufRead, BufReader, ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+use eyre::{Context, bail};
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use time::{
+    Date, OffsetDateTime, format_description::well_known::Rfc3339, macros::format_description,
+};
+
+use crate::{
+    cosmos_sdk_extra::dec::dec_to_base_units,
+    report::{CoinAmount, TxKind},
+};
+
+/// Version of the ledger entry format. Entries of version 1 were written without the field, and
+/// recorded collected amounts as Cosmos SDK Dec scaled by 10^18 instead of base units. Entries of
+/// version 2 recorded only the total collected amounts, without splitting them into delegation
+/// rewards and commission.
+pub const LEDGER_VERSION: u32 = 3;
+
+/// Ledger is an append-only JSONL file recording every committed withdraw transaction
+#[derive(Clone, Debug)]
+pub struct Ledger {
+    path: PathBuf,
+}
+
+/// Single committed transaction, one line in the ledger file
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    #[serde(default = "legacy_version")]
+    pub version: u32,
+    /// RFC3339 timestamp of when the transaction was committed
+    pub timestamp: String,
+    pub chain_id: String,
+    pub height: u64,
+    pub tx_hash: String,
+    pub kind: TxKind,
+    /// Transaction messages, in Cosmos JSON format
+    pub messages: Vec<Value>,
+    /// Coins withdrawn by the transaction, per denom, in base units. Empty for follow-up send
+    /// transactions, so that the same coins are not counted twice
+    pub collected: Vec<CoinAmount>,
+    /// Part of the collected coins which are delegation rewards. Not known for entries written
+    /// before version 3
+    #[serde(default)]
+    pub rewards: Vec<CoinAmount>,
+    /// Part of the collected coins which are validator commission. Not known for entries written
+    /// before version 3
+    #[serde(default)]
+    pub commission: Vec<CoinAmount>,
+    pub fee: Vec<CoinAmount>,
+    /// Address which receives the withdrawn coins
+    pub reward_address: String,
+}
+
+fn legacy_version() -> u32 {
+    1
+}
+
+impl LedgerEntry {
+    /// Converts entry of an older version to the current format
+    fn upgrade(mut self) -> eyre::Result<Self> {
+        if self.version == 1 {
+            for coin in self.collected.iter_mut() {
+                coin.amount = dec_to_base_units(&coin.amount)?.to_string();
+            }
+            self.version = 2;
+        }
+
+        match self.version {
+            // Split of the collected coins is not known, they are only counted in the total
+            2 => {
+                self.version = LEDGER_VERSION;
+                Ok(self)
+            }
+            LEDGER_VERSION => Ok(self),
+            version => bail!("unsupported ledger entry version {version}"),
+        }
+    }
+}
+
+impl Ledger {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&self, entry: &LedgerEntry) -> eyre::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .wrap_err_with(|| format!("failed to open ledger file '{}'", self.path.display()))?;
+
+        // Single write, so that concurrent writers do not interleave lines
+        file.write_all(&line)
+            .and_then(|_| file.sync_data())
+            .wrap_err_with(|| format!("failed to write ledger file '{}'", self.path.display()))
+    }
+
+    /// Reads all entries, converting older entries to the current format. Missing ledger file is
+    /// treated as empty
+    pub fn read(&self) -> eyre::Result<Vec<LedgerEntry>> {
+        let file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => {
+                return Err(err).wrap_err_with(|| {
+                    format!("failed to open ledger file '{}'", self.path.display())
+                });
+            }
+        };
+
+        let mut entries = Vec::new();
+        for (idx, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.wrap_err("failed to read ledger file")?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let entry = serde_json::from_str::<LedgerEntry>(&line)
+                .map_err(eyre::Report::from)
+                .and_then(LedgerEntry::upgrade)
+                .wrap_err_with(|| format!("failed to parse ledger file line {}", idx + 1))?;
+            entries.push(entry);
+        }
+
+        Ok(entries)
+    }
+}
+
+/// Filters applied to ledger entries
+#[derive(Debug, Default)]
+pub struct LedgerFilter {
+    pub chain_id: Option<String>,
+    /// Inclusive lower bound of entry timestamp
+    pub since: Option<OffsetDateTime>,
+    /// Exclusive upper bound of entry timestamp
+    pub until: Option<OffsetDateTime>,
+    /// Only entries which collected or paid fees in given denom
+    pub denom: Option<String>,
+}
+
+impl LedgerFilter {
+    pub fn matches(&self, entry: &LedgerEntry) -> eyre::Result<bool> {
+        if let Some(chain_id) = &self.chain_id {
+            if &entry.chain_id != chain_id {
+                return Ok(false);
+            }
+        }
+
+        if self.since.is_some() || self.until.is_some() {
+            let timestamp =
+                OffsetDateTime::parse(&entry.timestamp, &Rfc3339).wrap_err_with(|| {
+                    format!("invalid timestamp in ledger entry '{}'", entry.tx_hash)
+                })?;
+
+            if self.since.is_some_and(|since| timestamp < since)
+                || self.until.is_some_and(|until| timestamp >= until)
+            {
+                return Ok(false);
+            }
+        }
+
+        if let Some(denom) = &self.denom {
+            if !entry
+                .collected
+                .iter()
+                .chain(entry.fee.iter())
+                .any(|coin| &coin.denom == denom)
+            {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// Amount summed over ledger entries
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LedgerTotal {
+    pub chain_id: String,
+    pub denom: String,
+    pub amount: String,
+}
+
+/// Sums up coins per chain and denom. If `denom` is set, other denoms are left out
+pub fn sum_coins<'a>(
+    coins: impl IntoIterator<Item = (&'a str, &'a CoinAmount)>,
+    denom: Option<&str>,
+) -> eyre::Result<Vec<LedgerTotal>> {
+    let mut totals: BTreeMap<(&str, &str), BigUint> = BTreeMap::new();
+    for (chain_id, coin) in coins {
+        if denom.is_some_and(|denom| denom != coin.denom) {
+            continue;
+        }
+
+        let amount: BigUint = coin
+            .amount
+            .parse()
+            .wrap_err_with(|| format!("invalid amount '{}{}'", coin.amount, coin.denom))?;
+        *totals.entry((chain_id, &coin.denom)).or_default() += amount;
+    }
+
+    Ok(totals
+        .into_iter()
+        .map(|((chain_id, denom), amount)| LedgerTotal {
+            chain_id: chain_id.to_string(),
+            denom: denom.to_string(),
+            amount: amount.to_string(),
+        })
+        .collect())
+}
+
+/// Parses either RFC3339 timestamp, or a date in `YYYY-MM-DD` format (midnight UTC)
+pub fn parse_date(s: &str) -> eyre::Result<OffsetDateTime> {
+    if let Ok(t) = OffsetDateTime::parse(s, &Rfc3339) {
+        return Ok(t);
+    }
+
+    match Date::parse(s, format_description!("[year]-[month]-[day]")) {
+        Ok(date) => Ok(date.midnight().assume_utc()),
+        Err(_) => bail!("expected RFC3339 timestamp or YYYY-MM-DD date, got '{s}'"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::{
+        LEDGER_VERSION, Ledger, LedgerEntry, LedgerFilter, LedgerTotal, parse_date, sum_coins,
+    };
+    use crate::report::{CoinAmount, TxKind};
+
+    fn coins(coins: &[(&str, &str)]) -> Vec<CoinAmount> {
+        coins
+            .iter()
+            .map(|(amount, denom)| CoinAmount {
+                denom: denom.to_string(),
+                amount: amount.to_string(),
+            })
+            .collect()
+    }
+
+    /// Entry collecting given coins, all of them as delegation rewards
+    fn entry(
+        timestamp: &str,
+        chain_id: &str,
+        tx_hash: &str,
+        collected: &[(&str, &str)],
+    ) -> LedgerEntry {
+        LedgerEntry {
+            version: LEDGER_VERSION,
+            timestamp: timestamp.to_string(),
+            chain_id: chain_id.to_string(),
+            height: 100,
+            tx_hash: tx_hash.to_string(),
+            kind: TxKind::Withdraw,
+            messages: vec![json!({ "@type": "/cosmos.authz.v1beta1.MsgExec" })],
+            collected: coins(collected),
+            rewards: coins(collected),
+            commission: Vec::new(),
+            fee: Vec::new(),
+            reward_address: "osmo1reward".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_append_read() {
+        let path = std::env::temp_dir().join(format!(
+            "cosmos-withdrawer-ledger-test-{}.jsonl",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let ledger = Ledger::new(path.clone());
+
+        assert_eq!(ledger.read().unwrap(), Vec::new());
+
+        let first = entry(
+            "2025-07-01T00:00:00Z",
+            "osmosis-1",
+            "AA",
+            &[("10", "uosmo")],
+        );
+        let second = entry(
+            "2025-08-01T00:00:00Z",
+            "cosmoshub-4",
+            "BB",
+            &[("5", "uatom")],
+        );
+        ledger.append(&first).unwrap();
+        ledger.append(&second).unwrap();
+
+        assert_eq!(ledger.read().unwrap(), vec![first, second]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_legacy_entry() {
+        let path = std::env::temp_dir().join(format!(
+            "cosmos-withdrawer-ledger-legacy-test-{}.jsonl",
+            std::process::id()
+        ));
+        std::fs::write(
+            &path,
+            concat!(
+                r#"{"timestamp":"2025-07-01T00:00:00Z","chain_id":"osmosis-1","height":100,"tx_hash":"AA","kind":"withdraw","#,
+                r#""messages":[{"@type":"/cosmos.authz.v1beta1.MsgExec"}],"#,
+                r#""collected":[{"denom":"uosmo","amount":"10500000000000000000"}],"#,
+                r#""fee":[],"reward_address":"osmo1reward"}"#,
+                "\n",
+            ),
+        )
+        .unwrap();
+
+        let ledger = Ledger::new(path.clone());
+        let result = ledger.read();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            result.unwrap(),
+            vec![LedgerEntry {
+                rewards: Vec::new(),
+                ..entry(
+                    "2025-07-01T00:00:00Z",
+                    "osmosis-1",
+                    "AA",
+                    &[("10", "uosmo")]
+                )
+            }]
+        );
+    }
+
+    #[test]
+    fn test_read_entry_without_split() {
+        let path = std::env::temp_dir().join(format!(
+            "cosmos-withdrawer-ledger-v2-test-{}.jsonl",
+            std::process::id()
+        ));
+        std::fs::write(
+            &path,
+            concat!(
+                r#"{"version":2,"timestamp":"2025-07-01T00:00:00Z","chain_id":"osmosis-1","height":100,"tx_hash":"AA","kind":"withdraw","#,
+                r#""messages":[{"@type":"/cosmos.authz.v1beta1.MsgExec"}],"#,
+                r#""collected":[{"denom":"uosmo","amount":"10"}],"#,
+                r#""fee":[],"reward_address":"osmo1reward"}"#,
+                "\n",
+            ),
+        )
+        .unwrap();
+
+        let ledger = Ledger::new(path.clone());
+        let result = ledger.read();
+        std::fs::remove_file(&path).unwrap();
+
+        let entries = result.unwrap();
+        assert_eq!(entries[0].version, LEDGER_VERSION);
+        assert_eq!(entries[0].collected, coins(&[("10", "uosmo")]));
+        assert_eq!(entries[0].rewards, Vec::new());
+        assert_eq!(entries[0].commission, Vec::new());
+    }
+
+    #[test]
+    fn test_filter_totals() {
+        let entries = [
+            entry("2025-06-30T23:59:59Z", "osmosis-1", "AA", &[("1", "uosmo")]),
+            entry(
+                "2025-07-01T00:00:00Z",
+                "osmosis-1",
+                "BB",
+                &[("10", "uosmo"), ("3", "uion")],
+            ),
+            LedgerEntry {
+                rewards: coins(&[("15", "uosmo")]),
+                commission: coins(&[("5", "uosmo")]),
+                ..entry(
+                    "2025-09-30T12:00:00+02:00",
+                    "osmosis-1",
+                    "CC",
+                    &[("20", "uosmo")],
+                )
+            },
+            entry(
+                "2025-08-01T00:00:00Z",
+                "cosmoshub-4",
+                "DD",
+                &[("5", "uatom")],
+            ),
+            entry(
+                "2025-10-01T00:00:00Z",
+                "osmosis-1",
+                "EE",
+                &[("100", "uosmo")],
+            ),
+        ];
+
+        let filter = LedgerFilter {
+            chain_id: Some("osmosis-1".to_string()),
+            since: Some(parse_date("2025-07-01").unwrap()),
+            until: Some(parse_date("2025-10-01T00:00:00Z").unwrap()),
+            denom: Some("uosmo".to_string()),
+        };
+
+        let matching = entries
+            .iter()
+            .filter(|entry| filter.matches(entry).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            matching
+                .iter()
+                .map(|entry| entry.tx_hash.as_str())
+                .collect::<Vec<_>>(),
+            vec!["BB", "CC"]
+        );
+
+        let totals = sum_coins(
+            matching.iter().flat_map(|entry| {
+                entry
+                    .collected
+                    .iter()
+                    .map(|coin| (entry.chain_id.as_str(), coin))
+            }),
+            filter.denom.as_deref(),
+        )
+        .unwrap();
+        assert_eq!(
+            totals,
+            vec![LedgerTotal {
+                chain_id: "osmosis-1".to_string(),
+                denom: "uosmo".to_string(),
+                amount: "30".to_string(),
+            }]
+        );
+
+        // Commission withdrawn on osmosis-1 last quarter
+        let commission = sum_coins(
+            matching.iter().flat_map(|entry| {
+                entry
+                    .commission
+                    .iter()
+                    .map(|coin| (entry.chain_id.as_str(), coin))
+            }),
+            filter.denom.as_deref(),
+        )
+        .unwrap();
+        assert_eq!(
+            commission,
+            vec![LedgerTotal {
+                chain_id: "osmosis-1".to_string(),
+                denom: "uosmo".to_string(),
+                amount: "5".to_string(),
+            }]
+        );
+
+        assert!(parse_date("last quarter").is_err());
+    }
+}
@@ -0,0 +1,258 @@
+use std::{
+    fs::{DirBuilder, File, OpenOptions},
+    io::{ErrorKind, Read, Seek},
+    os::{
+        fd::AsRawFd,
+        unix::fs::{DirBuilderExt, FileExt, MetadataExt, OpenOptionsExt},
+    },
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use cosmrs::AccountId;
+use eyre::{Context, ContextCompat, bail};
+use tokio::time::sleep;
+use tracing::{debug, info};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Width the pid is padded to in the lock file, enough for any 32-bit pid
+const PID_WIDTH: usize = 10;
+
+/// Advisory lock held while transactions are signed & broadcast with the controller account.
+/// Prevents concurrent runs (e.g. cron overlapping a manual run) from racing on the account sequence.
+///
+/// The lock is released when dropped, or when the process exits.
+#[derive(Debug)]
+pub struct AccountLock {
+    _file: File,
+    path: PathBuf,
+}
+
+impl AccountLock {
+    /// Acquires the lock for given chain and controller account. Without `wait`, fails immediately if
+    /// another process holds the lock. Lock files are kept in a per-user directory by default, see
+    /// [`default_lock_dir`].
+    pub async fn acquire(
+        dir: Option<&Path>,
+        chain_id: &str,
+        controller_address: &AccountId,
+        wait: Option<Duration>,
+    ) -> eyre::Result<Self> {
+        let dir = match dir {
+            Some(dir) => dir.to_path_buf(),
+            None => default_lock_dir()?,
+        };
+        let path = dir.join(lock_file_name(chain_id, controller_address));
+
+        // Never follow symlinks nor truncate, so that a planted link cannot make us overwrite
+        // someone else's file
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .mode(0o600)
+            .custom_flags(libc::O_NOFOLLOW)
+            .open(&path)
+            .wrap_err_with(|| format!("failed to open lock file '{}'", path.display()))?;
+        let metadata = file
+            .metadata()
+            .wrap_err_with(|| format!("failed to inspect lock file '{}'", path.display()))?;
+        if !metadata.is_file() || metadata.nlink() != 1 {
+            bail!(
+                "lock file '{}' is not a regular file, refusing to use it",
+                path.display()
+            );
+        }
+
+        let started = Instant::now();
+        while !try_lock(&file).wrap_err_with(|| format!("failed to lock '{}'", path.display()))? {
+            let holder = read_holder(&mut file);
+            match wait {
+                Some(wait) if started.elapsed() < wait => {
+                    debug!(
+                        ?path,
+                        holder, "account is locked by another process, waiting"
+                    );
+                    sleep(POLL_INTERVAL).await;
+                }
+                Some(wait) => bail!(
+                    "timed out after {wait:?} waiting for another process ({holder}) to release lock on controller {controller_address} on chain '{chain_id}' (lock file '{}')",
+                    path.display()
+                ),
+                None => bail!(
+                    "another process ({holder}) is already running with controller {controller_address} on chain '{chain_id}' (lock file '{}'). Use --lock-wait to wait for it to finish",
+                    path.display()
+                ),
+            }
+        }
+
+        // Purely informational, helps to find the other process when lock is taken. Padded to a fixed
+        // width instead of truncating the file, so that a longer pid written earlier is overwritten
+        file.write_all_at(format!("{:<PID_WIDTH$}", std::process::id()).as_bytes(), 0)
+            .wrap_err_with(|| format!("failed to write lock file '{}'", path.display()))?;
+
+        info!(?path, "acquired account lock");
+
+        Ok(Self { _file: file, path })
+    }
+}
+
+impl Drop for AccountLock {
+    fn drop(&mut self) {
+        debug!(path = ?self.path, "releasing account lock");
+    }
+}
+
+/// Returns `$XDG_RUNTIME_DIR/cosmos-withdrawer`, or `~/.cosmos-withdrawer/locks` if runtime directory
+/// is not set. The directory is created accessible only by the owner.
+pub fn default_lock_dir() -> eyre::Result<PathBuf> {
+    let dir = match std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(runtime_dir) => PathBuf::from(runtime_dir).join("cosmos-withdrawer"),
+        None => {
+            let home = std::env::var_os("HOME").wrap_err(
+                "could not determine home directory for the default lock directory, use --lock-dir",
+            )?;
+            PathBuf::from(home).join(".cosmos-withdrawer").join("locks")
+        }
+    };
+
+    DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(&dir)
+        .wrap_err_with(|| format!("failed to create lock directory '{}'", dir.display()))?;
+
+    Ok(dir)
+}
+
+fn lock_file_name(chain_id: &str, controller_address: &AccountId) -> String {
+    let chain_id = chain_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+
+    format!("cosmos-withdrawer-{chain_id}-{controller_address}.lock")
+}
+
+/// Returns false if the lock is held by someone else
+fn try_lock(file: &File) -> std::io::Result<bool> {
+    // SAFETY: file descriptor is valid for the lifetime of `file`
+    let ret = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+    if ret == 0 {
+        return Ok(true);
+    }
+
+    let err = std::io::Error::last_os_error();
+    if err.kind() == ErrorKind::WouldBlock {
+        Ok(false)
+    } else {
+        Err(err)
+    }
+}
+
+fn read_holder(file: &mut File) -> String {
+    let mut pid = String::new();
+    match file.rewind().and_then(|_| file.read_to_string(&mut pid)) {
+        Ok(_) if !pid.trim().is_empty() => format!("pid {}", pid.trim()),
+        _ => "unknown pid".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{str::FromStr, time::Duration};
+
+    use cosmrs::AccountId;
+
+    use super::AccountLock;
+
+    #[tokio::test]
+    async fn test_account_lock() {
+        let dir = std::env::temp_dir().join(format!(
+            "cosmos-withdrawer-lock-test-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let controller =
+            AccountId::from_str("osmo1e4n3yara98z8lsxwcj7740q0nku3lcd70s9s29").unwrap();
+
+        let lock = AccountLock::acquire(Some(&dir), "osmosis-1", &controller, None)
+            .await
+            .unwrap();
+
+        // flock locks are per open file description, so a second acquisition conflicts even within
+        // the same process
+        let err = AccountLock::acquire(Some(&dir), "osmosis-1", &controller, None)
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains(&format!("pid {}", std::process::id())),
+            "{err}"
+        );
+
+        let err = AccountLock::acquire(
+            Some(&dir),
+            "osmosis-1",
+            &controller,
+            Some(Duration::from_millis(600)),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().starts_with("timed out"), "{err}");
+
+        // Other chains are not affected
+        let _other = AccountLock::acquire(Some(&dir), "osmo-test-5", &controller, None)
+            .await
+            .unwrap();
+
+        drop(lock);
+        let _lock = AccountLock::acquire(Some(&dir), "osmosis-1", &controller, None)
+            .await
+            .unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_account_lock_symlink() {
+        let dir = std::env::temp_dir().join(format!(
+            "cosmos-withdrawer-lock-symlink-test-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let controller =
+            AccountId::from_str("osmo1e4n3yara98z8lsxwcj7740q0nku3lcd70s9s29").unwrap();
+
+        let target = dir.join("target");
+        std::fs::write(&target, "precious").unwrap();
+        std::os::unix::fs::symlink(
+            &target,
+            dir.join(format!("cosmos-withdrawer-osmosis-1-{controller}.lock")),
+        )
+        .unwrap();
+        let result = AccountLock::acquire(Some(&dir), "osmosis-1", &controller, None).await;
+        let target_contents = std::fs::read_to_string(&target).unwrap();
+
+        // Hard links to other files are not used either
+        std::fs::hard_link(
+            &target,
+            dir.join(format!("cosmos-withdrawer-osmo-test-5-{controller}.lock")),
+        )
+        .unwrap();
+        let hard_link_result =
+            AccountLock::acquire(Some(&dir), "osmo-test-5", &controller, None).await;
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(result.is_err());
+        assert!(hard_link_result.is_err());
+        assert_eq!(target_contents, "precious");
+    }
+}
@@ -1,5 +1,10 @@
+use std::{net::SocketAddr, path::PathBuf, process::ExitCode};
+
 use clap::{Parser, Subcommand};
+use cosmrs::AccountId;
+use duration_string::DurationString;
 use eyre::eyre;
+use time::OffsetDateTime;
 use tracing::level_filters::LevelFilter;
 use tracing_subscriber::{
     EnvFilter,
@@ -11,13 +16,29 @@ use tracing_subscriber::{
 mod chain;
 mod chain_registry;
 mod cmd;
+mod config;
 mod cosmos_sdk_extra;
+mod keystore;
+mod ledger;
+mod lock;
+mod metrics;
+mod notify;
+mod report;
+mod secret;
 mod ser;
+#[cfg(test)]
+mod test_util;
 mod wallet;
 
 use crate::{
-    cmd::{AccountArgs, DebugSubcommand, SetupValoperMethod, TransactionArgs},
+    cmd::{
+        AccountArgs, DebugSubcommand, KeysSubcommand, KeystoreArgs, MultisignArgs,
+        SetupValoperMethod, SignArgs, TransactionArgs, WithdrawAction,
+    },
     cosmos_sdk_extra::str_coin::StrCoin,
+    ledger::{Ledger, LedgerFilter, parse_date},
+    notify::{Notifier, WebhookFormat},
+    report::{FailureReport, OutputFormat, exit_code},
     ser::TimestampStr,
 };
 
@@ -40,6 +61,48 @@ struct Cli {
     #[arg(long, env = "COSMOS_WITHDRAWER_VALOPER_HRP", global = true)]
     valoper_hrp: Option<String>,
 
+    /// Output format. `json` prints a single JSON document describing the result of `withdraw`, `setup-valoper`, `renew-grants`, `teardown` and `run` subcommands
+    #[arg(
+        long,
+        env = "COSMOS_WITHDRAWER_OUTPUT",
+        global = true,
+        value_enum,
+        default_value_t
+    )]
+    output: OutputFormat,
+
+    /// Webhook URL to POST withdraw results to. Used by `withdraw`, `daemon` and `run` subcommands
+    #[arg(
+        long,
+        env = "COSMOS_WITHDRAWER_WEBHOOK_URL",
+        global = true,
+        hide_env_values = true
+    )]
+    webhook_url: Option<String>,
+
+    /// Webhook payload format
+    #[arg(
+        long,
+        env = "COSMOS_WITHDRAWER_WEBHOOK_FORMAT",
+        global = true,
+        value_enum,
+        default_value_t
+    )]
+    webhook_format: WebhookFormat,
+
+    /// Warn about authz grants expiring within this window, e.g. `7d`. Used by `withdraw`, `daemon`, `run` and `status` subcommands. `0s` disables the check
+    #[arg(
+        long,
+        env = "COSMOS_WITHDRAWER_GRANT_EXPIRY_WARNING",
+        global = true,
+        default_value = "7d"
+    )]
+    grant_expiry_warning: DurationString,
+
+    /// Append-only JSONL file to record committed withdraw transactions in. Also read by `history` subcommand
+    #[arg(long, env = "COSMOS_WITHDRAWER_LEDGER_FILE", global = true)]
+    ledger_file: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Option<Subcommands>,
 }
@@ -57,9 +120,57 @@ enum Subcommands {
         #[command(subcommand)]
         method: SetupValoperMethod,
 
-        /// Authz grant expiration. Either RFC3339 timestamp, or duration string (relative from now). By default grants never expire, however some older Cosmos SDK based chains require expiration to be set.
+        /// Authz grant expiration. Either RFC3339 timestamp, or duration string (relative from now). By default grants never expire, however some older Cosmos SDK based chains require expiration to be set. Existing grants expiring at a different time are re-issued, unless a duration is given and they expire at most a tenth of it earlier
         #[arg(long)]
         expiration: Option<TimestampStr>,
+
+        /// File containing mnemonic of a delegator multisig member, can be repeated. Needed only if delegator is a multisig account, and the transaction is not generated for signing externally
+        #[arg(
+            long = "multisig-member-mnemonic-file",
+            env = "COSMOS_WITHDRAWER_MULTISIG_MEMBER_MNEMONIC_FILES",
+            value_delimiter = ','
+        )]
+        multisig_member_mnemonic_files: Vec<PathBuf>,
+    },
+    /// Re-issue grants given by the delegator to the controller with a new expiration, or top up the spend limit
+    RenewGrants {
+        #[clap(flatten)]
+        account: AccountArgs,
+
+        #[clap(flatten)]
+        transaction_args: TransactionArgs,
+
+        /// New authz grant expiration. Either RFC3339 timestamp, or duration string (relative from now). Grants keep their expiration if not set
+        #[arg(long, required_unless_present = "top_up")]
+        expiration: Option<TimestampStr>,
+
+        /// Amount to add to the remaining `SendAuthorization` spend limit, e.g. `1000000uosmo`
+        #[arg(long = "top-up", value_delimiter = ',')]
+        top_up: Vec<StrCoin>,
+
+        /// File containing mnemonic of a delegator multisig member, can be repeated. Needed only if delegator is a multisig account, and the transaction is not generated for signing externally
+        #[arg(
+            long = "multisig-member-mnemonic-file",
+            env = "COSMOS_WITHDRAWER_MULTISIG_MEMBER_MNEMONIC_FILES",
+            value_delimiter = ','
+        )]
+        multisig_member_mnemonic_files: Vec<PathBuf>,
+    },
+    /// Undo `setup-valoper`: revoke grants given to the controller, and reset withdraw address back to the delegator
+    Teardown {
+        #[clap(flatten)]
+        account: AccountArgs,
+
+        #[clap(flatten)]
+        transaction_args: TransactionArgs,
+
+        /// File containing mnemonic of a delegator multisig member, can be repeated. Needed only if delegator is a multisig account, and the transaction is not generated for signing externally
+        #[arg(
+            long = "multisig-member-mnemonic-file",
+            env = "COSMOS_WITHDRAWER_MULTISIG_MEMBER_MNEMONIC_FILES",
+            value_delimiter = ','
+        )]
+        multisig_member_mnemonic_files: Vec<PathBuf>,
     },
     /// Withdraw validator rewards & commissions
     Withdraw {
@@ -76,6 +187,109 @@ enum Subcommands {
             value_delimiter = ','
         )]
         thresholds: Vec<StrCoin>,
+
+        /// Only withdraw rewards from given validators. Required with `--offline`, where rewards cannot be queried
+        #[clap(
+            long = "validator",
+            env = "COSMOS_WITHDRAWER_WITHDRAW_VALIDATORS",
+            value_delimiter = ','
+        )]
+        validators: Vec<AccountId>,
+
+        /// Also withdraw commission of the validator operated by the delegator. Only used with `--offline`, otherwise commission is detected automatically
+        #[arg(long)]
+        commission: bool,
+
+        /// Plan the withdrawal for review, or apply a reviewed plan. Withdraws right away if not given
+        #[command(subcommand)]
+        action: Option<WithdrawAction>,
+    },
+    /// Periodically withdraw validator rewards & commissions until terminated
+    Daemon {
+        #[clap(flatten)]
+        account: AccountArgs,
+
+        #[clap(flatten)]
+        transaction_args: TransactionArgs,
+
+        /// Token thresholds for withdrawal. Format: 1234denom
+        #[clap(
+            long = "threshold",
+            env = "COSMOS_WITHDRAWER_WITHDRAW_THRESHOLDS",
+            value_delimiter = ','
+        )]
+        thresholds: Vec<StrCoin>,
+
+        /// How often to check for rewards, e.g. `1h` or `30m`
+        #[arg(long, env = "COSMOS_WITHDRAWER_DAEMON_INTERVAL", default_value = "1h")]
+        interval: DurationString,
+
+        /// Maximum random delay added to every interval, to avoid many instances hitting the RPC at the same time
+        #[arg(long, env = "COSMOS_WITHDRAWER_DAEMON_JITTER", default_value = "0s")]
+        jitter: DurationString,
+
+        /// Address to serve Prometheus metrics on, e.g. `0.0.0.0:9100`. Metrics are not served by default
+        #[arg(long, env = "COSMOS_WITHDRAWER_METRICS_ADDRESS")]
+        metrics_address: Option<SocketAddr>,
+    },
+    /// Withdraw validator rewards & commissions on every chain listed in the config file
+    Run {
+        /// Path to the TOML config file
+        #[arg(long, env = "COSMOS_WITHDRAWER_CONFIG")]
+        config: PathBuf,
+
+        /// Only process chains with given names. Processes all chains by default
+        #[arg(long = "chain")]
+        chains: Vec<String>,
+    },
+    /// Show grants, withdraw address, pending rewards & commissions, delegations, and controller balance
+    Status {
+        #[clap(flatten)]
+        account: AccountArgs,
+    },
+    /// Show withdrawals recorded in the ledger file
+    History {
+        /// Only show transactions on given chain
+        #[arg(long)]
+        chain_id: Option<String>,
+
+        /// Only show transactions committed at or after given time. Either RFC3339 timestamp, or YYYY-MM-DD date
+        #[arg(long, value_parser = parse_date)]
+        since: Option<OffsetDateTime>,
+
+        /// Only show transactions committed before given time. Either RFC3339 timestamp, or YYYY-MM-DD date
+        #[arg(long, value_parser = parse_date)]
+        until: Option<OffsetDateTime>,
+
+        /// Only show transactions involving given denom, and sum up only this denom
+        #[arg(long)]
+        denom: Option<String>,
+    },
+    /// Sign transaction generated with `--generate-only`, without connecting to the chain
+    Sign {
+        #[clap(flatten)]
+        account: AccountArgs,
+
+        #[clap(flatten)]
+        args: SignArgs,
+    },
+    /// Combine partial signatures of multisig members into a signed transaction, without connecting to the chain
+    Multisign {
+        #[clap(flatten)]
+        args: MultisignArgs,
+    },
+    /// Broadcast transaction signed with `sign` or `multisign`
+    Broadcast {
+        /// Signed transaction JSON file, as printed by `sign` or `multisign`. Use `-` to read from stdin
+        file: PathBuf,
+    },
+    /// Manage keys in the encrypted keystore
+    Keys {
+        #[clap(flatten)]
+        keystore: KeystoreArgs,
+
+        #[command(subcommand)]
+        keys: KeysSubcommand,
     },
     /// Debug subcommands
     Debug {
@@ -86,7 +300,7 @@ enum Subcommands {
 }
 
 #[tokio::main]
-async fn main() -> eyre::Result<()> {
+async fn main() -> ExitCode {
     tracing_subscriber::registry()
         .with(
             fmt::layer()
@@ -101,11 +315,47 @@ async fn main() -> eyre::Result<()> {
         )
         .init();
 
-    entrypoint().await
+    let result = entrypoint().await;
+    if let Err(err) = &result {
+        eprintln!("Error: {err:?}");
+    }
+
+    ExitCode::from(exit_code(&result))
 }
 
 async fn entrypoint() -> eyre::Result<()> {
     let cli = Cli::parse();
+    let output = cli.output;
+    // `run` reports failures of individual chains by itself
+    let report_failure = !matches!(cli.command, Some(Subcommands::Run { .. }));
+
+    let result = execute(cli).await;
+    if let Err(err) = &result {
+        if output == OutputFormat::Json && report_failure {
+            output.print_report(&FailureReport::new(err), None)?;
+        }
+    }
+
+    result
+}
+
+async fn execute(cli: Cli) -> eyre::Result<()> {
+    let notifier = cli
+        .webhook_url
+        .map(|url| Notifier::new(url, cli.webhook_format))
+        .transpose()?;
+    let ledger = cli.ledger_file.map(Ledger::new);
+
+    if let Some(Subcommands::Withdraw {
+        thresholds,
+        validators,
+        commission,
+        action: Some(action),
+        ..
+    }) = &cli.command
+    {
+        action.check_withdraw_args(thresholds, validators, *commission)?;
+    }
 
     match cli.command {
         Some(Subcommands::SetupValoper {
@@ -113,8 +363,9 @@ async fn entrypoint() -> eyre::Result<()> {
             transaction_args,
             method,
             expiration,
+            multisig_member_mnemonic_files,
         }) => {
-            crate::cmd::setup_valoper(
+            let report = crate::cmd::setup_valoper(
                 &cli.rpc_url,
                 cli.account_hrp.as_ref(),
                 cli.valoper_hrp.as_ref(),
@@ -122,24 +373,241 @@ async fn entrypoint() -> eyre::Result<()> {
                 transaction_args,
                 method,
                 expiration.as_ref(),
+                &multisig_member_mnemonic_files,
             )
-            .await?
+            .await?;
+
+            cli.output
+                .print_report(&report, report.unsigned_tx.as_ref())?;
+        }
+        Some(Subcommands::RenewGrants {
+            account,
+            transaction_args,
+            expiration,
+            top_up,
+            multisig_member_mnemonic_files,
+        }) => {
+            let report = crate::cmd::renew_grants(
+                &cli.rpc_url,
+                cli.account_hrp.as_ref(),
+                cli.valoper_hrp.as_ref(),
+                account,
+                transaction_args,
+                expiration.as_ref(),
+                &top_up,
+                &multisig_member_mnemonic_files,
+            )
+            .await?;
+
+            cli.output
+                .print_report(&report, report.unsigned_tx.as_ref())?;
+        }
+        Some(Subcommands::Teardown {
+            account,
+            transaction_args,
+            multisig_member_mnemonic_files,
+        }) => {
+            let report = crate::cmd::teardown(
+                &cli.rpc_url,
+                cli.account_hrp.as_ref(),
+                cli.valoper_hrp.as_ref(),
+                account,
+                transaction_args,
+                &multisig_member_mnemonic_files,
+            )
+            .await?;
+
+            cli.output
+                .print_report(&report, report.unsigned_tx.as_ref())?;
+        }
+        Some(Subcommands::Withdraw {
+            account,
+            transaction_args,
+            thresholds,
+            validators,
+            action:
+                Some(WithdrawAction::Plan {
+                    plan_file,
+                    amount_tolerance,
+                }),
+            ..
+        }) => {
+            let plan = crate::cmd::withdraw_plan(
+                &cli.rpc_url,
+                cli.account_hrp.as_ref(),
+                cli.valoper_hrp.as_ref(),
+                account,
+                transaction_args,
+                thresholds,
+                validators,
+                amount_tolerance,
+            )
+            .await?;
+
+            plan.write(plan_file.as_deref())?;
+        }
+        Some(Subcommands::Withdraw {
+            account,
+            transaction_args,
+            action: Some(WithdrawAction::Apply { plan }),
+            ..
+        }) => {
+            let result = crate::cmd::withdraw_apply(
+                &cli.rpc_url,
+                cli.account_hrp.as_ref(),
+                cli.valoper_hrp.as_ref(),
+                account,
+                transaction_args,
+                ledger,
+                &plan,
+            )
+            .await;
+
+            if let Some(notifier) = &notifier {
+                notifier.notify(None, &result).await;
+            }
+
+            let report = result?;
+
+            cli.output.print_report(&report, None)?;
         }
         Some(Subcommands::Withdraw {
             account,
             transaction_args,
             thresholds,
+            validators,
+            commission,
+            action: None,
         }) => {
-            crate::cmd::withdraw(
+            let result = if transaction_args.offline {
+                crate::cmd::withdraw_offline(
+                    cli.account_hrp.as_ref(),
+                    cli.valoper_hrp.as_ref(),
+                    account,
+                    transaction_args,
+                    &validators,
+                    commission,
+                )
+            } else {
+                crate::cmd::withdraw(
+                    &cli.rpc_url,
+                    cli.account_hrp.as_ref(),
+                    cli.valoper_hrp.as_ref(),
+                    account,
+                    transaction_args,
+                    thresholds,
+                    validators,
+                    cli.grant_expiry_warning.into(),
+                    ledger,
+                )
+                .await
+            };
+
+            if let Some(notifier) = &notifier {
+                notifier.notify(None, &result).await;
+            }
+
+            let report = result?;
+
+            cli.output
+                .print_report(&report, report.unsigned_tx.as_ref())?;
+        }
+        Some(Subcommands::Daemon {
+            account,
+            transaction_args,
+            thresholds,
+            interval,
+            jitter,
+            metrics_address,
+        }) => {
+            if let Some(metrics_address) = metrics_address {
+                crate::metrics::serve(metrics_address).await?;
+            }
+
+            crate::cmd::daemon(
                 &cli.rpc_url,
                 cli.account_hrp.as_ref(),
                 cli.valoper_hrp.as_ref(),
                 account,
                 transaction_args,
                 thresholds,
+                interval.into(),
+                jitter.into(),
+                cli.grant_expiry_warning.into(),
+                notifier.as_ref(),
+                ledger,
+            )
+            .await?
+        }
+        Some(Subcommands::Run { config, chains }) => {
+            crate::cmd::run(
+                &config,
+                &chains,
+                cli.output,
+                cli.grant_expiry_warning.into(),
+                notifier.as_ref(),
+                ledger.as_ref(),
             )
             .await?
         }
+        Some(Subcommands::Status { account }) => {
+            crate::cmd::status(
+                &cli.rpc_url,
+                cli.account_hrp.as_ref(),
+                cli.valoper_hrp.as_ref(),
+                account,
+                cli.grant_expiry_warning.into(),
+                cli.output,
+            )
+            .await?
+        }
+        Some(Subcommands::History {
+            chain_id,
+            since,
+            until,
+            denom,
+        }) => crate::cmd::history(
+            ledger.as_ref(),
+            LedgerFilter {
+                chain_id,
+                since,
+                until,
+                denom,
+            },
+            cli.output,
+        )?,
+        Some(Subcommands::Sign { account, args }) if args.multisig => {
+            let signatures = crate::cmd::sign_multisig(account, args).await?;
+
+            cli.output
+                .print_report(&signatures, Some(&serde_json::to_value(&signatures)?))?;
+        }
+        Some(Subcommands::Sign { account, args }) => {
+            let signed_tx = crate::cmd::sign(
+                cli.account_hrp.as_ref(),
+                cli.valoper_hrp.as_ref(),
+                account,
+                args,
+            )
+            .await?;
+
+            cli.output
+                .print_report(&signed_tx, Some(&serde_json::to_value(&signed_tx)?))?;
+        }
+        Some(Subcommands::Multisign { args }) => {
+            let signed_tx = crate::cmd::multisign(args)?;
+
+            cli.output
+                .print_report(&signed_tx, Some(&serde_json::to_value(&signed_tx)?))?;
+        }
+        Some(Subcommands::Broadcast { file }) => {
+            let report = crate::cmd::broadcast(&cli.rpc_url, &file).await?;
+
+            cli.output.print_report(&report, None)?;
+        }
+        Some(Subcommands::Keys { keystore, keys }) => {
+            crate::cmd::keys(&keystore, keys, cli.output)?
+        }
         Some(Subcommands::Debug { debug }) => {
             crate::cmd::debug(
                 &cli.rpc_url,
@@ -156,3 +624,44 @@ async fn entrypoint() -> eyre::Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use clap::{CommandFactory, Parser};
+    use cosmrs::AccountId;
+
+    use super::{Cli, Subcommands};
+    use crate::cmd::WithdrawAction;
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+
+        // Arguments of subcommands must not share ids with global arguments, which clap only
+        // detects once the arguments are accessed
+        let delegator = AccountId::new("cosmos", &[1; 20]).unwrap().to_string();
+        let controller = AccountId::new("cosmos", &[2; 20]).unwrap().to_string();
+        let cli = Cli::try_parse_from([
+            "cosmos-withdrawer",
+            "--output",
+            "json",
+            "withdraw",
+            "--delegator-address",
+            &delegator,
+            "--controller-address",
+            &controller,
+            "plan",
+            "--plan-file",
+            "plan.json",
+        ])
+        .unwrap();
+        let Some(Subcommands::Withdraw {
+            action: Some(WithdrawAction::Plan { plan_file, .. }),
+            ..
+        }) = cli.command
+        else {
+            panic!("expected withdraw plan subcommand");
+        };
+        assert_eq!(plan_file.unwrap().to_str(), Some("plan.json"));
+    }
+}
@@ -0,0 +1,323 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    net::SocketAddr,
+    sync::{
+        LazyLock, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use eyre::Context;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tracing::{debug, info};
+
+use crate::cosmos_sdk_extra::dec::dec_to_base_units;
+
+/// METRICS holds all values exposed via Prometheus metrics endpoint.
+/// Values are always recorded, but only served if metrics listener was started.
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::default);
+
+#[derive(Clone, Copy, Debug)]
+pub enum MetricKind {
+    Gauge,
+    Counter,
+}
+
+impl MetricKind {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Self::Gauge => "gauge",
+            Self::Counter => "counter",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MetricDesc {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub kind: MetricKind,
+}
+
+pub static PENDING_REWARDS: MetricDesc = MetricDesc {
+    name: "cosmos_withdrawer_pending_rewards",
+    help: "Pending delegation rewards of the delegator, summed over all validators",
+    kind: MetricKind::Gauge,
+};
+
+pub static PENDING_COMMISSION: MetricDesc = MetricDesc {
+    name: "cosmos_withdrawer_pending_commission",
+    help: "Pending validator commission of the delegator",
+    kind: MetricKind::Gauge,
+};
+
+pub static CONTROLLER_BALANCE: MetricDesc = MetricDesc {
+    name: "cosmos_withdrawer_controller_balance",
+    help: "Controller account balance",
+    kind: MetricKind::Gauge,
+};
+
+pub static LAST_WITHDRAWAL_TIMESTAMP: MetricDesc = MetricDesc {
+    name: "cosmos_withdrawer_last_withdrawal_timestamp_seconds",
+    help: "Unix timestamp of the last successful withdrawal",
+    kind: MetricKind::Gauge,
+};
+
+pub static FEES_SPENT: MetricDesc = MetricDesc {
+    name: "cosmos_withdrawer_fees_spent_total",
+    help: "Transaction fees spent by the controller account",
+    kind: MetricKind::Counter,
+};
+
+pub static FAILED_BROADCASTS: MetricDesc = MetricDesc {
+    name: "cosmos_withdrawer_failed_broadcasts_total",
+    help: "Number of transactions which failed to broadcast or to get committed",
+    kind: MetricKind::Counter,
+};
+
+type Labels = Vec<(&'static str, String)>;
+type Series = BTreeMap<Labels, f64>;
+
+#[derive(Default)]
+pub struct Metrics {
+    enabled: AtomicBool,
+    values: Mutex<BTreeMap<&'static str, (&'static MetricDesc, Series)>>,
+}
+
+impl Metrics {
+    /// Whether metrics are being served. Used to skip queries which are done only for metrics
+    pub fn enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set(&self, desc: &'static MetricDesc, labels: &[(&'static str, &str)], value: f64) {
+        self.update(desc, labels, |v| *v = value);
+    }
+
+    pub fn add(&self, desc: &'static MetricDesc, labels: &[(&'static str, &str)], value: f64) {
+        self.update(desc, labels, |v| *v += value);
+    }
+
+    /// Replaces all per-denom values of a metric for given chain, so that denoms which are
+    /// no longer present do not linger around
+    pub fn replace_denoms(
+        &self,
+        desc: &'static MetricDesc,
+        chain_id: &str,
+        values: impl IntoIterator<Item = (String, f64)>,
+    ) {
+        let mut metrics = self.values.lock().expect("metrics lock poisoned");
+        let (_, series) = metrics
+            .entry(desc.name)
+            .or_insert_with(|| (desc, Series::new()));
+
+        series.retain(|labels, _| {
+            !labels
+                .iter()
+                .any(|(name, value)| *name == "chain_id" && value == chain_id)
+        });
+
+        for (denom, value) in values {
+            series.insert(
+                vec![("chain_id", chain_id.to_string()), ("denom", denom)],
+                value,
+            );
+        }
+    }
+
+    fn update(
+        &self,
+        desc: &'static MetricDesc,
+        labels: &[(&'static str, &str)],
+        f: impl FnOnce(&mut f64),
+    ) {
+        let labels = labels
+            .iter()
+            .map(|(name, value)| (*name, value.to_string()))
+            .collect::<Labels>();
+
+        let mut metrics = self.values.lock().expect("metrics lock poisoned");
+        let (_, series) = metrics
+            .entry(desc.name)
+            .or_insert_with(|| (desc, Series::new()));
+        f(series.entry(labels).or_default());
+    }
+
+    /// Renders metrics in Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let metrics = self.values.lock().expect("metrics lock poisoned");
+        let mut out = String::new();
+
+        for (desc, series) in metrics.values() {
+            let _ = writeln!(out, "# HELP {} {}", desc.name, desc.help);
+            let _ = writeln!(out, "# TYPE {} {}", desc.name, desc.kind.type_name());
+
+            for (labels, value) in series {
+                let labels = labels
+                    .iter()
+                    .map(|(name, value)| format!("{name}=\"{}\"", escape_label_value(value)))
+                    .collect::<Vec<_>>()
+                    .join(",");
+
+                let _ = writeln!(out, "{}{{{labels}}} {value}", desc.name);
+            }
+        }
+
+        out
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Converts Cosmos SDK Dec amount to base units, rounded down the same way as in reports. Invalid
+/// amounts are exposed as NaN
+pub fn dec_amount_to_f64(amount: &str) -> f64 {
+    dec_to_base_units(amount)
+        .ok()
+        .and_then(|amount| amount.to_string().parse().ok())
+        .unwrap_or(f64::NAN)
+}
+
+/// Starts serving metrics on given address in background. Returns the address actually listened
+/// on, which differs from the given one if port 0 was requested
+pub async fn serve(address: SocketAddr) -> eyre::Result<SocketAddr> {
+    let listener = TcpListener::bind(address)
+        .await
+        .wrap_err_with(|| format!("failed to bind metrics listener to '{address}'"))?;
+    let address = listener
+        .local_addr()
+        .wrap_err("failed to get metrics listener address")?;
+
+    METRICS.enabled.store(true, Ordering::Relaxed);
+    info!(%address, "serving metrics");
+
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, peer)) => {
+                    tokio::spawn(async move {
+                        if let Err(err) = handle_connection(stream).await {
+                            debug!(?err, %peer, "failed to serve metrics");
+                        }
+                    });
+                }
+                Err(err) => debug!(?err, "failed to accept metrics connection"),
+            }
+        }
+    });
+
+    Ok(address)
+}
+
+async fn handle_connection(mut stream: TcpStream) -> eyre::Result<()> {
+    // Request itself does not matter, every path serves metrics. Just consume the headers.
+    let mut buf = [0u8; 4096];
+    let mut request = Vec::new();
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < 16384 {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+
+    let body = METRICS.render();
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::{
+        FAILED_BROADCASTS, METRICS, Metrics, PENDING_COMMISSION, PENDING_REWARDS,
+        dec_amount_to_f64, serve,
+    };
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::default();
+        metrics.replace_denoms(
+            &PENDING_REWARDS,
+            "osmosis-1",
+            [("uosmo".to_string(), 1.5), ("uion".to_string(), 2.0)],
+        );
+        metrics.replace_denoms(&PENDING_REWARDS, "osmosis-1", [("uosmo".to_string(), 3.0)]);
+        metrics.add(&FAILED_BROADCASTS, &[("chain_id", "osmosis-1")], 1.0);
+        metrics.add(&FAILED_BROADCASTS, &[("chain_id", "osmosis-1")], 1.0);
+        // Commission which is no longer known is cleared
+        metrics.replace_denoms(
+            &PENDING_COMMISSION,
+            "osmosis-1",
+            [("uosmo".to_string(), 7.0)],
+        );
+        metrics.replace_denoms(&PENDING_COMMISSION, "osmosis-1", []);
+
+        assert_eq!(
+            metrics.render(),
+            concat!(
+                "# HELP cosmos_withdrawer_failed_broadcasts_total Number of transactions which failed to broadcast or to get committed\n",
+                "# TYPE cosmos_withdrawer_failed_broadcasts_total counter\n",
+                "cosmos_withdrawer_failed_broadcasts_total{chain_id=\"osmosis-1\"} 2\n",
+                "# HELP cosmos_withdrawer_pending_commission Pending validator commission of the delegator\n",
+                "# TYPE cosmos_withdrawer_pending_commission gauge\n",
+                "# HELP cosmos_withdrawer_pending_rewards Pending delegation rewards of the delegator, summed over all validators\n",
+                "# TYPE cosmos_withdrawer_pending_rewards gauge\n",
+                "cosmos_withdrawer_pending_rewards{chain_id=\"osmosis-1\",denom=\"uosmo\"} 3\n",
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_serve() {
+        METRICS.add(&FAILED_BROADCASTS, &[("chain_id", "metrics-test-1")], 1.0);
+
+        let address = serve("127.0.0.1:0".parse().unwrap()).await.unwrap();
+        assert!(METRICS.enabled());
+
+        // Any path serves metrics
+        for path in ["/metrics", "/"] {
+            let response = reqwest::get(format!("http://{address}{path}"))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), reqwest::StatusCode::OK);
+            assert_eq!(
+                response.headers()["content-type"],
+                "text/plain; version=0.0.4"
+            );
+
+            let body = response.text().await.unwrap();
+            assert!(
+                body.contains(
+                    "cosmos_withdrawer_failed_broadcasts_total{chain_id=\"metrics-test-1\"} 1\n"
+                ),
+                "{body}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_dec_amount_to_f64() {
+        assert_eq!(dec_amount_to_f64("1500000000000000000"), 1.0);
+        assert_eq!(dec_amount_to_f64("1234000000000000000000"), 1234.0);
+        assert_eq!(dec_amount_to_f64("0"), 0.0);
+        assert!(dec_amount_to_f64("invalid").is_nan());
+    }
+}
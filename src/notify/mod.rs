@@ -0,0 +1,212 @@
+use std::time::Duration;
+
+use clap::ValueEnum;
+use eyre::Context;
+use serde_json::{Value, json};
+use tracing::{debug, warn};
+
+use crate::report::{FailureReport, WithdrawReport, WithdrawStatus};
+
+/// Webhook payload format
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum WebhookFormat {
+    /// Structured JSON payload
+    #[default]
+    Generic,
+    /// Slack incoming webhook message
+    Slack,
+    /// Discord webhook message
+    Discord,
+}
+
+/// Notifier posts withdraw run results to a webhook.
+///
+/// Payloads are built only from the run reports, which never contain key material.
+pub struct Notifier {
+    client: reqwest::Client,
+    url: String,
+    format: WebhookFormat,
+}
+
+impl Notifier {
+    pub fn new(url: String, format: WebhookFormat) -> eyre::Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .wrap_err("failed to set up webhook client")?;
+
+        Ok(Self {
+            client,
+            url,
+            format,
+        })
+    }
+
+    /// Notifies about withdraw run result. `name` identifies the run, e.g. chain name from config file.
+    /// Delivery failures are only logged, they never fail the run.
+    pub async fn notify(&self, name: Option<&str>, result: &eyre::Result<WithdrawReport>) {
+        let Some(payload) = build_payload(self.format, name, result) else {
+            debug!("nothing to notify about");
+            return;
+        };
+
+        if let Err(err) = self.post(&payload).await {
+            warn!(?err, "failed to deliver webhook notification");
+        }
+    }
+
+    async fn post(&self, payload: &Value) -> eyre::Result<()> {
+        self.client
+            .post(&self.url)
+            .json(payload)
+            .send()
+            .await
+            .wrap_err("failed to send webhook request")?
+            .error_for_status()
+            .wrap_err("webhook responded with an error")?;
+
+        Ok(())
+    }
+}
+
+fn build_payload(
+    format: WebhookFormat,
+    name: Option<&str>,
+    result: &eyre::Result<WithdrawReport>,
+) -> Option<Value> {
+    let (event, chain_id) = match result {
+        Ok(report) => match report.status {
+            WithdrawStatus::Withdrawn => ("withdrawn", Some(report.chain_id.as_str())),
+            WithdrawStatus::NothingToWithdraw => ("skipped", Some(report.chain_id.as_str())),
+            // Nothing happened on chain, nobody needs to know
+            WithdrawStatus::Generated | WithdrawStatus::DryRun => return None,
+        },
+        Err(_) => ("failed", None),
+    };
+
+    if format == WebhookFormat::Generic {
+        return Some(match result {
+            Ok(report) => json!({
+                "event": event,
+                "name": name,
+                "chain_id": chain_id,
+                "tx_hashes": report.transactions.iter().map(|tx| &tx.tx_hash).collect::<Vec<_>>(),
+                "collected": report.collected,
+            }),
+            Err(err) => {
+                let failure = FailureReport::new(err);
+                json!({
+                    "event": event,
+                    "name": name,
+                    "error": failure.error,
+                    "causes": failure.causes,
+                })
+            }
+        });
+    }
+
+    let subject = match (name, chain_id) {
+        (Some(name), Some(chain_id)) => format!("{name} ({chain_id})"),
+        (Some(name), None) => name.to_string(),
+        (None, Some(chain_id)) => chain_id.to_string(),
+        (None, None) => "cosmos-withdrawer".to_string(),
+    };
+
+    let text = match result {
+        Ok(report) if report.status == WithdrawStatus::Withdrawn => {
+            let collected = report
+                .collected
+                .iter()
+                .map(|coin| format!("{}{}", coin.amount, coin.denom))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let tx_hashes = report
+                .transactions
+                .iter()
+                .map(|tx| tx.tx_hash.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            format!("Withdrew {collected} on {subject}. Transactions: {tx_hashes}")
+        }
+        Ok(_) => format!("Nothing to withdraw yet on {subject}"),
+        Err(err) => format!("Withdrawal failed on {subject}: {err:#}"),
+    };
+
+    Some(match format {
+        WebhookFormat::Slack => json!({ "text": text }),
+        WebhookFormat::Discord => json!({ "content": text }),
+        WebhookFormat::Generic => unreachable!(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use eyre::eyre;
+    use pretty_assertions::assert_eq;
+    use serde_json::{Value, json};
+    use tokio::net::TcpListener;
+
+    use super::{Notifier, WebhookFormat};
+    use crate::{
+        report::{CoinAmount, WithdrawReport, WithdrawStatus},
+        test_util::{read_json_request, write_json_response},
+    };
+
+    /// Accepts a single HTTP request and returns its body
+    async fn receive_request(listener: TcpListener) -> Value {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let body = read_json_request(&mut stream).await;
+        write_json_response(&mut stream, None).await;
+        body
+    }
+
+    #[tokio::test]
+    async fn test_notify_withdrawn() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = tokio::spawn(receive_request(listener));
+
+        let report = WithdrawReport {
+            status: WithdrawStatus::Withdrawn,
+            chain_id: "osmosis-1".to_string(),
+            collected: vec![CoinAmount {
+                denom: "uosmo".to_string(),
+                amount: "1500000".to_string(),
+            }],
+            ..Default::default()
+        };
+
+        let notifier = Notifier::new(url, WebhookFormat::Generic).unwrap();
+        notifier.notify(Some("osmosis"), &Ok(report)).await;
+
+        assert_eq!(
+            server.await.unwrap(),
+            json!({
+                "event": "withdrawn",
+                "name": "osmosis",
+                "chain_id": "osmosis-1",
+                "tx_hashes": [],
+                "collected": [{ "denom": "uosmo", "amount": "1500000" }],
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_notify_failed_slack() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = tokio::spawn(receive_request(listener));
+
+        let err = eyre!("mnemonic not available for controller").wrap_err("failed to sign");
+        let notifier = Notifier::new(url, WebhookFormat::Slack).unwrap();
+        notifier.notify(None, &Err(err)).await;
+
+        assert_eq!(
+            server.await.unwrap(),
+            json!({
+                "text": "Withdrawal failed on cosmos-withdrawer: failed to sign: mnemonic not available for controller",
+            })
+        );
+    }
+}
@@ -0,0 +1,492 @@
+use std::fmt;
+
+use clap::ValueEnum;
+use cosmrs::{Coin, proto::Timestamp, rpc::endpoint::tx::Response as TxResponse, tx::Fee};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::chain::GrantInfo;
+
+/// Output format of the command results on stdout
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable output. Command results are mostly logged to stderr
+    #[default]
+    Text,
+    /// Single JSON document describing the command result
+    Json,
+}
+
+impl OutputFormat {
+    /// Prints command result to stdout. In text mode, only the unsigned transaction is printed (if any)
+    pub fn print_report(
+        &self,
+        report: &impl Serialize,
+        unsigned_tx: Option<&Value>,
+    ) -> eyre::Result<()> {
+        match self {
+            Self::Text => {
+                if let Some(unsigned_tx) = unsigned_tx {
+                    println!("{unsigned_tx}");
+                }
+            }
+            Self::Json => println!("{}", serde_json::to_string(report)?),
+        }
+
+        Ok(())
+    }
+}
+
+/// Coin amount in base units of the denom
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CoinAmount {
+    pub denom: String,
+    pub amount: String,
+}
+
+impl From<&Coin> for CoinAmount {
+    fn from(coin: &Coin) -> Self {
+        Self {
+            denom: coin.denom.to_string(),
+            amount: coin.amount.to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WithdrawStatus {
+    /// Transactions were committed to the chain
+    Withdrawn,
+    /// Rewards & commissions did not reach configured thresholds
+    #[default]
+    NothingToWithdraw,
+    /// Unsigned transaction was generated
+    Generated,
+    /// Transaction was signed, but not broadcast
+    DryRun,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RewardDecision {
+    /// Reward amount is at or above configured threshold, and will be withdrawn
+    Selected,
+    /// Reward amount is below configured threshold
+    BelowThreshold,
+    /// There is no threshold configured for the denom
+    NotConfigured,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RewardReport {
+    pub denom: String,
+    pub amount: String,
+    pub threshold: Option<String>,
+    pub decision: RewardDecision,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ValidatorReport {
+    pub validator_address: String,
+    /// Whether delegation rewards from this validator are withdrawn
+    pub withdraw_rewards: bool,
+    pub rewards: Vec<RewardReport>,
+    /// Whether validator commission is withdrawn. Only possible if delegator is the validator operator
+    pub withdraw_commission: bool,
+    pub commission: Vec<CoinAmount>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TxKind {
+    Withdraw,
+    Send,
+    SetupValoper,
+    Teardown,
+    RenewGrants,
+}
+
+impl fmt::Display for TxKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Withdraw => "withdraw",
+            Self::Send => "send",
+            Self::SetupValoper => "setup_valoper",
+            Self::Teardown => "teardown",
+            Self::RenewGrants => "renew_grants",
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TxReport {
+    pub kind: TxKind,
+    pub tx_hash: String,
+    pub height: u64,
+    pub gas_wanted: i64,
+    pub gas_used: i64,
+    pub fee: Vec<CoinAmount>,
+}
+
+impl TxReport {
+    pub fn new(kind: TxKind, fee: &Fee, response: &TxResponse) -> Self {
+        Self {
+            kind,
+            tx_hash: response.hash.to_string(),
+            height: response.height.value(),
+            gas_wanted: response.tx_result.gas_wanted,
+            gas_used: response.tx_result.gas_used,
+            fee: fee.amount.iter().map(CoinAmount::from).collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct WithdrawReport {
+    pub status: WithdrawStatus,
+    pub chain_id: String,
+    pub validators: Vec<ValidatorReport>,
+    /// Total amounts withdrawn, per denom
+    pub collected: Vec<CoinAmount>,
+    pub transactions: Vec<TxReport>,
+    /// Grants given to the controller which expire within the warning window, or have expired
+    pub expiring_grants: Vec<GrantInfo>,
+    /// Unsigned transaction, set only when transaction generation was requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unsigned_tx: Option<Value>,
+}
+
+impl fmt::Display for WithdrawReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.status {
+            WithdrawStatus::NothingToWithdraw => write!(f, "nothing to withdraw")?,
+            WithdrawStatus::Generated => write!(f, "generated unsigned transaction")?,
+            WithdrawStatus::DryRun => write!(f, "dry run")?,
+            WithdrawStatus::Withdrawn => {
+                write!(f, "withdrawn")?;
+                for tx in self.transactions.iter() {
+                    write!(f, " {}", tx.tx_hash)?;
+                }
+            }
+        }
+
+        if !self.expiring_grants.is_empty() {
+            write!(f, ", {} grants expiring", self.expiring_grants.len())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SetupValoperStatus {
+    /// Setup transaction was committed to the chain
+    #[default]
+    Submitted,
+    /// Unsigned transaction was generated
+    Generated,
+    /// Transaction was signed, but not broadcast
+    DryRun,
+    /// Grants and withdraw address are already set up as desired
+    AlreadyConfigured,
+}
+
+/// Change to the delegator setup, found missing by comparing desired setup with the chain
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "snake_case", tag = "change")]
+pub enum SetupChange {
+    /// Withdraw address is changed from the current one
+    WithdrawAddress { current: String, desired: String },
+    /// Grant is given. Replaces existing grant if it has expired, authorizes something else, or
+    /// expires at a different time
+    Grant {
+        msg_type_url: String,
+        replaces: Option<GrantInfo>,
+    },
+}
+
+impl fmt::Display for SetupChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WithdrawAddress { current, desired } => {
+                write!(f, "set withdraw address {current} -> {desired}")
+            }
+            Self::Grant {
+                msg_type_url,
+                replaces: None,
+            } => write!(f, "grant {msg_type_url}"),
+            Self::Grant {
+                msg_type_url,
+                replaces: Some(_),
+            } => write!(f, "replace grant {msg_type_url}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct SetupValoperReport {
+    pub status: SetupValoperStatus,
+    pub chain_id: String,
+    pub method: String,
+    /// Changes which are missing on chain. Not known in offline mode, where everything is sent
+    pub changes: Vec<SetupChange>,
+    /// Type URLs of the messages included in the transaction
+    pub messages: Vec<String>,
+    pub transactions: Vec<TxReport>,
+    /// Unsigned transaction, set only when transaction generation was requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unsigned_tx: Option<Value>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TeardownStatus {
+    /// Teardown transaction was committed to the chain
+    #[default]
+    Submitted,
+    /// There are no grants to revoke, and withdraw address is the delegator itself
+    NothingToTeardown,
+    /// Unsigned transaction was generated
+    Generated,
+    /// Transaction was signed, but not broadcast
+    DryRun,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct TeardownReport {
+    pub status: TeardownStatus,
+    pub chain_id: String,
+    /// Type URLs of the revoked grants
+    pub revoked: Vec<String>,
+    /// Withdraw address which is reset back to the delegator, if any
+    pub previous_withdraw_address: Option<String>,
+    pub transactions: Vec<TxReport>,
+    /// Unsigned transaction, set only when transaction generation was requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unsigned_tx: Option<Value>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RenewGrantsStatus {
+    /// Renewal transaction was committed to the chain
+    #[default]
+    Submitted,
+    /// Unsigned transaction was generated
+    Generated,
+    /// Transaction was signed, but not broadcast
+    DryRun,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RenewGrantsReport {
+    pub status: RenewGrantsStatus,
+    pub chain_id: String,
+    /// Type URLs of the renewed grants
+    pub renewed: Vec<String>,
+    /// New expiration of the renewed grants. Grants keep their expiration if not set
+    pub expiration: Option<Timestamp>,
+    /// Spend limit of `SendAuthorization` after top-up, if it was topped up
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub spend_limit: Vec<CoinAmount>,
+    pub transactions: Vec<TxReport>,
+    /// Unsigned transaction, set only when transaction generation was requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unsigned_tx: Option<Value>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct BroadcastReport {
+    pub chain_id: String,
+    pub tx_hash: String,
+    pub height: u64,
+    pub gas_wanted: i64,
+    pub gas_used: i64,
+}
+
+/// Exit code of a successful command. Every reported status is a success, including
+/// `nothing_to_withdraw`, `already_configured`, `generated` and `dry_run`
+pub const EXIT_SUCCESS: u8 = 0;
+/// Exit code of a failed command, or with `run`, of a run where at least one chain failed.
+/// Invalid command line arguments exit with 2, as handled by clap.
+pub const EXIT_FAILURE: u8 = 1;
+
+/// Maps command result to the process exit code
+pub fn exit_code<T>(result: &eyre::Result<T>) -> u8 {
+    match result {
+        Ok(_) => EXIT_SUCCESS,
+        Err(_) => EXIT_FAILURE,
+    }
+}
+
+/// Report printed when command fails
+#[derive(Clone, Debug, Serialize)]
+pub struct FailureReport {
+    pub status: &'static str,
+    pub error: String,
+    /// Error chain, outermost error first
+    pub causes: Vec<String>,
+}
+
+impl FailureReport {
+    pub fn new(err: &eyre::Report) -> Self {
+        Self {
+            status: "failed",
+            error: err.to_string(),
+            causes: err.chain().skip(1).map(|cause| cause.to_string()).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use eyre::{WrapErr, eyre};
+    use pretty_assertions::assert_eq;
+    use serde_json::{Value, json};
+
+    use super::{
+        CoinAmount, FailureReport, RenewGrantsStatus, SetupChange, SetupValoperStatus,
+        TeardownStatus, TxKind, TxReport, WithdrawReport, WithdrawStatus,
+    };
+
+    fn to_value(value: impl serde::Serialize) -> Value {
+        serde_json::to_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_status_serialization() {
+        for (status, expected) in [
+            (WithdrawStatus::Withdrawn, "withdrawn"),
+            (WithdrawStatus::NothingToWithdraw, "nothing_to_withdraw"),
+            (WithdrawStatus::Generated, "generated"),
+            (WithdrawStatus::DryRun, "dry_run"),
+        ] {
+            assert_eq!(to_value(status), json!(expected));
+        }
+
+        for (status, expected) in [
+            (SetupValoperStatus::Submitted, "submitted"),
+            (SetupValoperStatus::Generated, "generated"),
+            (SetupValoperStatus::DryRun, "dry_run"),
+            (SetupValoperStatus::AlreadyConfigured, "already_configured"),
+        ] {
+            assert_eq!(to_value(status), json!(expected));
+        }
+
+        for (status, expected) in [
+            (TeardownStatus::Submitted, "submitted"),
+            (TeardownStatus::NothingToTeardown, "nothing_to_teardown"),
+            (TeardownStatus::Generated, "generated"),
+            (TeardownStatus::DryRun, "dry_run"),
+        ] {
+            assert_eq!(to_value(status), json!(expected));
+        }
+
+        for (status, expected) in [
+            (RenewGrantsStatus::Submitted, "submitted"),
+            (RenewGrantsStatus::Generated, "generated"),
+            (RenewGrantsStatus::DryRun, "dry_run"),
+        ] {
+            assert_eq!(to_value(status), json!(expected));
+        }
+    }
+
+    #[test]
+    fn test_withdraw_report_serialization() {
+        let nothing_to_withdraw = WithdrawReport {
+            chain_id: "osmosis-1".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            to_value(&nothing_to_withdraw),
+            json!({
+                "status": "nothing_to_withdraw",
+                "chain_id": "osmosis-1",
+                "validators": [],
+                "collected": [],
+                "transactions": [],
+                "expiring_grants": [],
+            })
+        );
+        assert_eq!(nothing_to_withdraw.to_string(), "nothing to withdraw");
+
+        let withdrawn = WithdrawReport {
+            status: WithdrawStatus::Withdrawn,
+            chain_id: "osmosis-1".to_string(),
+            collected: vec![CoinAmount {
+                denom: "uosmo".to_string(),
+                amount: "1500000".to_string(),
+            }],
+            transactions: vec![TxReport {
+                kind: TxKind::Withdraw,
+                tx_hash: "ABCD".to_string(),
+                height: 123,
+                gas_wanted: 150000,
+                gas_used: 120000,
+                fee: vec![CoinAmount {
+                    denom: "uosmo".to_string(),
+                    amount: "3750".to_string(),
+                }],
+            }],
+            ..Default::default()
+        };
+        assert_eq!(
+            to_value(&withdrawn),
+            json!({
+                "status": "withdrawn",
+                "chain_id": "osmosis-1",
+                "validators": [],
+                "collected": [{ "denom": "uosmo", "amount": "1500000" }],
+                "transactions": [{
+                    "kind": "withdraw",
+                    "tx_hash": "ABCD",
+                    "height": 123,
+                    "gas_wanted": 150000,
+                    "gas_used": 120000,
+                    "fee": [{ "denom": "uosmo", "amount": "3750" }],
+                }],
+                "expiring_grants": [],
+            })
+        );
+        assert_eq!(withdrawn.to_string(), "withdrawn ABCD");
+
+        let generated = WithdrawReport {
+            status: WithdrawStatus::Generated,
+            unsigned_tx: Some(json!({ "body": {} })),
+            ..Default::default()
+        };
+        assert_eq!(to_value(&generated)["unsigned_tx"], json!({ "body": {} }));
+    }
+
+    #[test]
+    fn test_failure_report_serialization() {
+        let err = Err::<(), _>(eyre!("connection refused"))
+            .wrap_err("failed to query delegation rewards")
+            .unwrap_err();
+
+        assert_eq!(
+            to_value(FailureReport::new(&err)),
+            json!({
+                "status": "failed",
+                "error": "failed to query delegation rewards",
+                "causes": ["connection refused"],
+            })
+        );
+    }
+
+    #[test]
+    fn test_setup_change_serialization() {
+        assert_eq!(
+            to_value(SetupChange::WithdrawAddress {
+                current: "osmo1delegator".to_string(),
+                desired: "osmo1reward".to_string(),
+            }),
+            json!({
+                "change": "withdraw_address",
+                "current": "osmo1delegator",
+                "desired": "osmo1reward",
+            })
+        );
+    }
+}
@@ -0,0 +1,234 @@
+use std::{
+    collections::BTreeSet,
+    convert::Infallible,
+    fmt,
+    fs::File,
+    io::Read,
+    mem::ManuallyDrop,
+    ops::Deref,
+    os::fd::{FromRawFd, RawFd},
+    path::Path,
+    str::FromStr,
+    sync::Mutex,
+};
+
+use eyre::{Context, bail};
+use serde::{Deserialize, Deserializer};
+use zeroize::Zeroizing;
+
+/// String holding secret material, such as a mnemonic or a passphrase. Never printed by `Debug`,
+/// and zeroized when dropped.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct SecretString(Zeroizing<String>);
+
+impl SecretString {
+    pub fn new(secret: String) -> Self {
+        Self(Zeroizing::new(secret))
+    }
+}
+
+impl Deref for SecretString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretString([REDACTED])")
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(secret: String) -> Self {
+        Self::new(secret)
+    }
+}
+
+impl FromStr for SecretString {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::new(s.to_string()))
+    }
+}
+
+impl<'de> Deserialize<'de> for SecretString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::new)
+    }
+}
+
+/// File descriptors secrets were already read from. Inherited descriptors are read to the end, so
+/// each of them can only provide a single secret.
+static READ_FDS: Mutex<BTreeSet<RawFd>> = Mutex::new(BTreeSet::new());
+
+/// Places a single secret can be read from. At most one of them can be set
+#[derive(Clone, Copy, Default)]
+pub struct SecretSource<'a> {
+    /// Secret given directly, e.g. via command line flag or environment variable
+    pub value: Option<&'a str>,
+    pub file: Option<&'a Path>,
+    /// File descriptor inherited from the parent process, e.g. `3< mnemonic.txt` in shell
+    pub fd: Option<RawFd>,
+    /// Prompt for the secret on the terminal
+    pub prompt: bool,
+}
+
+impl SecretSource<'_> {
+    pub fn is_set(&self) -> bool {
+        self.value.is_some() || self.file.is_some() || self.fd.is_some() || self.prompt
+    }
+
+    /// Reads the secret, `name` is used in error messages and the prompt. Trailing line endings of
+    /// secrets read from files are ignored.
+    pub fn read(&self, name: &str) -> eyre::Result<Option<SecretString>> {
+        let sources = [
+            self.value.is_some(),
+            self.file.is_some(),
+            self.fd.is_some(),
+            self.prompt,
+        ];
+        if sources.iter().filter(|set| **set).count() > 1 {
+            bail!("only one of {name}, {name} file, {name} file descriptor and prompt can be set");
+        }
+
+        if let Some(value) = self.value {
+            return Ok(Some(SecretString::new(value.to_string())));
+        }
+
+        if let Some(path) = self.file {
+            let mut file = File::open(path)
+                .wrap_err_with(|| format!("failed to open {name} file '{}'", path.display()))?;
+            return read_to_secret(&mut file)
+                .wrap_err_with(|| format!("failed to read {name} file '{}'", path.display()))
+                .map(Some);
+        }
+
+        if let Some(fd) = self.fd {
+            if (0..=2).contains(&fd) {
+                bail!("{name} file descriptor {fd} is a standard stream, use 3 or above");
+            }
+            if !READ_FDS.lock().expect("fd registry poisoned").insert(fd) {
+                bail!(
+                    "{name} file descriptor {fd} was already read, each secret needs its own file descriptor"
+                );
+            }
+            // SAFETY: fd is checked to be open. It is never closed, as `ManuallyDrop` leaves the
+            // descriptor owned by whoever opened it
+            if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+                bail!("{name} file descriptor {fd} is not open");
+            }
+            let mut file = ManuallyDrop::new(unsafe { File::from_raw_fd(fd) });
+            return read_to_secret(&mut *file)
+                .wrap_err_with(|| format!("failed to read {name} from file descriptor {fd}"))
+                .map(Some);
+        }
+
+        if self.prompt {
+            let secret = rpassword::prompt_password(format!("Enter {name}: "))
+                .wrap_err_with(|| format!("failed to prompt for {name}"))?;
+            return Ok(Some(SecretString::new(secret)));
+        }
+
+        Ok(None)
+    }
+}
+
+fn read_to_secret(reader: &mut impl Read) -> std::io::Result<SecretString> {
+    let mut secret = Zeroizing::new(String::new());
+    reader.read_to_string(&mut secret)?;
+
+    // Spaces can be a part of the secret, line endings hardly so
+    let len = secret.trim_end_matches(['\r', '\n']).len();
+    secret.truncate(len);
+
+    Ok(SecretString(secret))
+}
+
+#[cfg(test)]
+mod test {
+    use std::os::fd::IntoRawFd;
+
+    use pretty_assertions::assert_eq;
+
+    use super::{SecretSource, SecretString};
+
+    #[test]
+    fn test_secret_source() {
+        let secret: SecretString = "correct horse".parse().unwrap();
+        assert_eq!(format!("{secret:?}"), "SecretString([REDACTED])");
+        assert_eq!(
+            format!("{:?}", Some(secret.clone())),
+            "Some(SecretString([REDACTED]))"
+        );
+
+        let path = std::env::temp_dir().join(format!(
+            "cosmos-withdrawer-secret-test-{}",
+            std::process::id()
+        ));
+        std::fs::write(&path, "correct horse \r\n").unwrap();
+
+        let read = |source: SecretSource| source.read("mnemonic").unwrap().map(|s| s.to_string());
+        assert_eq!(read(SecretSource::default()), None);
+        assert_eq!(
+            read(SecretSource {
+                value: Some(&secret),
+                ..Default::default()
+            }),
+            Some("correct horse".to_string())
+        );
+        assert_eq!(
+            read(SecretSource {
+                file: Some(&path),
+                ..Default::default()
+            }),
+            Some("correct horse ".to_string())
+        );
+
+        let fd = std::fs::File::open(&path).unwrap().into_raw_fd();
+        let fd_source = SecretSource {
+            fd: Some(fd),
+            ..Default::default()
+        };
+        assert_eq!(read(fd_source), Some("correct horse ".to_string()));
+        // Descriptor is left open, but cannot be read from again
+        assert_ne!(unsafe { libc::fcntl(fd, libc::F_GETFD) }, -1);
+        assert_eq!(
+            fd_source.read("mnemonic").unwrap_err().to_string(),
+            format!(
+                "mnemonic file descriptor {fd} was already read, each secret needs its own file descriptor"
+            )
+        );
+        unsafe { libc::close(fd) };
+
+        for fd in 0..=2 {
+            let err = SecretSource {
+                fd: Some(fd),
+                ..Default::default()
+            }
+            .read("mnemonic")
+            .unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("mnemonic file descriptor {fd} is a standard stream, use 3 or above")
+            );
+        }
+
+        let err = SecretSource {
+            value: Some(&secret),
+            file: Some(&path),
+            ..Default::default()
+        }
+        .read("mnemonic")
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "only one of mnemonic, mnemonic file, mnemonic file descriptor and prompt can be set"
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,422 @@
+use cosmrs::{
+    proto::{
+        Timestamp,
+        cosmos::{
+            authz::v1beta1::{GenericAuthorization, MsgGrant, MsgRevoke},
+            bank::v1beta1::{MsgSend, SendAuthorization},
+            distribution::v1beta1::{
+                MsgSetWithdrawAddress, MsgWithdrawDelegatorReward, MsgWithdrawValidatorCommission,
+            },
+        },
+    },
+    tx::Fee,
+};
+use eyre::Context;
+use serde_json::{Map, Value, json};
+use time::{OffsetDateTime, macros::format_description};
+
+use super::{CosmosJsonSerializable, MsgExecCustom};
+
+/// Legacy Amino JSON encoding, as used by `SIGN_MODE_LEGACY_AMINO_JSON`. Messages are wrapped into
+/// `{"type": "<amino name>", "value": {...}}` objects and empty fields are omitted.
+pub trait ToAminoJson {
+    fn to_amino_value(&self) -> eyre::Result<Value>;
+}
+
+impl ToAminoJson for MsgGrant {
+    fn to_amino_value(&self) -> eyre::Result<Value> {
+        let grant = match &self.grant {
+            Some(grant) => {
+                let mut value = Map::new();
+                if let Some(authorization) = &grant.authorization {
+                    let authz = CosmosJsonSerializable::try_from(authorization)
+                        .wrap_err("failed to decode authorization")?;
+                    value.insert("authorization".to_string(), authz.to_amino_value()?);
+                }
+                if let Some(expiration) = &grant.expiration {
+                    value.insert(
+                        "expiration".to_string(),
+                        format_timestamp(expiration)?.into(),
+                    );
+                }
+                Value::Object(value)
+            }
+            None => json!({}),
+        };
+
+        Ok(json!({
+            "type": "cosmos-sdk/MsgGrant",
+            "value": {
+                "grant": grant,
+                "grantee": self.grantee,
+                "granter": self.granter,
+            },
+        }))
+    }
+}
+
+impl ToAminoJson for MsgRevoke {
+    fn to_amino_value(&self) -> eyre::Result<Value> {
+        Ok(json!({
+            "type": "cosmos-sdk/MsgRevoke",
+            "value": {
+                "grantee": self.grantee,
+                "granter": self.granter,
+                "msg_type_url": self.msg_type_url,
+            },
+        }))
+    }
+}
+
+impl ToAminoJson for MsgSetWithdrawAddress {
+    fn to_amino_value(&self) -> eyre::Result<Value> {
+        Ok(json!({
+            "type": "cosmos-sdk/MsgModifyWithdrawAddress",
+            "value": {
+                "delegator_address": self.delegator_address,
+                "withdraw_address": self.withdraw_address,
+            },
+        }))
+    }
+}
+
+impl ToAminoJson for MsgWithdrawDelegatorReward {
+    fn to_amino_value(&self) -> eyre::Result<Value> {
+        Ok(json!({
+            "type": "cosmos-sdk/MsgWithdrawDelegationReward",
+            "value": {
+                "delegator_address": self.delegator_address,
+                "validator_address": self.validator_address,
+            },
+        }))
+    }
+}
+
+impl ToAminoJson for MsgWithdrawValidatorCommission {
+    fn to_amino_value(&self) -> eyre::Result<Value> {
+        Ok(json!({
+            "type": "cosmos-sdk/MsgWithdrawValCommission",
+            "value": {
+                "validator_address": self.validator_address,
+            },
+        }))
+    }
+}
+
+impl ToAminoJson for MsgSend {
+    fn to_amino_value(&self) -> eyre::Result<Value> {
+        // Amount is never omitted, even if empty
+        Ok(json!({
+            "type": "cosmos-sdk/MsgSend",
+            "value": {
+                "amount": self.amount.iter().map(|coin| json!({
+                    "amount": coin.amount,
+                    "denom": coin.denom,
+                })).collect::<Vec<_>>(),
+                "from_address": self.from_address,
+                "to_address": self.to_address,
+            },
+        }))
+    }
+}
+
+impl ToAminoJson for MsgExecCustom {
+    fn to_amino_value(&self) -> eyre::Result<Value> {
+        Ok(json!({
+            "type": "cosmos-sdk/MsgExec",
+            "value": {
+                "grantee": self.grantee,
+                "msgs": self
+                    .msgs
+                    .iter()
+                    .map(|msg| msg.to_amino_value())
+                    .collect::<eyre::Result<Vec<_>>>()?,
+            },
+        }))
+    }
+}
+
+impl ToAminoJson for GenericAuthorization {
+    fn to_amino_value(&self) -> eyre::Result<Value> {
+        Ok(json!({
+            "type": "cosmos-sdk/GenericAuthorization",
+            "value": {
+                "msg": self.msg,
+            },
+        }))
+    }
+}
+
+impl ToAminoJson for SendAuthorization {
+    fn to_amino_value(&self) -> eyre::Result<Value> {
+        // Spend limit is never omitted, even if empty
+        let mut value = json!({
+            "spend_limit": self.spend_limit.iter().map(|coin| json!({
+                "amount": coin.amount,
+                "denom": coin.denom,
+            })).collect::<Vec<_>>(),
+        });
+        if !self.allow_list.is_empty() {
+            value["allow_list"] = self.allow_list.clone().into();
+        }
+
+        Ok(json!({
+            "type": "cosmos-sdk/SendAuthorization",
+            "value": value,
+        }))
+    }
+}
+
+impl ToAminoJson for CosmosJsonSerializable {
+    fn to_amino_value(&self) -> eyre::Result<Value> {
+        match self {
+            Self::MsgGrant(msg) => msg.to_amino_value(),
+            Self::MsgRevoke(msg) => msg.to_amino_value(),
+            Self::MsgSetWithdrawAddress(msg) => msg.to_amino_value(),
+            Self::MsgWithdrawDelegatorReward(msg) => msg.to_amino_value(),
+            Self::MsgWithdrawValidatorCommission(msg) => msg.to_amino_value(),
+            Self::MsgSend(msg) => msg.to_amino_value(),
+            Self::MsgExec(msg) => msg.to_amino_value(),
+            Self::GenericAuthorization(msg) => msg.to_amino_value(),
+            Self::SendAuthorization(msg) => msg.to_amino_value(),
+        }
+    }
+}
+
+/// Returns sign bytes of the legacy Amino JSON `StdSignDoc`: compact JSON with sorted keys, escaped
+/// like Go's `encoding/json` does.
+pub fn amino_sign_doc_bytes<'a>(
+    chain_id: &str,
+    account_number: u64,
+    sequence: u64,
+    fee: &Fee,
+    memo: &str,
+    msgs: impl IntoIterator<Item = &'a CosmosJsonSerializable>,
+) -> eyre::Result<Vec<u8>> {
+    let sign_doc = amino_sign_doc(chain_id, account_number, sequence, fee, memo, msgs)?;
+
+    // Escaped characters can only appear within JSON strings
+    let sign_doc = serde_json::to_string(&sign_doc)?
+        .replace('<', "\\u003c")
+        .replace('>', "\\u003e")
+        .replace('&', "\\u0026")
+        .replace('\u{2028}', "\\u2028")
+        .replace('\u{2029}', "\\u2029");
+
+    Ok(sign_doc.into_bytes())
+}
+
+/// Returns the legacy Amino JSON `StdSignDoc`
+pub fn amino_sign_doc<'a>(
+    chain_id: &str,
+    account_number: u64,
+    sequence: u64,
+    fee: &Fee,
+    memo: &str,
+    msgs: impl IntoIterator<Item = &'a CosmosJsonSerializable>,
+) -> eyre::Result<Value> {
+    let mut fee_value = json!({
+        "amount": fee.amount.iter().map(|coin| json!({
+            "amount": coin.amount.to_string(),
+            "denom": coin.denom.to_string(),
+        })).collect::<Vec<_>>(),
+        "gas": fee.gas_limit.to_string(),
+    });
+    if let Some(payer) = &fee.payer {
+        fee_value["payer"] = payer.to_string().into();
+    }
+    if let Some(granter) = &fee.granter {
+        fee_value["granter"] = granter.to_string().into();
+    }
+
+    // serde_json maps are ordered by key, which gives the sorted output required here
+    let sign_doc = json!({
+        "account_number": account_number.to_string(),
+        "chain_id": chain_id,
+        "fee": fee_value,
+        "memo": memo,
+        "msgs": msgs
+            .into_iter()
+            .map(|msg| msg.to_amino_value())
+            .collect::<eyre::Result<Vec<_>>>()?,
+        "sequence": sequence.to_string(),
+    });
+
+    Ok(sign_doc)
+}
+
+/// Formats timestamp like Go's `time.RFC3339Nano` in UTC
+fn format_timestamp(timestamp: &Timestamp) -> eyre::Result<String> {
+    let t = OffsetDateTime::from_unix_timestamp(timestamp.seconds)
+        .wrap_err("timestamp is out of range")?;
+    let formatted = t.format(format_description!(
+        "[year]-[month]-[day]T[hour]:[minute]:[second]"
+    ))?;
+
+    match timestamp.nanos {
+        0 => Ok(format!("{formatted}Z")),
+        nanos => {
+            let fraction = format!("{nanos:09}");
+            Ok(format!("{formatted}.{}Z", fraction.trim_end_matches('0')))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cosmrs::{
+        Any, Coin,
+        proto::{
+            Timestamp,
+            cosmos::{
+                authz::v1beta1::{GenericAuthorization, Grant, MsgGrant, MsgRevoke},
+                bank::v1beta1::{MsgSend, SendAuthorization},
+                base::v1beta1::Coin as ProtoCoin,
+                distribution::v1beta1::{
+                    MsgSetWithdrawAddress, MsgWithdrawDelegatorReward,
+                    MsgWithdrawValidatorCommission,
+                },
+            },
+            prost::Name,
+        },
+        tx::Fee,
+    };
+    use pretty_assertions::assert_eq;
+
+    use super::amino_sign_doc_bytes;
+    use crate::ser::{CosmosJsonSerializable, MsgExecCustom};
+
+    // Derived from the test mnemonic with m/44'/118'/0'/0/{0,1,2}, validator is operated by the
+    // delegator
+    const DELEGATOR: &str = "osmo1rsaj0sj0sqpy48xajzaq328ednss65ljxs0tmp";
+    const CONTROLLER: &str = "osmo1mq3als39tqq8ccv9uuyt2k8xvvmqaedss6v3a6";
+    const REWARD: &str = "osmo1kqlcns6h3zx9xhlez3m2fw589mljr6qtjhxwlk";
+    const VALIDATOR: &str = "osmovaloper1rsaj0sj0sqpy48xajzaq328ednss65lju88gvx";
+
+    // Expected sign docs are not captured from this implementation. They were written out by a
+    // separate script from the amino names registered by x/authz, x/bank and x/distribution, with
+    // keys sorted and `<>&` escaped as Go's encoding/json does, and the two outputs compared byte
+    // for byte
+
+    #[test]
+    fn test_amino_sign_doc() {
+        let grant = |authorization, expiration| -> CosmosJsonSerializable {
+            MsgGrant {
+                granter: DELEGATOR.to_string(),
+                grantee: CONTROLLER.to_string(),
+                grant: Some(Grant {
+                    authorization: Some(authorization),
+                    expiration,
+                }),
+            }
+            .into()
+        };
+        let generic = Any::from_msg(&GenericAuthorization {
+            msg: MsgWithdrawDelegatorReward::type_url(),
+        })
+        .unwrap();
+
+        let msgs = [
+            MsgSetWithdrawAddress {
+                delegator_address: DELEGATOR.to_string(),
+                withdraw_address: REWARD.to_string(),
+            }
+            .into(),
+            grant(generic.clone(), None),
+            grant(
+                generic,
+                Some(Timestamp {
+                    seconds: 1767225600,
+                    nanos: 500_000_000,
+                }),
+            ),
+            grant(
+                Any::from_msg(&SendAuthorization {
+                    spend_limit: vec![ProtoCoin {
+                        denom: "uosmo".to_string(),
+                        amount: "1000000".to_string(),
+                    }],
+                    allow_list: vec![REWARD.to_string()],
+                })
+                .unwrap(),
+                None,
+            ),
+            MsgRevoke {
+                granter: DELEGATOR.to_string(),
+                grantee: CONTROLLER.to_string(),
+                msg_type_url: MsgSend::type_url(),
+            }
+            .into(),
+        ];
+        let fee = Fee::from_amount_and_gas(
+            Coin {
+                denom: "uosmo".parse().unwrap(),
+                amount: 5000,
+            },
+            200_000_u64,
+        );
+
+        let bytes =
+            amino_sign_doc_bytes("osmosis-1", 42, 7, &fee, "<valoper> & co", &msgs).unwrap();
+        assert_eq!(
+            String::from_utf8(bytes).unwrap(),
+            concat!(
+                r#"{"account_number":"42","chain_id":"osmosis-1","fee":{"amount":[{"amount":"5000","denom":"uosmo"}],"gas":"200000"},"memo":"\u003cvaloper\u003e \u0026 co","msgs":["#,
+                r#"{"type":"cosmos-sdk/MsgModifyWithdrawAddress","value":{"delegator_address":"osmo1rsaj0sj0sqpy48xajzaq328ednss65ljxs0tmp","withdraw_address":"osmo1kqlcns6h3zx9xhlez3m2fw589mljr6qtjhxwlk"}},"#,
+                r#"{"type":"cosmos-sdk/MsgGrant","value":{"grant":{"authorization":{"type":"cosmos-sdk/GenericAuthorization","value":{"msg":"/cosmos.distribution.v1beta1.MsgWithdrawDelegatorReward"}}},"grantee":"osmo1mq3als39tqq8ccv9uuyt2k8xvvmqaedss6v3a6","granter":"osmo1rsaj0sj0sqpy48xajzaq328ednss65ljxs0tmp"}},"#,
+                r#"{"type":"cosmos-sdk/MsgGrant","value":{"grant":{"authorization":{"type":"cosmos-sdk/GenericAuthorization","value":{"msg":"/cosmos.distribution.v1beta1.MsgWithdrawDelegatorReward"}},"expiration":"2026-01-01T00:00:00.5Z"},"grantee":"osmo1mq3als39tqq8ccv9uuyt2k8xvvmqaedss6v3a6","granter":"osmo1rsaj0sj0sqpy48xajzaq328ednss65ljxs0tmp"}},"#,
+                r#"{"type":"cosmos-sdk/MsgGrant","value":{"grant":{"authorization":{"type":"cosmos-sdk/SendAuthorization","value":{"allow_list":["osmo1kqlcns6h3zx9xhlez3m2fw589mljr6qtjhxwlk"],"spend_limit":[{"amount":"1000000","denom":"uosmo"}]}}},"grantee":"osmo1mq3als39tqq8ccv9uuyt2k8xvvmqaedss6v3a6","granter":"osmo1rsaj0sj0sqpy48xajzaq328ednss65ljxs0tmp"}},"#,
+                r#"{"type":"cosmos-sdk/MsgRevoke","value":{"grantee":"osmo1mq3als39tqq8ccv9uuyt2k8xvvmqaedss6v3a6","granter":"osmo1rsaj0sj0sqpy48xajzaq328ednss65ljxs0tmp","msg_type_url":"/cosmos.bank.v1beta1.MsgSend"}}"#,
+                r#"],"sequence":"7"}"#,
+            )
+        );
+    }
+
+    #[test]
+    fn test_amino_sign_doc_msg_exec() {
+        let msgs = [MsgExecCustom {
+            grantee: CONTROLLER.to_string(),
+            msgs: vec![
+                MsgWithdrawDelegatorReward {
+                    delegator_address: DELEGATOR.to_string(),
+                    validator_address: VALIDATOR.to_string(),
+                }
+                .into(),
+                MsgWithdrawValidatorCommission {
+                    validator_address: VALIDATOR.to_string(),
+                }
+                .into(),
+                MsgSend {
+                    from_address: DELEGATOR.to_string(),
+                    to_address: REWARD.to_string(),
+                    amount: vec![ProtoCoin {
+                        denom: "uosmo".to_string(),
+                        amount: "1000".to_string(),
+                    }],
+                }
+                .into(),
+            ],
+        }
+        .into()];
+        let fee = Fee::from_amount_and_gas(
+            Coin {
+                denom: "uosmo".parse().unwrap(),
+                amount: 5000,
+            },
+            200_000_u64,
+        );
+
+        let bytes = amino_sign_doc_bytes("osmosis-1", 42, 7, &fee, "", &msgs).unwrap();
+        assert_eq!(
+            String::from_utf8(bytes).unwrap(),
+            concat!(
+                r#"{"account_number":"42","chain_id":"osmosis-1","fee":{"amount":[{"amount":"5000","denom":"uosmo"}],"gas":"200000"},"memo":"","msgs":["#,
+                r#"{"type":"cosmos-sdk/MsgExec","value":{"grantee":"osmo1mq3als39tqq8ccv9uuyt2k8xvvmqaedss6v3a6","msgs":["#,
+                r#"{"type":"cosmos-sdk/MsgWithdrawDelegationReward","value":{"delegator_address":"osmo1rsaj0sj0sqpy48xajzaq328ednss65ljxs0tmp","validator_address":"osmovaloper1rsaj0sj0sqpy48xajzaq328ednss65lju88gvx"}},"#,
+                r#"{"type":"cosmos-sdk/MsgWithdrawValCommission","value":{"validator_address":"osmovaloper1rsaj0sj0sqpy48xajzaq328ednss65lju88gvx"}},"#,
+                r#"{"type":"cosmos-sdk/MsgSend","value":{"amount":[{"amount":"1000","denom":"uosmo"}],"from_address":"osmo1rsaj0sj0sqpy48xajzaq328ednss65ljxs0tmp","to_address":"osmo1kqlcns6h3zx9xhlez3m2fw589mljr6qtjhxwlk"}}"#,
+                r#"]}}],"sequence":"7"}"#,
+            )
+        );
+    }
+}
@@ -5,8 +5,9 @@ use cosmrs::{
     proto::{
         Timestamp,
         cosmos::{
-            authz::v1beta1::{GenericAuthorization, MsgExec, MsgGrant},
-            bank::v1beta1::MsgSend,
+            authz::v1beta1::{GenericAuthorization, Grant, MsgExec, MsgGrant, MsgRevoke},
+            bank::v1beta1::{MsgSend, SendAuthorization},
+            base::v1beta1::Coin,
             distribution::v1beta1::{
                 MsgSetWithdrawAddress, MsgWithdrawDelegatorReward, MsgWithdrawValidatorCommission,
             },
@@ -15,10 +16,14 @@ use cosmrs::{
     },
 };
 use duration_string::DurationString;
-use eyre::bail;
+use eyre::{Context, ContextCompat, bail};
 use serde_json::{Value, json};
 use time::{OffsetDateTime, UtcDateTime, format_description::well_known::Rfc3339, macros::offset};
 
+mod amino;
+
+pub use self::amino::{ToAminoJson, amino_sign_doc, amino_sign_doc_bytes};
+
 pub trait ToCosmosJson {
     fn to_value(&self) -> Value;
 }
@@ -43,13 +48,11 @@ impl ToCosmosJson for MsgGrant {
             "granter": self.granter,
             "grantee": self.grantee,
             "grant": self.grant.as_ref().map(|grant| json!({
-                "authorization": if let Some(authorization) = &grant.authorization {
-                    // NOTE: this tool utilizes GenericAuthorization only, should be alright for now
-                    let authz: GenericAuthorization = cosmrs::Any::to_msg(authorization).expect("failed to decode authorization");
-                    Some(authz.to_value())
-                } else {
-                    None
-                },
+                "authorization": grant.authorization.as_ref().map(|authorization| {
+                    CosmosJsonSerializable::try_from(authorization)
+                        .expect("failed to decode authorization")
+                        .to_value()
+                }),
                 // RFC3339
                 "expiration": grant.expiration,
             })),
@@ -57,6 +60,17 @@ impl ToCosmosJson for MsgGrant {
     }
 }
 
+impl ToCosmosJson for MsgRevoke {
+    fn to_value(&self) -> Value {
+        json!({
+            "@type": MsgRevoke::type_url(),
+            "granter": self.granter,
+            "grantee": self.grantee,
+            "msg_type_url": self.msg_type_url,
+        })
+    }
+}
+
 impl ToCosmosJson for MsgSetWithdrawAddress {
     fn to_value(&self) -> Value {
         json!({
@@ -111,21 +125,39 @@ impl ToCosmosJson for GenericAuthorization {
     }
 }
 
+impl ToCosmosJson for SendAuthorization {
+    fn to_value(&self) -> Value {
+        json!({
+            "@type": SendAuthorization::type_url(),
+            "spend_limit": self.spend_limit.iter().map(|coin| {
+                json!({
+                    "denom": coin.denom,
+                    "amount": coin.amount,
+                })
+            }).collect::<Vec<_>>(),
+            "allow_list": self.allow_list,
+        })
+    }
+}
+
 #[derive(Clone)]
 pub enum CosmosJsonSerializable {
     MsgGrant(MsgGrant),
+    MsgRevoke(MsgRevoke),
     MsgSetWithdrawAddress(MsgSetWithdrawAddress),
     MsgWithdrawDelegatorReward(MsgWithdrawDelegatorReward),
     MsgWithdrawValidatorCommission(MsgWithdrawValidatorCommission),
     MsgSend(MsgSend),
     MsgExec(MsgExecCustom),
     GenericAuthorization(GenericAuthorization),
+    SendAuthorization(SendAuthorization),
 }
 
 impl ToCosmosJson for CosmosJsonSerializable {
     fn to_value(&self) -> Value {
         match self {
             Self::MsgGrant(msg) => msg.to_value(),
+            Self::MsgRevoke(msg) => msg.to_value(),
             Self::MsgSetWithdrawAddress(msg) => msg.to_value(),
             Self::MsgWithdrawDelegatorReward(msg) => msg.to_value(),
             Self::MsgWithdrawValidatorCommission(msg) => msg.to_value(),
@@ -136,30 +168,232 @@ impl ToCosmosJson for CosmosJsonSerializable {
                 "msgs": msg.msgs.iter().map(|v| v.to_value()).collect::<Vec<_>>(),
             }),
             Self::GenericAuthorization(msg) => msg.to_value(),
+            Self::SendAuthorization(msg) => msg.to_value(),
         }
     }
 }
 
 impl CosmosJsonSerializable {
+    /// Address of the account which has to sign the message. Validator operator address is given
+    /// for commission withdrawals, it shares the bytes with operator's account address.
+    pub fn signer(&self) -> Option<&str> {
+        match self {
+            Self::MsgGrant(msg) => Some(&msg.granter),
+            Self::MsgRevoke(msg) => Some(&msg.granter),
+            Self::MsgSetWithdrawAddress(msg) => Some(&msg.delegator_address),
+            Self::MsgWithdrawDelegatorReward(msg) => Some(&msg.delegator_address),
+            Self::MsgWithdrawValidatorCommission(msg) => Some(&msg.validator_address),
+            Self::MsgSend(msg) => Some(&msg.from_address),
+            Self::MsgExec(msg) => Some(&msg.grantee),
+            Self::GenericAuthorization(_) => None,
+            Self::SendAuthorization(_) => None,
+        }
+    }
+
     pub fn to_any(&self) -> Result<Any, EncodeError> {
         match self {
             Self::MsgGrant(msg) => Any::from_msg(msg),
+            Self::MsgRevoke(msg) => Any::from_msg(msg),
             Self::MsgSetWithdrawAddress(msg) => Any::from_msg(msg),
             Self::MsgWithdrawDelegatorReward(msg) => Any::from_msg(msg),
             Self::MsgWithdrawValidatorCommission(msg) => Any::from_msg(msg),
             Self::MsgSend(msg) => Any::from_msg(msg),
             Self::MsgExec(msg) => Any::from_msg(&msg.to_native_msg_exec()?),
             Self::GenericAuthorization(msg) => Any::from_msg(msg),
+            Self::SendAuthorization(msg) => Any::from_msg(msg),
         }
     }
 }
 
+impl TryFrom<&Any> for CosmosJsonSerializable {
+    type Error = eyre::Report;
+
+    /// Decodes transaction message back from its protobuf encoding
+    fn try_from(any: &Any) -> Result<Self, Self::Error> {
+        let type_url = any.type_url.as_str();
+        let decoded = if type_url == MsgGrant::type_url() {
+            any.to_msg::<MsgGrant>().map(Self::from)
+        } else if type_url == MsgRevoke::type_url() {
+            any.to_msg::<MsgRevoke>().map(Self::from)
+        } else if type_url == MsgSetWithdrawAddress::type_url() {
+            any.to_msg::<MsgSetWithdrawAddress>().map(Self::from)
+        } else if type_url == MsgWithdrawDelegatorReward::type_url() {
+            any.to_msg::<MsgWithdrawDelegatorReward>().map(Self::from)
+        } else if type_url == MsgWithdrawValidatorCommission::type_url() {
+            any.to_msg::<MsgWithdrawValidatorCommission>()
+                .map(Self::from)
+        } else if type_url == MsgSend::type_url() {
+            any.to_msg::<MsgSend>().map(Self::from)
+        } else if type_url == GenericAuthorization::type_url() {
+            any.to_msg::<GenericAuthorization>().map(Self::from)
+        } else if type_url == SendAuthorization::type_url() {
+            any.to_msg::<SendAuthorization>().map(Self::from)
+        } else if type_url == MsgExec::type_url() {
+            let msg_exec = any.to_msg::<MsgExec>()?;
+            return Ok(MsgExecCustom {
+                grantee: msg_exec.grantee,
+                msgs: msg_exec
+                    .msgs
+                    .iter()
+                    .map(Self::try_from)
+                    .collect::<eyre::Result<_>>()?,
+            }
+            .into());
+        } else {
+            bail!("unsupported message type '{type_url}'");
+        };
+
+        decoded.wrap_err_with(|| format!("failed to decode '{type_url}'"))
+    }
+}
+
+impl TryFrom<&Value> for CosmosJsonSerializable {
+    type Error = eyre::Report;
+
+    /// Parses transaction message back from the JSON produced by [`ToCosmosJson`], e.g. from
+    /// `--generate-only` output
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        let type_url = json_str(value, "@type")?;
+        let msg = if type_url == MsgGrant::type_url() {
+            let grant = value
+                .get("grant")
+                .filter(|grant| !grant.is_null())
+                .map(|grant| -> eyre::Result<Grant> {
+                    let authorization = grant
+                        .get("authorization")
+                        .filter(|authorization| !authorization.is_null())
+                        .map(|authorization| {
+                            Self::try_from(authorization)?
+                                .to_any()
+                                .map_err(eyre::Report::from)
+                        })
+                        .transpose()?;
+                    let expiration = grant
+                        .get("expiration")
+                        .filter(|expiration| !expiration.is_null())
+                        .map(|expiration| serde_json::from_value(expiration.clone()))
+                        .transpose()
+                        .wrap_err("failed to parse grant expiration")?;
+
+                    Ok(Grant {
+                        authorization,
+                        expiration,
+                    })
+                })
+                .transpose()?;
+
+            MsgGrant {
+                granter: json_str(value, "granter")?,
+                grantee: json_str(value, "grantee")?,
+                grant,
+            }
+            .into()
+        } else if type_url == MsgRevoke::type_url() {
+            MsgRevoke {
+                granter: json_str(value, "granter")?,
+                grantee: json_str(value, "grantee")?,
+                msg_type_url: json_str(value, "msg_type_url")?,
+            }
+            .into()
+        } else if type_url == MsgSetWithdrawAddress::type_url() {
+            MsgSetWithdrawAddress {
+                delegator_address: json_str(value, "delegator_address")?,
+                withdraw_address: json_str(value, "withdraw_address")?,
+            }
+            .into()
+        } else if type_url == MsgWithdrawDelegatorReward::type_url() {
+            MsgWithdrawDelegatorReward {
+                delegator_address: json_str(value, "delegator_address")?,
+                validator_address: json_str(value, "validator_address")?,
+            }
+            .into()
+        } else if type_url == MsgWithdrawValidatorCommission::type_url() {
+            MsgWithdrawValidatorCommission {
+                validator_address: json_str(value, "validator_address")?,
+            }
+            .into()
+        } else if type_url == MsgSend::type_url() {
+            MsgSend {
+                from_address: json_str(value, "from_address")?,
+                to_address: json_str(value, "to_address")?,
+                amount: json_coins(value, "amount")?,
+            }
+            .into()
+        } else if type_url == GenericAuthorization::type_url() {
+            GenericAuthorization {
+                msg: json_str(value, "msg")?,
+            }
+            .into()
+        } else if type_url == SendAuthorization::type_url() {
+            SendAuthorization {
+                spend_limit: json_coins(value, "spend_limit")?,
+                allow_list: json_array(value, "allow_list")?
+                    .iter()
+                    .map(|address| {
+                        address
+                            .as_str()
+                            .map(str::to_string)
+                            .wrap_err("message field 'allow_list' has to contain strings")
+                    })
+                    .collect::<eyre::Result<_>>()?,
+            }
+            .into()
+        } else if type_url == MsgExec::type_url() {
+            MsgExecCustom {
+                grantee: json_str(value, "grantee")?,
+                msgs: json_array(value, "msgs")?
+                    .iter()
+                    .map(Self::try_from)
+                    .collect::<eyre::Result<_>>()?,
+            }
+            .into()
+        } else {
+            bail!("unsupported message type '{type_url}'");
+        };
+
+        Ok(msg)
+    }
+}
+
+fn json_str(value: &Value, field: &str) -> eyre::Result<String> {
+    value
+        .get(field)
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .wrap_err_with(|| format!("message field '{field}' is missing or not a string"))
+}
+
+fn json_array<'a>(value: &'a Value, field: &str) -> eyre::Result<&'a Vec<Value>> {
+    value
+        .get(field)
+        .and_then(Value::as_array)
+        .wrap_err_with(|| format!("message field '{field}' is missing or not an array"))
+}
+
+fn json_coins(value: &Value, field: &str) -> eyre::Result<Vec<Coin>> {
+    json_array(value, field)?
+        .iter()
+        .map(|coin| {
+            Ok(Coin {
+                denom: json_str(coin, "denom")?,
+                amount: json_str(coin, "amount")?,
+            })
+        })
+        .collect()
+}
+
 impl From<MsgGrant> for CosmosJsonSerializable {
     fn from(value: MsgGrant) -> Self {
         Self::MsgGrant(value)
     }
 }
 
+impl From<MsgRevoke> for CosmosJsonSerializable {
+    fn from(value: MsgRevoke) -> Self {
+        Self::MsgRevoke(value)
+    }
+}
+
 impl From<MsgSetWithdrawAddress> for CosmosJsonSerializable {
     fn from(value: MsgSetWithdrawAddress) -> Self {
         Self::MsgSetWithdrawAddress(value)
@@ -196,6 +430,12 @@ impl From<GenericAuthorization> for CosmosJsonSerializable {
     }
 }
 
+impl From<SendAuthorization> for CosmosJsonSerializable {
+    fn from(value: SendAuthorization) -> Self {
+        Self::SendAuthorization(value)
+    }
+}
+
 /// MsgExecCustom represents MsgExec message, but constrainted to message types supported by CosmosJsonSerializable enum
 #[derive(Clone)]
 pub struct MsgExecCustom {
@@ -224,8 +464,19 @@ impl TryFrom<MsgExecCustom> for MsgExec {
     }
 }
 
+/// Timestamp given either in RFC3339, or as a duration from now
 #[derive(Clone, Debug)]
-pub struct TimestampStr(Timestamp);
+pub struct TimestampStr {
+    timestamp: Timestamp,
+    duration: Option<Duration>,
+}
+
+impl TimestampStr {
+    /// Duration the timestamp was given as, if it was relative to now
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration
+    }
+}
 
 impl FromStr for TimestampStr {
     type Err = eyre::ErrReport;
@@ -233,11 +484,15 @@ impl FromStr for TimestampStr {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         if let Ok(duration) = DurationString::from_str(s) {
             let now = UtcDateTime::now();
-            let expiration = now + Duration::from(duration);
-            return Ok(Self(Timestamp {
-                seconds: expiration.unix_timestamp(),
-                nanos: expiration.nanosecond() as i32,
-            }));
+            let duration = Duration::from(duration);
+            let expiration = now + duration;
+            return Ok(Self {
+                timestamp: Timestamp {
+                    seconds: expiration.unix_timestamp(),
+                    nanos: expiration.nanosecond() as i32,
+                },
+                duration: Some(duration),
+            });
         }
 
         let t = OffsetDateTime::parse(s, &Rfc3339)?;
@@ -246,15 +501,161 @@ impl FromStr for TimestampStr {
             bail!("date is out of range")
         }
 
-        Ok(Self(Timestamp {
-            seconds: t.unix_timestamp(),
-            nanos: t.nanosecond() as i32,
-        }))
+        Ok(Self {
+            timestamp: Timestamp {
+                seconds: t.unix_timestamp(),
+                nanos: t.nanosecond() as i32,
+            },
+            duration: None,
+        })
     }
 }
 
 impl AsRef<Timestamp> for TimestampStr {
     fn as_ref(&self) -> &Timestamp {
-        &self.0
+        &self.timestamp
+    }
+}
+
+/// Implements serde Deserialize for types which are parsed from strings via FromStr
+macro_rules! deserialize_from_str {
+    ($($ty:ty),+ $(,)?) => {
+        $(
+            impl<'de> serde::Deserialize<'de> for $ty {
+                fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    let s = String::deserialize(deserializer)?;
+                    s.parse().map_err(|err| serde::de::Error::custom(format!("{err:#}")))
+                }
+            }
+        )+
+    };
+}
+
+pub(crate) use deserialize_from_str;
+
+#[cfg(test)]
+mod test {
+    use cosmrs::{
+        Any,
+        proto::{
+            Timestamp,
+            cosmos::{
+                authz::v1beta1::{GenericAuthorization, Grant, MsgGrant, MsgRevoke},
+                bank::v1beta1::{MsgSend, SendAuthorization},
+                base::v1beta1::Coin,
+                distribution::v1beta1::{
+                    MsgSetWithdrawAddress, MsgWithdrawDelegatorReward,
+                    MsgWithdrawValidatorCommission,
+                },
+            },
+        },
+    };
+    use pretty_assertions::assert_eq;
+    use serde_json::{Value, json};
+
+    use super::{CosmosJsonSerializable, MsgExecCustom, ToCosmosJson};
+
+    #[test]
+    fn test_cosmos_json_roundtrip() {
+        let msgs: Vec<CosmosJsonSerializable> = vec![
+            MsgGrant {
+                granter: "cosmos1delegator".to_string(),
+                grantee: "cosmos1controller".to_string(),
+                grant: Some(Grant {
+                    authorization: Some(
+                        Any::from_msg(&GenericAuthorization {
+                            msg: "/cosmos.distribution.v1beta1.MsgWithdrawDelegatorReward"
+                                .to_string(),
+                        })
+                        .unwrap(),
+                    ),
+                    expiration: Some(Timestamp {
+                        seconds: 1_767_225_600,
+                        nanos: 500_000_000,
+                    }),
+                }),
+            }
+            .into(),
+            MsgGrant {
+                granter: "cosmos1delegator".to_string(),
+                grantee: "cosmos1controller".to_string(),
+                grant: Some(Grant {
+                    authorization: Some(
+                        Any::from_msg(&SendAuthorization {
+                            spend_limit: vec![Coin {
+                                denom: "uatom".to_string(),
+                                amount: "1000000".to_string(),
+                            }],
+                            allow_list: vec!["cosmos1reward".to_string()],
+                        })
+                        .unwrap(),
+                    ),
+                    expiration: None,
+                }),
+            }
+            .into(),
+            MsgRevoke {
+                granter: "cosmos1delegator".to_string(),
+                grantee: "cosmos1controller".to_string(),
+                msg_type_url: "/cosmos.distribution.v1beta1.MsgWithdrawDelegatorReward".to_string(),
+            }
+            .into(),
+            MsgSetWithdrawAddress {
+                delegator_address: "cosmos1delegator".to_string(),
+                withdraw_address: "cosmos1reward".to_string(),
+            }
+            .into(),
+            MsgExecCustom {
+                grantee: "cosmos1controller".to_string(),
+                msgs: vec![
+                    MsgWithdrawDelegatorReward {
+                        delegator_address: "cosmos1delegator".to_string(),
+                        validator_address: "cosmosvaloper1validator".to_string(),
+                    }
+                    .into(),
+                    MsgWithdrawValidatorCommission {
+                        validator_address: "cosmosvaloper1validator".to_string(),
+                    }
+                    .into(),
+                    MsgSend {
+                        from_address: "cosmos1delegator".to_string(),
+                        to_address: "cosmos1reward".to_string(),
+                        amount: vec![Coin {
+                            denom: "uatom".to_string(),
+                            amount: "1234".to_string(),
+                        }],
+                    }
+                    .into(),
+                ],
+            }
+            .into(),
+        ];
+
+        for msg in &msgs {
+            // Goes through a string, as generated transactions are read from files
+            let value: Value = serde_json::from_str(&msg.to_value().to_string()).unwrap();
+            let parsed = CosmosJsonSerializable::try_from(&value).unwrap();
+            assert_eq!(parsed.to_any().unwrap(), msg.to_any().unwrap());
+        }
+
+        let err = CosmosJsonSerializable::try_from(&json!({
+            "@type": "/cosmos.bank.v1beta1.MsgMultiSend",
+        }))
+        .err()
+        .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "unsupported message type '/cosmos.bank.v1beta1.MsgMultiSend'"
+        );
+
+        let err = CosmosJsonSerializable::try_from(&json!({
+            "@type": "/cosmos.distribution.v1beta1.MsgWithdrawValidatorCommission",
+        }))
+        .err()
+        .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "message field 'validator_address' is missing or not a string"
+        );
     }
 }
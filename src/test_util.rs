@@ -0,0 +1,50 @@
+//! Helpers shared by tests of several modules
+
+use serde_json::Value;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+/// Reads a single HTTP/1.1 request and returns its JSON body. Only requests with
+/// `Content-Length` are supported, which is what reqwest sends for JSON bodies
+pub async fn read_json_request(stream: &mut TcpStream) -> Value {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 4096];
+
+    loop {
+        let n = stream.read(&mut buf).await.unwrap();
+        assert_ne!(n, 0, "connection closed before the request was read");
+        request.extend_from_slice(&buf[..n]);
+
+        let request = String::from_utf8_lossy(&request);
+        if let Some((headers, body)) = request.split_once("\r\n\r\n") {
+            let content_length: usize = headers
+                .lines()
+                .filter_map(|line| line.split_once(':'))
+                .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+                .map(|(_, value)| value.trim().parse().unwrap())
+                .unwrap();
+
+            if body.len() >= content_length {
+                return serde_json::from_str(body).unwrap();
+            }
+        }
+    }
+}
+
+/// Answers the request with `200 OK`, and closes the connection. Body is left empty if not given
+pub async fn write_json_response(stream: &mut TcpStream, body: Option<&Value>) {
+    let response = match body {
+        Some(body) => {
+            let body = body.to_string();
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+        }
+        None => "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+    };
+
+    stream.write_all(response.as_bytes()).await.unwrap();
+}
@@ -0,0 +1,941 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use eyre::{ContextCompat, bail};
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+use sha3::{Digest, Keccak256};
+
+use crate::ser::{CosmosJsonSerializable, MsgExecCustom, ToAminoJson};
+
+pub type Eip712Types = BTreeMap<String, Vec<Eip712Field>>;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Eip712Field {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub r#type: String,
+}
+
+impl Eip712Field {
+    fn new(name: &str, r#type: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            r#type: r#type.to_string(),
+        }
+    }
+}
+
+/// EIP-712 typed structured data, see <https://eips.ethereum.org/EIPS/eip-712>
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TypedData {
+    pub types: Eip712Types,
+    pub primary_type: String,
+    pub domain: Value,
+    pub message: Value,
+}
+
+impl TypedData {
+    /// Returns `0x19 0x01 || domainSeparator || hashStruct(message)`. The signature is made over
+    /// Keccak256 of these bytes, which eth_secp256k1 signers do on their own.
+    pub fn sign_bytes(&self) -> eyre::Result<Vec<u8>> {
+        let mut bytes = vec![0x19, 0x01];
+        bytes.extend(self.hash_struct("EIP712Domain", &self.domain)?);
+        bytes.extend(self.hash_struct(&self.primary_type, &self.message)?);
+
+        Ok(bytes)
+    }
+
+    pub fn hash_struct(&self, type_name: &str, value: &Value) -> eyre::Result<[u8; 32]> {
+        let fields = self
+            .types
+            .get(type_name)
+            .wrap_err_with(|| format!("unknown EIP-712 type '{type_name}'"))?;
+        let object = value
+            .as_object()
+            .wrap_err_with(|| format!("expected object for EIP-712 type '{type_name}'"))?;
+        if let Some(key) = object
+            .keys()
+            .find(|key| !fields.iter().any(|field| &field.name == *key))
+        {
+            bail!("field '{key}' is not defined in EIP-712 type '{type_name}'");
+        }
+
+        let mut hasher = Keccak256::new();
+        hasher.update(Keccak256::digest(self.encode_type(type_name)?));
+        for field in fields {
+            let value = object.get(&field.name).wrap_err_with(|| {
+                format!(
+                    "missing field '{}' of EIP-712 type '{type_name}'",
+                    field.name
+                )
+            })?;
+            hasher.update(self.encode_value(&field.r#type, value)?);
+        }
+
+        Ok(hasher.finalize().into())
+    }
+
+    /// Returns e.g. `Mail(Person from,Person to,string contents)Person(string name,address wallet)`
+    pub fn encode_type(&self, type_name: &str) -> eyre::Result<String> {
+        if !self.types.contains_key(type_name) {
+            bail!("unknown EIP-712 type '{type_name}'");
+        }
+
+        let mut dependencies = BTreeSet::new();
+        self.find_dependencies(type_name, &mut dependencies);
+        dependencies.remove(type_name);
+
+        let mut encoded = String::new();
+        for name in std::iter::once(type_name).chain(dependencies.iter().map(String::as_str)) {
+            let fields = self.types[name]
+                .iter()
+                .map(|field| format!("{} {}", field.r#type, field.name))
+                .collect::<Vec<_>>();
+            encoded.push_str(&format!("{name}({})", fields.join(",")));
+        }
+
+        Ok(encoded)
+    }
+
+    fn find_dependencies(&self, type_name: &str, dependencies: &mut BTreeSet<String>) {
+        let type_name = type_name.split('[').next().unwrap_or(type_name);
+        if dependencies.contains(type_name) {
+            return;
+        }
+
+        let Some(fields) = self.types.get(type_name) else {
+            return;
+        };
+
+        dependencies.insert(type_name.to_string());
+        for field in fields {
+            self.find_dependencies(&field.r#type, dependencies);
+        }
+    }
+
+    fn encode_value(&self, type_name: &str, value: &Value) -> eyre::Result<[u8; 32]> {
+        if let Some((element_type, _)) = type_name
+            .strip_suffix(']')
+            .and_then(|type_name| type_name.rsplit_once('['))
+        {
+            let items = value
+                .as_array()
+                .wrap_err_with(|| format!("expected array for EIP-712 type '{type_name}'"))?;
+
+            let mut hasher = Keccak256::new();
+            for item in items {
+                hasher.update(self.encode_value(element_type, item)?);
+            }
+
+            return Ok(hasher.finalize().into());
+        }
+
+        if self.types.contains_key(type_name) {
+            return self.hash_struct(type_name, value);
+        }
+
+        encode_atomic_value(type_name, value)
+    }
+}
+
+fn encode_atomic_value(type_name: &str, value: &Value) -> eyre::Result<[u8; 32]> {
+    let mismatch = || format!("invalid value {value} for EIP-712 type '{type_name}'");
+    let unsupported = || format!("unsupported EIP-712 type '{type_name}'");
+
+    let mut word = [0u8; 32];
+    match type_name {
+        "string" => {
+            let value = value.as_str().wrap_err_with(mismatch)?;
+            word = Keccak256::digest(value).into();
+        }
+        "bytes" => {
+            let bytes = decode_hex(value).wrap_err_with(mismatch)?;
+            word = Keccak256::digest(bytes).into();
+        }
+        "bool" => {
+            word[31] = value.as_bool().wrap_err_with(mismatch)? as u8;
+        }
+        "address" => {
+            let bytes = decode_hex(value)
+                .filter(|bytes| bytes.len() == 20)
+                .wrap_err_with(mismatch)?;
+            word[12..].copy_from_slice(&bytes);
+        }
+        _ => {
+            if let Some(size) = type_name.strip_prefix("bytes") {
+                let size = size
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|size| (1..=32).contains(size))
+                    .wrap_err_with(unsupported)?;
+                let bytes = decode_hex(value)
+                    .filter(|bytes| bytes.len() <= size)
+                    .wrap_err_with(mismatch)?;
+                word[..bytes.len()].copy_from_slice(&bytes);
+            } else if let Some((signed, bits)) = type_name
+                .strip_prefix("uint")
+                .map(|bits| (false, bits))
+                .or_else(|| type_name.strip_prefix("int").map(|bits| (true, bits)))
+            {
+                let bits = bits
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|bits| (8..=256).contains(bits) && bits % 8 == 0)
+                    .wrap_err_with(unsupported)?;
+                word = encode_integer(value, signed, bits).wrap_err_with(mismatch)?;
+            } else {
+                bail!(unsupported());
+            }
+        }
+    }
+
+    Ok(word)
+}
+
+/// Integers are given either as JSON numbers, decimal strings or `0x` prefixed hex strings. Hex
+/// strings are taken as two's complement words and can use all 256 bits.
+fn encode_integer(value: &Value, signed: bool, bits: u32) -> Option<[u8; 32]> {
+    let mut word = [0u8; 32];
+    if let Some(hex) = value.as_str().and_then(|value| value.strip_prefix("0x")) {
+        let hex = format!("{hex:0>64}");
+        if hex.len() != 64 {
+            return None;
+        }
+        word.copy_from_slice(&hex::decode(hex).ok()?);
+        return Some(word);
+    }
+
+    let integer: i128 = match value {
+        Value::Number(number) => number
+            .as_i64()
+            .map(i128::from)
+            .or_else(|| number.as_u64().map(i128::from))?,
+        Value::String(value) => value.parse().ok()?,
+        _ => return None,
+    };
+
+    let in_range = match (signed, bits) {
+        (_, 128..) => signed || integer >= 0,
+        (false, _) => integer >= 0 && integer < 1 << bits,
+        (true, _) => integer >= -(1 << (bits - 1)) && integer < 1 << (bits - 1),
+    };
+    if !in_range {
+        return None;
+    }
+
+    if integer < 0 {
+        word = [0xff; 32];
+    }
+    word[16..].copy_from_slice(&integer.to_be_bytes());
+
+    Some(word)
+}
+
+fn decode_hex(value: &Value) -> Option<Vec<u8>> {
+    hex::decode(value.as_str()?.strip_prefix("0x")?).ok()
+}
+
+/// Marks the value of an `Any`, whose structure is given by the sibling `type` field
+const ANY_VALUE: &str = "<any value>";
+
+/// Field order of structures used in legacy typed data, along with the kind of nested objects.
+/// Ethermint derives the types from Go structures, so fields follow the protobuf definitions rather
+/// than the sorted amino JSON.
+fn struct_fields(kind: &str) -> eyre::Result<&'static [(&'static str, &'static str)]> {
+    Ok(match kind {
+        "cosmos-sdk/MsgExec" => &[("grantee", ""), ("msgs", "any")],
+        "cosmos-sdk/MsgGrant" => &[("granter", ""), ("grantee", ""), ("grant", "grant")],
+        "cosmos-sdk/MsgRevoke" => &[("granter", ""), ("grantee", ""), ("msg_type_url", "")],
+        "cosmos-sdk/MsgModifyWithdrawAddress" => {
+            &[("delegator_address", ""), ("withdraw_address", "")]
+        }
+        "cosmos-sdk/MsgWithdrawDelegationReward" => {
+            &[("delegator_address", ""), ("validator_address", "")]
+        }
+        "cosmos-sdk/MsgWithdrawValCommission" => &[("validator_address", "")],
+        "cosmos-sdk/MsgSend" => &[("from_address", ""), ("to_address", ""), ("amount", "coin")],
+        "cosmos-sdk/GenericAuthorization" => &[("msg", "")],
+        "cosmos-sdk/SendAuthorization" => &[("spend_limit", "coin"), ("allow_list", "")],
+        "any" => &[("type", ""), ("value", ANY_VALUE)],
+        "grant" => &[("authorization", "any"), ("expiration", "")],
+        "coin" => &[("denom", ""), ("amount", "")],
+        _ => bail!("EIP-712 sign mode does not support '{kind}'"),
+    })
+}
+
+/// Builds the legacy Ethermint typed data of an amino JSON `StdSignDoc`, as verified by chains
+/// accepting the `ExtensionOptionsWeb3Tx` extension. Injective uses its own domain name and signs
+/// over the timeout height as well.
+pub fn legacy_typed_data(
+    mut sign_doc: Value,
+    chain_id: u64,
+    fee_payer: &str,
+    injective: bool,
+) -> eyre::Result<TypedData> {
+    let sign_doc_object = sign_doc
+        .as_object_mut()
+        .wrap_err("expected amino JSON sign doc object")?;
+    sign_doc_object
+        .get_mut("fee")
+        .and_then(Value::as_object_mut)
+        .wrap_err("amino JSON sign doc has no fee")?
+        .insert("feePayer".to_string(), fee_payer.into());
+    if injective {
+        sign_doc_object.insert("timeout_height".to_string(), "0".into());
+    }
+
+    let msgs = sign_doc_object
+        .get("msgs")
+        .and_then(Value::as_array)
+        .filter(|msgs| !msgs.is_empty())
+        .wrap_err("amino JSON sign doc has no messages")?;
+
+    // Types are derived from the first message only, rest of them must look the same
+    let mut msg_types: Option<(String, Eip712Types)> = None;
+    for msg in msgs {
+        let (kind, types) = amino_msg_types(msg)?;
+        match &msg_types {
+            None => msg_types = Some((kind, types)),
+            Some((first_kind, _)) if *first_kind != kind => {
+                bail!(
+                    "EIP-712 sign mode requires messages of the same type, got '{first_kind}' and '{kind}'"
+                );
+            }
+            Some((_, first_types)) if *first_types != types => {
+                bail!("EIP-712 sign mode requires messages of the same structure");
+            }
+            Some(_) => {}
+        }
+    }
+
+    let mut types = legacy_root_types(injective);
+    types.extend(msg_types.map(|(_, types)| types).unwrap_or_default());
+
+    Ok(TypedData {
+        types,
+        primary_type: "Tx".to_string(),
+        domain: json!({
+            "name": if injective { "Injective Web3" } else { "Cosmos Web3" },
+            "version": "1.0.0",
+            "chainId": chain_id,
+            "verifyingContract": "cosmos",
+            "salt": "0",
+        }),
+        message: sign_doc,
+    })
+}
+
+/// Returns amino type of the message, along with the EIP-712 types describing its value
+fn amino_msg_types(msg: &Value) -> eyre::Result<(String, Eip712Types)> {
+    let kind = msg
+        .get("type")
+        .and_then(Value::as_str)
+        .wrap_err("amino JSON message has no type")?;
+    let value = msg
+        .get("value")
+        .and_then(Value::as_object)
+        .wrap_err("amino JSON message has no value")?;
+
+    let mut types = Eip712Types::new();
+    add_struct_types(&mut types, "MsgValue", "_", kind, value)?;
+
+    Ok((kind.to_string(), types))
+}
+
+/// Splits messages into groups which can be signed with legacy typed data, one transaction per
+/// group. Messages of the same type & structure are kept together in their original order, and
+/// `MsgExec` is split by the type of the executed messages.
+pub fn split_eip712_msgs(
+    msgs: Vec<CosmosJsonSerializable>,
+) -> eyre::Result<Vec<Vec<CosmosJsonSerializable>>> {
+    let mut groups: Vec<((String, Eip712Types), Vec<CosmosJsonSerializable>)> = Vec::new();
+    let mut push = |msg: CosmosJsonSerializable| -> eyre::Result<()> {
+        let key = amino_msg_types(&msg.to_amino_value()?)?;
+        match groups.iter_mut().find(|(group_key, _)| *group_key == key) {
+            Some((_, group)) => group.push(msg),
+            None => groups.push((key, vec![msg])),
+        }
+        Ok(())
+    };
+
+    for msg in msgs {
+        let CosmosJsonSerializable::MsgExec(MsgExecCustom { grantee, msgs }) = msg else {
+            push(msg)?;
+            continue;
+        };
+
+        for msgs in split_eip712_msgs(msgs)? {
+            push(
+                MsgExecCustom {
+                    grantee: grantee.clone(),
+                    msgs,
+                }
+                .into(),
+            )?;
+        }
+    }
+
+    Ok(groups.into_iter().map(|(_, group)| group).collect())
+}
+
+fn legacy_root_types(injective: bool) -> Eip712Types {
+    let mut tx = vec![
+        Eip712Field::new("account_number", "string"),
+        Eip712Field::new("chain_id", "string"),
+        Eip712Field::new("fee", "Fee"),
+        Eip712Field::new("memo", "string"),
+        Eip712Field::new("msgs", "Msg[]"),
+        Eip712Field::new("sequence", "string"),
+    ];
+    if injective {
+        tx.push(Eip712Field::new("timeout_height", "string"));
+    }
+
+    BTreeMap::from([
+        (
+            "EIP712Domain".to_string(),
+            vec![
+                Eip712Field::new("name", "string"),
+                Eip712Field::new("version", "string"),
+                Eip712Field::new("chainId", "uint256"),
+                Eip712Field::new("verifyingContract", "string"),
+                Eip712Field::new("salt", "string"),
+            ],
+        ),
+        ("Tx".to_string(), tx),
+        (
+            "Fee".to_string(),
+            vec![
+                Eip712Field::new("feePayer", "string"),
+                Eip712Field::new("amount", "Coin[]"),
+                Eip712Field::new("gas", "string"),
+            ],
+        ),
+        (
+            "Coin".to_string(),
+            vec![
+                Eip712Field::new("denom", "string"),
+                Eip712Field::new("amount", "string"),
+            ],
+        ),
+        (
+            "Msg".to_string(),
+            vec![
+                Eip712Field::new("type", "string"),
+                Eip712Field::new("value", "MsgValue"),
+            ],
+        ),
+    ])
+}
+
+/// Adds type of the object and its nested objects, named after their path like Ethermint does,
+/// e.g. `_.msgs.value` becomes `TypeMsgsValue`. Empty collections are left out of the types.
+fn add_struct_types(
+    types: &mut Eip712Types,
+    type_name: &str,
+    prefix: &str,
+    kind: &str,
+    object: &Map<String, Value>,
+) -> eyre::Result<()> {
+    let fields = struct_fields(kind)?;
+    if let Some(key) = object
+        .keys()
+        .find(|key| !fields.iter().any(|(name, _)| name == key))
+    {
+        bail!("EIP-712 sign mode does not support field '{key}' of '{kind}'");
+    }
+
+    let mut type_fields = Vec::new();
+    for (name, child_kind) in fields {
+        let Some(value) = object.get(*name) else {
+            continue;
+        };
+        let child_kind = match *child_kind {
+            ANY_VALUE => object
+                .get("type")
+                .and_then(Value::as_str)
+                .wrap_err("Any value has no type")?,
+            child_kind => child_kind,
+        };
+
+        let path = format!("{prefix}.{name}");
+        let field_type = match value {
+            Value::String(_) => "string".to_string(),
+            Value::Bool(_) => "bool".to_string(),
+            Value::Object(child) => {
+                let child_type = sanitize_typedef(&path);
+                add_struct_types(types, &child_type, &path, child_kind, child)?;
+                child_type
+            }
+            Value::Array(items) if items.is_empty() => continue,
+            Value::Array(items) if items.iter().all(Value::is_string) => "string[]".to_string(),
+            Value::Array(items) => {
+                let child_type = sanitize_typedef(&path);
+
+                let mut item_types: Option<Eip712Types> = None;
+                for item in items {
+                    let child = item.as_object().wrap_err_with(|| {
+                        format!("EIP-712 sign mode does not support mixed array '{name}'")
+                    })?;
+
+                    let mut child_types = Eip712Types::new();
+                    add_struct_types(&mut child_types, &child_type, &path, child_kind, child)?;
+                    match &item_types {
+                        None => item_types = Some(child_types),
+                        Some(first_types) if *first_types != child_types => {
+                            bail!(
+                                "EIP-712 sign mode does not support heterogeneous array '{name}', split the messages into separate transactions"
+                            );
+                        }
+                        Some(_) => {}
+                    }
+                }
+                types.extend(item_types.unwrap_or_default());
+
+                format!("{child_type}[]")
+            }
+            _ => bail!("EIP-712 sign mode does not support value {value} of field '{name}'"),
+        };
+
+        type_fields.push(Eip712Field::new(name, &field_type));
+    }
+
+    types.insert(type_name.to_string(), type_fields);
+
+    Ok(())
+}
+
+/// `_.foo_bar.baz` -> `TypeFooBarBaz`
+fn sanitize_typedef(path: &str) -> String {
+    path.split('.')
+        .flat_map(|part| match part {
+            "_" => vec!["Type".to_string()],
+            part => part
+                .split('_')
+                .map(|subpart| {
+                    let mut chars = subpart.chars();
+                    chars
+                        .next()
+                        .map(|first| first.to_uppercase().chain(chars).collect())
+                        .unwrap_or_default()
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// EVM chain id of the EIP-712 domain. Ethermint chain ids carry it, e.g. `evmos_9001-2`, while
+/// Injective signs with Ethereum mainnet chain id.
+pub fn default_eip712_chain_id(chain_id: &str, injective: bool) -> eyre::Result<u64> {
+    let evm_chain_id = chain_id
+        .rsplit_once('_')
+        .and_then(|(_, rest)| rest.split_once('-'))
+        .and_then(|(evm_chain_id, _)| evm_chain_id.parse().ok());
+
+    match evm_chain_id {
+        Some(evm_chain_id) => Ok(evm_chain_id),
+        None if injective => Ok(1),
+        None => bail!(
+            "could not determine EVM chain id from chain id '{chain_id}', use --eip712-chain-id"
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use bip32::secp256k1::ecdsa::SigningKey;
+    use cosmrs::{
+        Any, Coin,
+        proto::{
+            cosmos::{
+                authz::v1beta1::{GenericAuthorization, Grant, MsgGrant},
+                bank::v1beta1::SendAuthorization,
+                base::v1beta1::Coin as ProtoCoin,
+                distribution::v1beta1::{
+                    MsgSetWithdrawAddress, MsgWithdrawDelegatorReward,
+                    MsgWithdrawValidatorCommission,
+                },
+            },
+            prost::Name,
+        },
+        tx::Fee,
+    };
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use sha3::{Digest, Keccak256};
+
+    use super::{
+        Eip712Field, TypedData, default_eip712_chain_id, legacy_typed_data, split_eip712_msgs,
+    };
+    use crate::{
+        ser::{CosmosJsonSerializable, MsgExecCustom, ToCosmosJson, amino_sign_doc},
+        wallet::{LocalSigner, Signer, WalletKeyType},
+    };
+
+    const DELEGATOR: &str = "evmos16nf0mht68937d27cwrqqdtwv9y2alm06nevstf";
+    const CONTROLLER: &str = "evmos1c9rjzdd3f3mu30he3eelwqsgxf06ph83xd26eg";
+    const VALIDATOR: &str = "evmosvaloper16nf0mht68937d27cwrqqdtwv9y2alm067hrq25";
+    const OTHER_VALIDATOR: &str = "evmosvaloper1my5c5yx3kpe4sd7uf0v9mtryrv8neme8casswk";
+
+    fn fields(fields: &[(&str, &str)]) -> Vec<Eip712Field> {
+        fields
+            .iter()
+            .map(|(name, r#type)| Eip712Field::new(name, r#type))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_typed_data_mail() {
+        // Example from EIP-712 specification
+        let typed_data = TypedData {
+            types: BTreeMap::from([
+                (
+                    "EIP712Domain".to_string(),
+                    fields(&[
+                        ("name", "string"),
+                        ("version", "string"),
+                        ("chainId", "uint256"),
+                        ("verifyingContract", "address"),
+                    ]),
+                ),
+                (
+                    "Person".to_string(),
+                    fields(&[("name", "string"), ("wallet", "address")]),
+                ),
+                (
+                    "Mail".to_string(),
+                    fields(&[("from", "Person"), ("to", "Person"), ("contents", "string")]),
+                ),
+            ]),
+            primary_type: "Mail".to_string(),
+            domain: json!({
+                "name": "Ether Mail",
+                "version": "1",
+                "chainId": 1,
+                "verifyingContract": "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC",
+            }),
+            message: json!({
+                "from": {
+                    "name": "Cow",
+                    "wallet": "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826",
+                },
+                "to": {
+                    "name": "Bob",
+                    "wallet": "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB",
+                },
+                "contents": "Hello, Bob!",
+            }),
+        };
+
+        assert_eq!(
+            typed_data.encode_type("Mail").unwrap(),
+            "Mail(Person from,Person to,string contents)Person(string name,address wallet)"
+        );
+        assert_eq!(
+            hex::encode(
+                typed_data
+                    .hash_struct("EIP712Domain", &typed_data.domain)
+                    .unwrap()
+            ),
+            "f2cee375fa42b42143804025fc449deafd50cc031ca257e0b194a650a912090f"
+        );
+        assert_eq!(
+            hex::encode(typed_data.hash_struct("Mail", &typed_data.message).unwrap()),
+            "c52c0ee5d84264471806290a3f2c4cecfc5490626bf912d01f240d7a274b371e"
+        );
+
+        let sign_bytes = typed_data.sign_bytes().unwrap();
+        assert_eq!(
+            hex::encode(Keccak256::digest(&sign_bytes)),
+            "be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2"
+        );
+
+        let key = SigningKey::from_slice(&Keccak256::digest("cow")).unwrap();
+        let signature = LocalSigner::new(key)
+            .sign(
+                &sign_bytes,
+                WalletKeyType::EthermintSecp256k1 { injective: false },
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            hex::encode(signature),
+            concat!(
+                "4355c47d63924e8a72e509b65029052eb6c299d53a04e167c5775fd466751c9d",
+                "07299936d304c153f6443dfa05f40ff007d72911b6f72307f996231605b91562",
+                "01",
+            )
+        );
+    }
+
+    fn sign_doc(msgs: &[CosmosJsonSerializable]) -> serde_json::Value {
+        let fee = Fee::from_amount_and_gas(
+            Coin {
+                denom: "aevmos".parse().unwrap(),
+                amount: 4_000_000_000_000_000,
+            },
+            200_000_u64,
+        );
+
+        amino_sign_doc("evmos_9001-2", 42, 7, &fee, "cosmos-withdrawer", msgs).unwrap()
+    }
+
+    #[test]
+    fn test_legacy_typed_data() {
+        let msgs: Vec<CosmosJsonSerializable> = vec![
+            MsgExecCustom {
+                grantee: CONTROLLER.to_string(),
+                msgs: vec![
+                    MsgWithdrawDelegatorReward {
+                        delegator_address: DELEGATOR.to_string(),
+                        validator_address: VALIDATOR.to_string(),
+                    }
+                    .into(),
+                    MsgWithdrawDelegatorReward {
+                        delegator_address: DELEGATOR.to_string(),
+                        validator_address: OTHER_VALIDATOR.to_string(),
+                    }
+                    .into(),
+                ],
+            }
+            .into(),
+        ];
+
+        let typed_data = legacy_typed_data(sign_doc(&msgs), 9001, CONTROLLER, false).unwrap();
+        assert_eq!(
+            typed_data.encode_type("Tx").unwrap(),
+            concat!(
+                "Tx(string account_number,string chain_id,Fee fee,string memo,Msg[] msgs,string sequence)",
+                "Coin(string denom,string amount)",
+                "Fee(string feePayer,Coin[] amount,string gas)",
+                "Msg(string type,MsgValue value)",
+                "MsgValue(string grantee,TypeMsgs[] msgs)",
+                "TypeMsgs(string type,TypeMsgsValue value)",
+                "TypeMsgsValue(string delegator_address,string validator_address)",
+            )
+        );
+        assert_eq!(
+            serde_json::to_value(&typed_data).unwrap()["domain"],
+            json!({
+                "chainId": 9001,
+                "name": "Cosmos Web3",
+                "salt": "0",
+                "verifyingContract": "cosmos",
+                "version": "1.0.0",
+            })
+        );
+        assert_eq!(
+            typed_data.message,
+            json!({
+                "account_number": "42",
+                "chain_id": "evmos_9001-2",
+                "fee": {
+                    "amount": [{"amount": "4000000000000000", "denom": "aevmos"}],
+                    "feePayer": CONTROLLER,
+                    "gas": "200000",
+                },
+                "memo": "cosmos-withdrawer",
+                "msgs": [{
+                    "type": "cosmos-sdk/MsgExec",
+                    "value": {
+                        "grantee": CONTROLLER,
+                        "msgs": [
+                            {
+                                "type": "cosmos-sdk/MsgWithdrawDelegationReward",
+                                "value": {
+                                    "delegator_address": DELEGATOR,
+                                    "validator_address": VALIDATOR,
+                                },
+                            },
+                            {
+                                "type": "cosmos-sdk/MsgWithdrawDelegationReward",
+                                "value": {
+                                    "delegator_address": DELEGATOR,
+                                    "validator_address": OTHER_VALIDATOR,
+                                },
+                            },
+                        ],
+                    },
+                }],
+                "sequence": "7",
+            })
+        );
+        // Digests of the typed data above, serialized as `eth_signTypedData_v4` input. They were
+        // computed with a standalone implementation of the EIP-712 encoding, checked against the
+        // Mail example of the specification, and can be reproduced with any v4 implementation, e.g.
+        // `TypedDataUtils.eip712Hash` of `@metamask/eth-sig-util`
+        assert_eq!(
+            hex::encode(Keccak256::digest(typed_data.sign_bytes().unwrap())),
+            "67d6a2f4289e4f00c4c65830bbe24d08376206a92a67bf9a637fa6f653ec6ff6"
+        );
+
+        let typed_data = legacy_typed_data(sign_doc(&msgs), 1, CONTROLLER, true).unwrap();
+        assert_eq!(
+            typed_data.encode_type("Tx").unwrap(),
+            concat!(
+                "Tx(string account_number,string chain_id,Fee fee,string memo,Msg[] msgs,string sequence,string timeout_height)",
+                "Coin(string denom,string amount)",
+                "Fee(string feePayer,Coin[] amount,string gas)",
+                "Msg(string type,MsgValue value)",
+                "MsgValue(string grantee,TypeMsgs[] msgs)",
+                "TypeMsgs(string type,TypeMsgsValue value)",
+                "TypeMsgsValue(string delegator_address,string validator_address)",
+            )
+        );
+        assert_eq!(typed_data.domain["name"], json!("Injective Web3"));
+        assert_eq!(typed_data.message["timeout_height"], json!("0"));
+        assert_eq!(
+            hex::encode(Keccak256::digest(typed_data.sign_bytes().unwrap())),
+            "a59b4707ffa65e79e74be3ef6ecfaa814725ce6b26345e6a3364e2e6f0a927f9"
+        );
+
+        // Legacy typed data describes the first message only
+        let msgs: Vec<CosmosJsonSerializable> = vec![
+            MsgExecCustom {
+                grantee: CONTROLLER.to_string(),
+                msgs: vec![
+                    MsgWithdrawDelegatorReward {
+                        delegator_address: DELEGATOR.to_string(),
+                        validator_address: VALIDATOR.to_string(),
+                    }
+                    .into(),
+                    MsgWithdrawValidatorCommission {
+                        validator_address: VALIDATOR.to_string(),
+                    }
+                    .into(),
+                ],
+            }
+            .into(),
+        ];
+        let err = legacy_typed_data(sign_doc(&msgs), 9001, CONTROLLER, false).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "EIP-712 sign mode does not support heterogeneous array 'msgs', split the messages into separate transactions"
+        );
+    }
+
+    #[test]
+    fn test_split_eip712_msgs() {
+        let reward = |validator_address: &str| -> CosmosJsonSerializable {
+            MsgWithdrawDelegatorReward {
+                delegator_address: DELEGATOR.to_string(),
+                validator_address: validator_address.to_string(),
+            }
+            .into()
+        };
+        let commission = || -> CosmosJsonSerializable {
+            MsgWithdrawValidatorCommission {
+                validator_address: VALIDATOR.to_string(),
+            }
+            .into()
+        };
+        let exec = |msgs: Vec<CosmosJsonSerializable>| -> CosmosJsonSerializable {
+            MsgExecCustom {
+                grantee: CONTROLLER.to_string(),
+                msgs,
+            }
+            .into()
+        };
+        let set_withdraw_address = || -> CosmosJsonSerializable {
+            MsgSetWithdrawAddress {
+                delegator_address: DELEGATOR.to_string(),
+                withdraw_address: CONTROLLER.to_string(),
+            }
+            .into()
+        };
+        let grant = |authorization: Any| -> CosmosJsonSerializable {
+            MsgGrant {
+                granter: DELEGATOR.to_string(),
+                grantee: CONTROLLER.to_string(),
+                grant: Some(Grant {
+                    authorization: Some(authorization),
+                    expiration: None,
+                }),
+            }
+            .into()
+        };
+        let generic_authorization =
+            |msg: String| Any::from_msg(&GenericAuthorization { msg }).unwrap();
+        let send_authorization = || {
+            Any::from_msg(&SendAuthorization {
+                spend_limit: vec![ProtoCoin {
+                    denom: "aevmos".to_string(),
+                    amount: "1000000000000000000".to_string(),
+                }],
+                allow_list: vec![CONTROLLER.to_string()],
+            })
+            .unwrap()
+        };
+        let values = |batches: &[Vec<CosmosJsonSerializable>]| {
+            batches
+                .iter()
+                .map(|msgs| msgs.iter().map(|msg| msg.to_value()).collect::<Vec<_>>())
+                .collect::<Vec<_>>()
+        };
+
+        // Withdrawal of rewards & commission, as built by `withdraw`
+        let batches = split_eip712_msgs(vec![exec(vec![
+            reward(VALIDATOR),
+            commission(),
+            reward(OTHER_VALIDATOR),
+        ])])
+        .unwrap();
+        assert_eq!(
+            values(&batches),
+            values(&[
+                vec![exec(vec![reward(VALIDATOR), reward(OTHER_VALIDATOR)])],
+                vec![exec(vec![commission()])],
+            ])
+        );
+        for msgs in batches.iter() {
+            legacy_typed_data(sign_doc(msgs), 9001, CONTROLLER, false).unwrap();
+        }
+
+        // Grants of the authz-send method, as built by `setup-valoper`
+        let batches = split_eip712_msgs(vec![
+            set_withdraw_address(),
+            grant(generic_authorization(MsgWithdrawDelegatorReward::type_url())),
+            grant(generic_authorization(
+                MsgWithdrawValidatorCommission::type_url(),
+            )),
+            grant(send_authorization()),
+        ])
+        .unwrap();
+        assert_eq!(
+            values(&batches),
+            values(&[
+                vec![set_withdraw_address()],
+                vec![
+                    grant(generic_authorization(MsgWithdrawDelegatorReward::type_url())),
+                    grant(generic_authorization(
+                        MsgWithdrawValidatorCommission::type_url()
+                    )),
+                ],
+                vec![grant(send_authorization())],
+            ])
+        );
+        for msgs in batches.iter() {
+            legacy_typed_data(sign_doc(msgs), 9001, CONTROLLER, false).unwrap();
+        }
+
+        // Messages of one type are kept in one transaction
+        let msgs = vec![exec(vec![reward(VALIDATOR), reward(OTHER_VALIDATOR)])];
+        assert_eq!(
+            values(&split_eip712_msgs(msgs.clone()).unwrap()),
+            values(&[msgs])
+        );
+    }
+
+    #[test]
+    fn test_default_eip712_chain_id() {
+        assert_eq!(
+            default_eip712_chain_id("evmos_9001-2", false).unwrap(),
+            9001
+        );
+        assert_eq!(default_eip712_chain_id("injective-1", true).unwrap(), 1);
+        assert!(default_eip712_chain_id("cosmoshub-4", false).is_err());
+    }
+}
@@ -0,0 +1,193 @@
+use std::path::Path;
+
+use aes_gcm::{
+    Aes256Gcm, KeyInit, Nonce,
+    aead::{Aead, Payload},
+};
+use aes_kw::KekAes128;
+use base64::{
+    Engine,
+    prelude::{BASE64_STANDARD, BASE64_URL_SAFE_NO_PAD},
+};
+use bip32::secp256k1::ecdsa::SigningKey;
+use cosmrs::proto::cosmos::crypto::secp256k1::PrivKey;
+use eyre::{Context, ContextCompat, bail, eyre};
+use prost::Message;
+use serde::Deserialize;
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+use crate::cosmos_sdk_extra::keyring::Record;
+
+const JWE_ALG: &str = "PBES2-HS256+A128KW";
+const JWE_ENC: &str = "A256GCM";
+
+/// Private key types which are plain secp256k1 keys, differing only in their type URL
+const SECP256K1_PRIV_KEY_TYPES: &[&str] = &[
+    "/cosmos.crypto.secp256k1.PrivKey",
+    "/ethermint.crypto.v1.ethsecp256k1.PrivKey",
+    "/injective.crypto.v1beta1.ethsecp256k1.PrivKey",
+];
+
+#[derive(Deserialize)]
+struct JweHeader {
+    alg: String,
+    enc: String,
+    p2c: u32,
+    p2s: String,
+    #[serde(default)]
+    zip: Option<String>,
+}
+
+/// Keyring item, as stored by github.com/99designs/keyring
+#[derive(Deserialize)]
+struct KeyringItem {
+    #[serde(rename = "Data")]
+    data: String,
+}
+
+/// Loads private key named `key_name` from Cosmos SDK `file` keyring backend directory
+/// (e.g. `~/.osmosisd/keyring-file`).
+pub fn load_keyring_key(dir: &Path, key_name: &str, passphrase: &str) -> eyre::Result<SigningKey> {
+    let path = dir.join(format!("{key_name}.info"));
+    let data = std::fs::read_to_string(&path)
+        .wrap_err_with(|| format!("failed to read keyring entry '{}'", path.display()))?;
+
+    let payload = Zeroizing::new(
+        decrypt_jwe(data.trim(), passphrase)
+            .wrap_err_with(|| format!("failed to decrypt keyring entry '{}'", path.display()))?,
+    );
+
+    let item: KeyringItem =
+        serde_json::from_slice(&payload).wrap_err("failed to parse keyring item")?;
+    let record_bytes = Zeroizing::new(
+        BASE64_STANDARD
+            .decode(item.data)
+            .wrap_err("failed to decode keyring item data")?,
+    );
+
+    decode_record(&record_bytes)
+        .wrap_err_with(|| format!("failed to load key '{key_name}' from keyring"))
+}
+
+/// Decrypts JWE compact serialization using PBES2-HS256+A128KW key management & A256GCM content encryption
+fn decrypt_jwe(token: &str, passphrase: &str) -> eyre::Result<Vec<u8>> {
+    let [header_b64, encrypted_key, iv, ciphertext, tag]: [&str; 5] = token
+        .split('.')
+        .collect::<Vec<_>>()
+        .try_into()
+        .map_err(|_| eyre!("expected JWE compact serialization with 5 parts"))?;
+
+    let decode = |part: &str, name: &str| {
+        BASE64_URL_SAFE_NO_PAD
+            .decode(part)
+            .wrap_err_with(|| format!("failed to decode JWE {name}"))
+    };
+
+    let header: JweHeader = serde_json::from_slice(&decode(header_b64, "header")?)
+        .wrap_err("failed to parse JWE header")?;
+    if header.alg != JWE_ALG || header.enc != JWE_ENC {
+        bail!(
+            "unsupported JWE algorithm '{}' with encryption '{}'",
+            header.alg,
+            header.enc
+        );
+    }
+    if let Some(zip) = header.zip {
+        bail!("unsupported JWE compression '{zip}'");
+    }
+
+    // RFC 7518 section 4.8.1.1: salt is algorithm name, zero byte and the salt input
+    let mut salt = JWE_ALG.as_bytes().to_vec();
+    salt.push(0);
+    salt.extend(decode(&header.p2s, "salt")?);
+
+    let kek: [u8; 16] =
+        pbkdf2::pbkdf2_hmac_array::<Sha256, 16>(passphrase.as_bytes(), &salt, header.p2c);
+
+    let cek = KekAes128::from(kek)
+        .unwrap_vec(&decode(encrypted_key, "encrypted key")?)
+        .map_err(|_| eyre!("invalid passphrase"))?;
+
+    let iv = decode(iv, "initialization vector")?;
+    if iv.len() != 12 {
+        bail!("unexpected JWE initialization vector length {}", iv.len());
+    }
+
+    let mut msg = decode(ciphertext, "ciphertext")?;
+    msg.extend(decode(tag, "authentication tag")?);
+
+    Aes256Gcm::new_from_slice(&cek)
+        .map_err(|_| eyre!("unexpected JWE content encryption key length"))?
+        .decrypt(
+            Nonce::from_slice(&iv),
+            Payload {
+                msg: &msg,
+                aad: header_b64.as_bytes(),
+            },
+        )
+        .map_err(|_| eyre!("failed to decrypt JWE payload"))
+}
+
+fn decode_record(bytes: &[u8]) -> eyre::Result<SigningKey> {
+    // Cosmos SDK < 0.46 stored amino encoded LocalInfo instead, which gets migrated by the SDK itself
+    // on first access. Running e.g. `osmosisd keys list` with a recent binary does that.
+    let record = Record::decode(bytes).wrap_err(
+        "failed to decode keyring record, legacy amino encoded keyrings are not supported",
+    )?;
+
+    let local = match record {
+        Record {
+            local: Some(local), ..
+        } => local,
+        Record {
+            ledger: Some(_), ..
+        } => bail!("ledger keys cannot be used"),
+        Record { multi: Some(_), .. } => bail!("multisig keys cannot be used"),
+        Record {
+            offline: Some(_), ..
+        } => bail!("offline keys do not contain private key"),
+        _ => bail!("keyring record does not contain a key"),
+    };
+
+    let priv_key = local
+        .priv_key
+        .wrap_err("keyring record does not contain private key")?;
+
+    if !SECP256K1_PRIV_KEY_TYPES.contains(&priv_key.type_url.as_str()) {
+        bail!("unsupported private key type '{}'", priv_key.type_url);
+    }
+
+    let priv_key =
+        PrivKey::decode(priv_key.value.as_slice()).wrap_err("failed to decode private key")?;
+    SigningKey::from_slice(&priv_key.key).wrap_err("invalid secp256k1 private key")
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::load_keyring_key;
+
+    /// Keyring entry for a key named `test`, encrypted with passphrase `testpassphrase`.
+    /// Private key bytes are 0x01..=0x20
+    const TEST_INFO: &str = "eyJhbGciOiJQQkVTMi1IUzI1NitBMTI4S1ciLCJjcmVhdGVkIjoiMjAyNS0wNi0wMVQxMjowMDowMFoiLCJlbmMiOiJBMjU2R0NNIiwicDJjIjo4MTkyLCJwMnMiOiJ1T2dEc2R1cFBPRDA5S1lESTZPSVdBIn0.uUwm-3xi7TUhpMRgWZFphyymvPuIWoAt_3Jx9qkHOKnKmNhl3bQrOw.09QhmjJJnR1ofynk.gfPYKQn6F04FXg2XFyJPquD73jVNPUbyB3guDMQXzzqzKcOiizvV_Tqh67oJIGt02WLu3Oyq9jdI3JY-9imD9JbhAfhelqJqu-CpJupqRbE498hWE3EqBuygHh2_0pGgqVqa4W-X148-ljXWRr6_hdQCdBOtxcfCB7WO38hRIZhRB-37ozPFTbn0TkWeGaqbBz8-m7R_ZISd3ukjzMZCPE8XobzcV2j3M4y-cRN0xd4kftr24chOhAkM_M6-wZwB6ta9G-CRPuL3nXPjJnFZ7KEpuWNtejEIKE0sEELQwg2WJqVxWymgXvl5e0egA1TdlwXLUi2o-DQsdSUAhbUPfVaQTpD2-YYIQ7n4iRWI1-ARfVRkPrdGocZHtgJR3zRd2XQlimg5BCmtjxUm1VNl069gEjJymnOEpSZ5HlS03CYj4FXW7ipwuaGLs_rpfnI.dRMoaiAVPmfmjvjWlz1B8Q";
+
+    #[test]
+    fn test_load_keyring_key() {
+        let dir = std::env::temp_dir().join(format!(
+            "cosmos-withdrawer-keyring-test-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("test.info"), TEST_INFO).unwrap();
+
+        let key = load_keyring_key(&dir, "test", "testpassphrase").unwrap();
+        assert_eq!(key.to_bytes().to_vec(), (1..=32).collect::<Vec<u8>>());
+
+        let err = load_keyring_key(&dir, "test", "wrong").unwrap_err();
+        assert_eq!(format!("{:#}", err.root_cause()), "invalid passphrase");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,29 +1,53 @@
-use std::str::FromStr;
+use std::{path::Path, str::FromStr};
 
 use bech32::Hrp;
 use bip32::{
-    DerivationPath, Mnemonic, PrivateKey, XPrv,
-    secp256k1::{ecdsa::SigningKey, elliptic_curve::rand_core::OsRng},
+    DerivationPath, Mnemonic, XPrv,
+    secp256k1::{
+        ecdsa::{SigningKey, VerifyingKey},
+        elliptic_curve::rand_core::OsRng,
+    },
 };
+use clap::ValueEnum;
 use cosmrs::{
     AccountId, Any, Tx,
     crypto::PublicKey,
-    tx::{Body, BodyBuilder, Fee, ModeInfo, SignDoc, SignMode, SignerInfo, SignerPublicKey},
+    tx::{
+        AuthInfo, Body, BodyBuilder, Fee, ModeInfo, SignDoc, SignMode, SignerInfo, SignerPublicKey,
+    },
 };
-use eyre::{Context, bail};
+use eyre::{Context, ContextCompat, bail};
 use prost::{Message, Name};
-use sha3::Digest;
+use serde::{Deserialize, Serialize};
 use tracing::debug;
 
+mod armor;
+mod eip712;
+mod keyring;
+mod multisig;
+mod signer;
+
+pub use self::armor::{decrypt_armored_key, parse_private_key_hex};
+pub use self::eip712::split_eip712_msgs;
+pub use self::keyring::load_keyring_key;
+pub use self::multisig::{
+    MemberSignature, combine_multisig_signatures, load_multisig_signer, sign_multisig_member,
+    sign_multisig_transaction,
+};
+pub use self::signer::{CommandSigner, HttpSigner, LocalSigner, Signer};
+
 use crate::{
     chain::{Bech32Prefixes, ChainInfo},
-    cmd::AccountArgs,
-    cosmos_sdk_extra::ethermint::EthPubKey,
-    cosmos_sdk_extra::injective::EthPubKey as InjectiveEthPubKey,
-    ser::CosmosJsonSerializable,
+    cmd::{AccountArgs, KeystoreArgs},
+    cosmos_sdk_extra::ethermint::{EthPubKey, ExtensionOptionsWeb3Tx},
+    cosmos_sdk_extra::injective::{
+        EthPubKey as InjectiveEthPubKey, ExtensionOptionsWeb3Tx as InjectiveExtensionOptionsWeb3Tx,
+    },
+    secret::{SecretSource, SecretString},
+    ser::{CosmosJsonSerializable, amino_sign_doc, amino_sign_doc_bytes, deserialize_from_str},
 };
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum WalletKeyType {
     /// Standard Cosmos SDK secp256k1 key
     #[default]
@@ -51,19 +75,18 @@ impl WalletKeyType {
             (Some(v @ Self::Secp256k1), _) => v,
             // If user prefers eth_secp256k1 over secp256k1, respect that
             (Some(v @ Self::EthermintSecp256k1 { .. }), Self::Secp256k1) => v,
-            // Remaining case
+            // Explicit Injective hint always wins
+            (Some(a @ Self::EthermintSecp256k1 { injective: true }), _) => a,
+            // If user specified plain eth_secp256k1, keep the Injective flavour detected on chain
             (
-                Some(a @ Self::EthermintSecp256k1 { injective: ask_inj }),
+                Some(a @ Self::EthermintSecp256k1 { injective: false }),
                 b @ Self::EthermintSecp256k1 { injective: is_inj },
             ) => {
-                if ask_inj {
-                    // This case is impossible, user cannot specify eth_secp256k1 with injective hint
-                    unreachable!();
+                if is_inj {
+                    b
+                } else {
+                    a
                 }
-
-                // If user specified eth_secp256k1, then that hint most likely does not have Injective field set,
-                // therefore convert key transparently.
-                if is_inj { b } else { a }
             }
         }
     }
@@ -76,11 +99,20 @@ impl FromStr for WalletKeyType {
         match s {
             "secp256k1" => Ok(Self::Secp256k1),
             "eth_secp256k1" => Ok(Self::EthermintSecp256k1 { injective: false }),
+            "eth_secp256k1_injective" => Ok(Self::EthermintSecp256k1 { injective: true }),
             s => bail!("Unsupported wallet key type '{s}'"),
         }
     }
 }
 
+deserialize_from_str!(WalletKeyType);
+
+impl Serialize for WalletKeyType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.type_name())
+    }
+}
+
 impl<'a> TryFrom<&'a Any> for WalletKeyType {
     type Error = eyre::ErrReport;
 
@@ -106,33 +138,102 @@ impl TryFrom<Any> for WalletKeyType {
     }
 }
 
-pub fn derive_key(mnemonic: &str, password: &str, coin_type: u64) -> eyre::Result<SigningKey> {
-    let derivation_path: DerivationPath = format!("m/44'/{coin_type}'/0'/0/0")
+/// Derives account address of given public key
+pub fn public_key_account_id(
+    public_key: &VerifyingKey,
+    key_type: WalletKeyType,
+    hrp: &Hrp,
+) -> eyre::Result<AccountId> {
+    match key_type {
+        WalletKeyType::Secp256k1 => Ok(PublicKey::from(*public_key).account_id(hrp.as_str())?),
+        WalletKeyType::EthermintSecp256k1 { .. } => {
+            // Need uncompressed public key to derive the address
+            let pubkey = public_key.to_encoded_point(false);
+
+            let pubkey_bytes: [u8; 65] = pubkey
+                .as_bytes()
+                .try_into()
+                .expect("secp256k1 uncompressed public key should be 65 bytes");
+
+            let mut hasher = sha3::Keccak256::default();
+            sha3::Digest::update(&mut hasher, &pubkey_bytes[1..]);
+            let hashed_bytes: [u8; 32] = sha3::Digest::finalize(hasher).into();
+
+            AccountId::new(hrp.as_str(), &hashed_bytes[12..32])
+        }
+    }
+}
+
+/// Returns the standard BIP44 derivation path for the first account & address of given coin type
+pub fn default_hd_path(coin_type: u64) -> eyre::Result<DerivationPath> {
+    format!("m/44'/{coin_type}'/0'/0/0")
         .parse()
-        .wrap_err("failed to parse derivation path")?;
+        .wrap_err("failed to parse derivation path")
+}
 
+pub fn derive_key(
+    mnemonic: &str,
+    password: &str,
+    derivation_path: &DerivationPath,
+) -> eyre::Result<SigningKey> {
     let mnemonic =
         Mnemonic::new(mnemonic, Default::default()).wrap_err("failed to parse mnemonic")?;
     let seed = mnemonic.to_seed(password);
 
     let signing_key =
-        XPrv::derive_from_path(seed, &derivation_path).wrap_err("failed to derive keypair")?;
+        XPrv::derive_from_path(seed, derivation_path).wrap_err("failed to derive keypair")?;
 
     Ok(signing_key.into())
 }
 
+/// Returns BIP39 passphrase given either directly or via file. Empty passphrase is used by default
+pub fn read_mnemonic_passphrase(
+    passphrase: Option<&str>,
+    passphrase_file: Option<&Path>,
+) -> eyre::Result<SecretString> {
+    let source = SecretSource {
+        value: passphrase,
+        file: passphrase_file,
+        ..Default::default()
+    };
+
+    Ok(source.read("mnemonic passphrase")?.unwrap_or_default())
+}
+
 pub struct TxSigner {
-    key: SigningKey,
+    signer: Box<dyn Signer>,
     key_type: WalletKeyType,
+    sign_mode: TxSignMode,
+    eip712_chain_id: Option<u64>,
     account_number: u64,
     sequence: u64,
 }
 
+/// Transaction signing mode
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TxSignMode {
+    /// `SIGN_MODE_DIRECT`, signing protobuf encoded transaction
+    #[default]
+    Direct,
+    /// `SIGN_MODE_LEGACY_AMINO_JSON`, required by some older chains and external signers
+    AminoJson,
+    /// Legacy EIP-712 typed data signature in `ExtensionOptionsWeb3Tx`, for eth_secp256k1 keys on
+    /// Ethermint based chains and Injective
+    Eip712,
+}
+
 impl TxSigner {
     pub fn new(key: SigningKey, key_type: WalletKeyType) -> Self {
+        Self::from_signer(Box::new(LocalSigner::new(key)), key_type)
+    }
+
+    pub fn from_signer(signer: Box<dyn Signer>, key_type: WalletKeyType) -> Self {
         Self {
-            key,
+            signer,
             key_type,
+            sign_mode: TxSignMode::Direct,
+            eip712_chain_id: None,
             account_number: 0,
             sequence: 0,
         }
@@ -143,28 +244,11 @@ impl TxSigner {
     }
 
     pub fn public_key(&self) -> PublicKey {
-        self.key.public_key().into()
+        self.signer.public_key().into()
     }
 
     pub fn account_id(&self, hrp: &Hrp) -> eyre::Result<AccountId> {
-        match self.key_type {
-            WalletKeyType::Secp256k1 => Ok(self.public_key().account_id(hrp.as_str())?),
-            WalletKeyType::EthermintSecp256k1 { .. } => {
-                // Need uncompressed public key to derive the address
-                let pubkey = self.key.public_key().to_encoded_point(false);
-
-                let pubkey_bytes: [u8; 65] = pubkey
-                    .as_bytes()
-                    .try_into()
-                    .expect("secp256k1 uncompressed public key should be 65 bytes");
-
-                let mut hasher = sha3::Keccak256::default();
-                sha3::Digest::update(&mut hasher, &pubkey_bytes[1..]);
-                let hashed_bytes: [u8; 32] = sha3::Digest::finalize(hasher).into();
-
-                AccountId::new(hrp.as_str(), &hashed_bytes[12..32])
-            }
-        }
+        public_key_account_id(&self.signer.public_key(), self.key_type, hrp)
     }
 
     pub fn signer_public_key(&self) -> SignerPublicKey {
@@ -191,11 +275,26 @@ impl TxSigner {
         }
     }
 
+    pub fn key_type(&self) -> WalletKeyType {
+        self.key_type
+    }
+
     pub fn with_numbers(mut self, account_number: u64, sequence: u64) -> Self {
         self.account_number = account_number;
         self.sequence = sequence;
         self
     }
+
+    pub fn with_sign_mode(mut self, sign_mode: TxSignMode) -> Self {
+        self.sign_mode = sign_mode;
+        self
+    }
+
+    /// Overrides EVM chain id used in EIP-712 domain, which is derived from chain id by default
+    pub fn with_eip712_chain_id(mut self, eip712_chain_id: Option<u64>) -> Self {
+        self.eip712_chain_id = eip712_chain_id;
+        self
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -221,7 +320,180 @@ impl SigningAccountType {
     }
 }
 
-pub fn setup_signer(
+/// Key material options of a single account role
+struct KeyArgs<'a> {
+    expected_address: &'a AccountId,
+    mnemonic: SecretSource<'a>,
+    mnemonic_passphrase: Option<&'a str>,
+    mnemonic_passphrase_file: Option<&'a Path>,
+    coin_type: u64,
+    hd_path: Option<&'a DerivationPath>,
+    keyring_dir: Option<&'a Path>,
+    keyring_key_name: Option<&'a str>,
+    keyring_passphrase: Option<&'a str>,
+    private_key: Option<&'a str>,
+    armored_key_file: Option<&'a Path>,
+    armored_key_passphrase: Option<&'a str>,
+    remote_signer_url: Option<&'a str>,
+    signer_command: Option<&'a str>,
+    key_name: Option<&'a str>,
+    keystore: &'a KeystoreArgs,
+}
+
+impl<'a> KeyArgs<'a> {
+    fn new(args: &'a AccountArgs, signing_account_type: &SigningAccountType) -> Self {
+        match signing_account_type {
+            SigningAccountType::Controller { .. } => Self::controller(args),
+            SigningAccountType::Delegator { .. } => Self::delegator(args),
+        }
+    }
+
+    fn controller(args: &'a AccountArgs) -> Self {
+        Self {
+            expected_address: &args.controller_address,
+            mnemonic: SecretSource {
+                value: args.controller_mnemonic.as_deref(),
+                file: args.controller_mnemonic_file.as_deref(),
+                fd: args.controller_mnemonic_fd,
+                prompt: args.controller_mnemonic_prompt,
+            },
+            mnemonic_passphrase: args.controller_mnemonic_passphrase.as_deref(),
+            mnemonic_passphrase_file: args.controller_mnemonic_passphrase_file.as_deref(),
+            coin_type: args.controller_mnemonic_coin_type,
+            hd_path: args.controller_hd_path.as_ref(),
+            keyring_dir: args.controller_keyring_dir.as_deref(),
+            keyring_key_name: args.controller_keyring_key_name.as_deref(),
+            keyring_passphrase: args.controller_keyring_passphrase.as_deref(),
+            private_key: args.controller_private_key.as_deref(),
+            armored_key_file: args.controller_armored_key_file.as_deref(),
+            armored_key_passphrase: args.controller_armored_key_passphrase.as_deref(),
+            remote_signer_url: args.controller_remote_signer_url.as_deref(),
+            signer_command: args.controller_signer_command.as_deref(),
+            key_name: args.controller_key.as_deref(),
+            keystore: &args.keystore,
+        }
+    }
+
+    fn delegator(args: &'a AccountArgs) -> Self {
+        Self {
+            expected_address: &args.delegator_address,
+            mnemonic: SecretSource {
+                value: args.delegator_mnemonic.as_deref(),
+                file: args.delegator_mnemonic_file.as_deref(),
+                fd: args.delegator_mnemonic_fd,
+                prompt: args.delegator_mnemonic_prompt,
+            },
+            mnemonic_passphrase: args.delegator_mnemonic_passphrase.as_deref(),
+            mnemonic_passphrase_file: args.delegator_mnemonic_passphrase_file.as_deref(),
+            coin_type: args.delegator_mnemonic_coin_type,
+            hd_path: args.delegator_hd_path.as_ref(),
+            keyring_dir: args.delegator_keyring_dir.as_deref(),
+            keyring_key_name: args.delegator_keyring_key_name.as_deref(),
+            keyring_passphrase: args.delegator_keyring_passphrase.as_deref(),
+            private_key: args.delegator_private_key.as_deref(),
+            armored_key_file: args.delegator_armored_key_file.as_deref(),
+            armored_key_passphrase: args.delegator_armored_key_passphrase.as_deref(),
+            remote_signer_url: args.delegator_remote_signer_url.as_deref(),
+            signer_command: args.delegator_signer_command.as_deref(),
+            key_name: args.delegator_key.as_deref(),
+            keystore: &args.keystore,
+        }
+    }
+
+    async fn load_signer(&self, account_type: &str) -> eyre::Result<Box<dyn Signer>> {
+        let sources = [
+            self.mnemonic.is_set(),
+            self.keyring_dir.is_some(),
+            self.private_key.is_some(),
+            self.armored_key_file.is_some(),
+            self.remote_signer_url.is_some(),
+            self.signer_command.is_some(),
+            self.key_name.is_some(),
+        ];
+
+        match sources.iter().filter(|set| **set).count() {
+            0 => bail!(
+                "no signing key available for {account_type}, set mnemonic, keyring, keystore key, private key, armored key or signer"
+            ),
+            1 => {}
+            _ => bail!(
+                "only one of mnemonic, keyring, keystore key, private key, armored key, remote signer and signer command can be set for {account_type}"
+            ),
+        }
+
+        if let Some(url) = self.remote_signer_url {
+            let signer = HttpSigner::connect(url.to_string())
+                .await
+                .wrap_err_with(|| format!("failed to set up {account_type} remote signer"))?;
+            return Ok(Box::new(signer));
+        }
+
+        if let Some(command) = self.signer_command {
+            let signer = CommandSigner::connect(command.to_string())
+                .await
+                .wrap_err_with(|| format!("failed to set up {account_type} signer command"))?;
+            return Ok(Box::new(signer));
+        }
+
+        Ok(Box::new(LocalSigner::new(
+            self.load_signing_key(account_type)?,
+        )))
+    }
+
+    fn load_signing_key(&self, account_type: &str) -> eyre::Result<SigningKey> {
+        if let Some(mnemonic) = self.mnemonic.read(&format!("{account_type} mnemonic"))? {
+            let passphrase =
+                read_mnemonic_passphrase(self.mnemonic_passphrase, self.mnemonic_passphrase_file)?;
+            let hd_path = match self.hd_path {
+                Some(hd_path) => hd_path.clone(),
+                None => default_hd_path(self.coin_type)?,
+            };
+
+            debug!(
+                account = account_type,
+                ?hd_path,
+                "deriving key from mnemonic"
+            );
+            return derive_key(&mnemonic, &passphrase, &hd_path);
+        }
+
+        if let Some(keyring_dir) = self.keyring_dir {
+            let key_name = self
+                .keyring_key_name
+                .wrap_err_with(|| format!("keyring key name not set for {account_type}"))?;
+            let passphrase = self
+                .keyring_passphrase
+                .wrap_err_with(|| format!("keyring passphrase not set for {account_type}"))?;
+
+            return load_keyring_key(keyring_dir, key_name, passphrase);
+        }
+
+        if let Some(key_name) = self.key_name {
+            let keystore = self.keystore.keystore()?;
+            debug!(account = account_type, key_name, path = ?keystore.path(), "loading key from keystore");
+            return keystore
+                .load_key(key_name, self.keystore.passphrase()?)
+                .wrap_err_with(|| format!("failed to load {account_type} key '{key_name}'"));
+        }
+
+        if let Some(private_key) = self.private_key {
+            return parse_private_key_hex(private_key)
+                .wrap_err_with(|| format!("failed to parse {account_type} private key"));
+        }
+
+        let path = self.armored_key_file.expect("key source was checked above");
+        let passphrase = self
+            .armored_key_passphrase
+            .wrap_err_with(|| format!("armored key passphrase not set for {account_type}"))?;
+        let armored = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("failed to read armored key file '{}'", path.display()))?;
+
+        decrypt_armored_key(&armored, passphrase)
+            .wrap_err_with(|| format!("failed to decrypt armored key '{}'", path.display()))
+    }
+}
+
+pub async fn setup_signer(
     account_args: &AccountArgs,
     bech32_prefixes: &Bech32Prefixes,
     signing_account_type: SigningAccountType,
@@ -244,52 +516,43 @@ pub fn setup_signer(
         return Ok(TxSigner::random(key_type).with_numbers(account_number, sequence));
     }
 
-    let (expected_address, mnemonic, password, coin_type) = match signing_account_type {
-        SigningAccountType::Controller { .. } => (
-            &account_args.controller_address,
-            account_args.controller_mnemonic.as_ref(),
-            "",
-            account_args.controller_mnemonic_coin_type,
-        ),
-        SigningAccountType::Delegator { .. } => (
-            &account_args.delegator_address,
-            account_args.delegator_mnemonic.as_ref(),
-            "",
-            account_args.delegator_mnemonic_coin_type,
-        ),
-    };
-
-    let Some(mnemonic) = mnemonic else {
-        bail!(
-            "mnemonic not available for {}",
-            signing_account_type.type_name()
-        );
-    };
-
-    let signing_key = derive_key(mnemonic, password, coin_type)?;
-    let signer = TxSigner::new(signing_key, key_type).with_numbers(account_number, sequence);
+    let key_args = KeyArgs::new(account_args, &signing_account_type);
+    let signer = key_args
+        .load_signer(signing_account_type.type_name())
+        .await?;
+    let signer = TxSigner::from_signer(signer, key_type).with_numbers(account_number, sequence);
 
     let address = signer
         .account_id(&bech32_prefixes.account_prefix)
-        .wrap_err("failed to derive address from signing key")?;
+        .wrap_err("failed to derive address from signer public key")?;
 
     debug!(
         ?address,
         account = signing_account_type.type_name(),
         key_type = key_type.type_name(),
-        coin_type,
-        "derived signer address"
+        "resolved signer address"
     );
-    if *expected_address != address {
+    if *key_args.expected_address != address {
         bail!(
-            "expected {} address '{expected_address}', got '{address}'",
-            signing_account_type.type_name()
+            "expected {} address '{}', got '{address}'",
+            signing_account_type.type_name(),
+            key_args.expected_address,
         );
     }
 
     Ok(signer)
 }
 
+/// Loads key of a delegator multisig member from the delegator key options. Its address is not
+/// checked, as membership is verified against the multisig public key once signatures are combined
+pub async fn setup_multisig_member_signer(
+    account_args: &AccountArgs,
+) -> eyre::Result<Box<dyn Signer>> {
+    KeyArgs::delegator(account_args)
+        .load_signer("multisig member")
+        .await
+}
+
 pub fn construct_transaction_body<'a, I: IntoIterator<Item = &'a CosmosJsonSerializable>>(
     memo: &str,
     msgs: I,
@@ -304,69 +567,220 @@ pub fn construct_transaction_body<'a, I: IntoIterator<Item = &'a CosmosJsonSeria
         .finish())
 }
 
-pub fn sign_transaction(
+pub async fn sign_transaction(
     chain_info: &ChainInfo,
     signer: &TxSigner,
     fee: Fee,
     body: Body,
 ) -> eyre::Result<Tx> {
+    prepare_transaction(chain_info, signer, fee, body)?
+        .sign(signer)
+        .await
+}
+
+/// Transaction with everything but the signature, along with the bytes to be signed
+pub struct UnsignedTx {
+    body: Body,
+    auth_info: AuthInfo,
+    sign_bytes: Vec<u8>,
+    /// Set in EIP-712 sign mode, where the signature travels in `ExtensionOptionsWeb3Tx`
+    web3_extension: Option<Web3Extension>,
+}
+
+struct Web3Extension {
+    typed_data_chain_id: u64,
+    fee_payer: String,
+    injective: bool,
+}
+
+pub fn prepare_transaction(
+    chain_info: &ChainInfo,
+    signer: &TxSigner,
+    fee: Fee,
+    body: Body,
+) -> eyre::Result<UnsignedTx> {
+    let sign_mode = match signer.sign_mode {
+        TxSignMode::Direct => SignMode::Direct,
+        TxSignMode::AminoJson | TxSignMode::Eip712 => SignMode::LegacyAminoJson,
+    };
     let signer_info = SignerInfo {
         public_key: Some(signer.signer_public_key()),
-        mode_info: ModeInfo::single(SignMode::Direct),
+        mode_info: ModeInfo::single(sign_mode),
         sequence: signer.sequence,
     };
 
     let auth_info = signer_info.auth_info(fee);
-    let sign_doc = SignDoc::new(&body, &auth_info, &chain_info.id, signer.account_number)
-        .wrap_err("failed to create SignDoc")?;
-
-    let sign_doc_bytes = sign_doc
-        .into_bytes()
-        .wrap_err("failed to serialize SignDoc into bytes")?;
-
-    let signature: Vec<u8> = match signer.key_type {
-        WalletKeyType::Secp256k1 => signer
-            .key
-            .sign_recoverable(&sign_doc_bytes)
-            .wrap_err("failed to sign SignDoc")?
-            .0
-            .to_vec(),
-        WalletKeyType::EthermintSecp256k1 { .. } => {
-            let hash = sha3::Keccak256::digest(&sign_doc_bytes);
-            let (signature, recovery_id) = signer
-                .key
-                .sign_prehash_recoverable(&hash)
-                .wrap_err("failed to sign SignDoc")?;
-
-            let mut signature_bytes = signature.to_vec();
-            signature_bytes.push(recovery_id.to_byte());
-            signature_bytes
+    let mut web3_extension = None;
+    let sign_bytes = match signer.sign_mode {
+        TxSignMode::Direct => {
+            let sign_doc = SignDoc::new(&body, &auth_info, &chain_info.id, signer.account_number)
+                .wrap_err("failed to create SignDoc")?;
+
+            sign_doc
+                .into_bytes()
+                .wrap_err("failed to serialize SignDoc into bytes")?
+        }
+        TxSignMode::AminoJson => {
+            let msgs = body
+                .messages
+                .iter()
+                .map(CosmosJsonSerializable::try_from)
+                .collect::<eyre::Result<Vec<_>>>()?;
+
+            amino_sign_doc_bytes(
+                chain_info.id.as_str(),
+                signer.account_number,
+                signer.sequence,
+                &auth_info.fee,
+                &body.memo,
+                &msgs,
+            )
+            .wrap_err("failed to create amino JSON sign doc")?
+        }
+        TxSignMode::Eip712 => {
+            let WalletKeyType::EthermintSecp256k1 { injective } = signer.key_type else {
+                bail!("EIP-712 sign mode requires an eth_secp256k1 key");
+            };
+
+            let msgs = body
+                .messages
+                .iter()
+                .map(CosmosJsonSerializable::try_from)
+                .collect::<eyre::Result<Vec<_>>>()?;
+            let sign_doc = amino_sign_doc(
+                chain_info.id.as_str(),
+                signer.account_number,
+                signer.sequence,
+                &auth_info.fee,
+                &body.memo,
+                &msgs,
+            )
+            .wrap_err("failed to create amino JSON sign doc")?;
+
+            let typed_data_chain_id = match signer.eip712_chain_id {
+                Some(eip712_chain_id) => eip712_chain_id,
+                None => eip712::default_eip712_chain_id(chain_info.id.as_str(), injective)?,
+            };
+            let fee_payer = signer
+                .account_id(&chain_info.bech32.account_prefix)?
+                .to_string();
+            let typed_data =
+                eip712::legacy_typed_data(sign_doc, typed_data_chain_id, &fee_payer, injective)
+                    .wrap_err("failed to create EIP-712 typed data")?;
+            debug!(typed_data = %serde_json::to_string(&typed_data)?, "signing EIP-712 typed data");
+
+            web3_extension = Some(Web3Extension {
+                typed_data_chain_id,
+                fee_payer,
+                injective,
+            });
+            typed_data.sign_bytes()?
         }
     };
 
-    Ok(Tx {
+    Ok(UnsignedTx {
         body,
         auth_info,
-        signatures: vec![signature.to_vec()],
+        sign_bytes,
+        web3_extension,
     })
 }
 
+impl UnsignedTx {
+    pub fn sign_bytes(&self) -> &[u8] {
+        &self.sign_bytes
+    }
+
+    pub async fn sign(self, signer: &TxSigner) -> eyre::Result<Tx> {
+        let Self {
+            mut body,
+            auth_info,
+            sign_bytes,
+            web3_extension,
+        } = self;
+
+        let signature = signer.signer.sign(&sign_bytes, signer.key_type).await?;
+
+        let Some(Web3Extension {
+            typed_data_chain_id,
+            fee_payer,
+            injective,
+        }) = web3_extension
+        else {
+            return Ok(Tx {
+                body,
+                auth_info,
+                signatures: vec![signature],
+            });
+        };
+
+        // Ethermint requires the transaction signature to be empty
+        body.extension_options.push(if injective {
+            Any {
+                type_url: InjectiveExtensionOptionsWeb3Tx::type_url(),
+                value: InjectiveExtensionOptionsWeb3Tx {
+                    typed_data_chain_id,
+                    fee_payer,
+                    fee_payer_sig: signature,
+                }
+                .encode_to_vec(),
+            }
+        } else {
+            Any {
+                type_url: ExtensionOptionsWeb3Tx::type_url(),
+                value: ExtensionOptionsWeb3Tx {
+                    typed_data_chain_id,
+                    fee_payer,
+                    fee_payer_sig: signature,
+                }
+                .encode_to_vec(),
+            }
+        });
+
+        Ok(Tx {
+            body,
+            auth_info,
+            signatures: vec![Vec::new()],
+        })
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::str::FromStr;
 
     use bech32::Hrp;
-    use cosmrs::AccountId;
+    use bip32::secp256k1::ecdsa::{RecoveryId, Signature, VerifyingKey, signature::Verifier};
+    use cosmrs::{
+        AccountId, Any, Coin,
+        proto::cosmos::distribution::v1beta1::MsgWithdrawDelegatorReward,
+        tx::{Fee, ModeInfo, SignMode},
+    };
     use pretty_assertions::assert_eq;
+    use prost::Message;
+    use serde_json::json;
+    use sha2::Sha256;
+    use sha3::{Digest, Keccak256};
 
-    use super::{TxSigner, WalletKeyType, derive_key};
+    use super::{
+        TxSignMode, TxSigner, WalletKeyType, construct_transaction_body, default_hd_path,
+        derive_key, eip712::legacy_typed_data, read_mnemonic_passphrase, sign_transaction,
+    };
+    use crate::{
+        chain::{Bech32Prefixes, ChainInfo},
+        cosmos_sdk_extra::{
+            ethermint::ExtensionOptionsWeb3Tx,
+            injective::ExtensionOptionsWeb3Tx as InjectiveExtensionOptionsWeb3Tx,
+        },
+        ser::{CosmosJsonSerializable, MsgExecCustom, amino_sign_doc, amino_sign_doc_bytes},
+    };
 
     #[test]
     fn test_eth_secp256k1_address() {
         let expected = AccountId::from_str("inj19lhpj24vqtglud7kd7e4n3zj8z4lxkl7ex3uv0").unwrap();
 
         // Don't worry, it's not a real wallet
-        let key = derive_key("relief raise grow sketch turtle endless lens replace morning symptom short coin cousin hospital sauce foam stumble wife kind tortoise member heavy web render", "", 60).unwrap();
+        let key = derive_key("relief raise grow sketch turtle endless lens replace morning symptom short coin cousin hospital sauce foam stumble wife kind tortoise member heavy web render", "", &default_hd_path(60).unwrap()).unwrap();
         let signer = TxSigner::new(key, WalletKeyType::EthermintSecp256k1 { injective: true });
 
         assert_eq!(
@@ -374,4 +788,319 @@ mod test {
             signer.account_id(&Hrp::parse_unchecked("inj")).unwrap(),
         );
     }
+
+    #[test]
+    fn test_wallet_key_type() {
+        let injective = WalletKeyType::EthermintSecp256k1 { injective: true };
+        let ethermint = WalletKeyType::EthermintSecp256k1 { injective: false };
+        assert_eq!(
+            WalletKeyType::from_str("eth_secp256k1_injective").unwrap(),
+            injective
+        );
+        assert_eq!(WalletKeyType::from_str("eth_secp256k1").unwrap(), ethermint);
+        assert!(WalletKeyType::from_str("ed25519").is_err());
+
+        // Injective flavour detected on chain is kept when plain eth_secp256k1 is asked for
+        assert_eq!(injective.override_type(Some(ethermint)), injective);
+        assert_eq!(ethermint.override_type(Some(injective)), injective);
+        assert_eq!(
+            WalletKeyType::Secp256k1.override_type(Some(injective)),
+            injective
+        );
+        assert_eq!(
+            injective.override_type(Some(WalletKeyType::Secp256k1)),
+            WalletKeyType::Secp256k1
+        );
+        assert_eq!(injective.override_type(None), injective);
+    }
+
+    #[test]
+    fn test_mnemonic_passphrase() {
+        let path = std::env::temp_dir().join(format!(
+            "cosmos-withdrawer-passphrase-test-{}",
+            std::process::id()
+        ));
+        std::fs::write(&path, "TREZOR \n").unwrap();
+        let passphrase = read_mnemonic_passphrase(None, Some(&path)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(&*passphrase, "TREZOR ");
+
+        // Don't worry, it's not a real wallet
+        let mnemonic = "relief raise grow sketch turtle endless lens replace morning symptom short coin cousin hospital sauce foam stumble wife kind tortoise member heavy web render";
+        let hrp = Hrp::parse_unchecked("cosmos");
+        let address = |passphrase| {
+            TxSigner::new(
+                derive_key(mnemonic, passphrase, &default_hd_path(118).unwrap()).unwrap(),
+                WalletKeyType::Secp256k1,
+            )
+            .account_id(&hrp)
+            .unwrap()
+        };
+
+        assert_ne!(address(""), address("TREZOR"));
+        assert_eq!(
+            address(""),
+            address(&read_mnemonic_passphrase(None, None).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_hd_path() {
+        let mnemonic = "relief raise grow sketch turtle endless lens replace morning symptom short coin cousin hospital sauce foam stumble wife kind tortoise member heavy web render";
+        let hrp = Hrp::parse_unchecked("inj");
+        let address = |hd_path: &str| {
+            TxSigner::new(
+                derive_key(mnemonic, "", &hd_path.parse().unwrap()).unwrap(),
+                WalletKeyType::EthermintSecp256k1 { injective: true },
+            )
+            .account_id(&hrp)
+            .unwrap()
+        };
+
+        assert_eq!(
+            address("m/44'/60'/0'/0/0"),
+            AccountId::from_str("inj19lhpj24vqtglud7kd7e4n3zj8z4lxkl7ex3uv0").unwrap()
+        );
+        assert_ne!(address("m/44'/60'/0'/0/0"), address("m/44'/60'/0'/0/1"));
+        assert_ne!(address("m/44'/60'/0'/0/0"), address("m/44'/60'/1'/0/0"));
+    }
+
+    #[tokio::test]
+    async fn test_sign_transaction_amino_json() {
+        let mnemonic = "relief raise grow sketch turtle endless lens replace morning symptom short coin cousin hospital sauce foam stumble wife kind tortoise member heavy web render";
+        let key = derive_key(mnemonic, "", &default_hd_path(118).unwrap()).unwrap();
+        let verifying_key = *key.verifying_key();
+
+        let chain_info = ChainInfo {
+            id: "osmosis-1".parse().unwrap(),
+            chain_supports_setting_withdrawal_address: true,
+            bech32: Bech32Prefixes {
+                account_prefix: Hrp::parse_unchecked("osmo"),
+                valoper_prefix: Hrp::parse_unchecked("osmovaloper"),
+            },
+        };
+        // Controller and delegator derived from the mnemonic with m/44'/118'/0'/0/{0,1}, the
+        // transaction is signed by the controller
+        let msgs: Vec<CosmosJsonSerializable> = vec![
+            MsgExecCustom {
+                grantee: "osmo1rsaj0sj0sqpy48xajzaq328ednss65ljxs0tmp".to_string(),
+                msgs: vec![
+                    MsgWithdrawDelegatorReward {
+                        delegator_address: "osmo1mq3als39tqq8ccv9uuyt2k8xvvmqaedss6v3a6"
+                            .to_string(),
+                        validator_address: "osmovaloper1mq3als39tqq8ccv9uuyt2k8xvvmqaeds2dyj2a"
+                            .to_string(),
+                    }
+                    .into(),
+                ],
+            }
+            .into(),
+        ];
+        let fee = Fee::from_amount_and_gas(
+            Coin {
+                denom: "uosmo".parse().unwrap(),
+                amount: 5000,
+            },
+            200_000_u64,
+        );
+
+        let signer = TxSigner::new(key, WalletKeyType::Secp256k1)
+            .with_numbers(42, 7)
+            .with_sign_mode(TxSignMode::AminoJson);
+        let tx = sign_transaction(
+            &chain_info,
+            &signer,
+            fee.clone(),
+            construct_transaction_body("cosmos-withdrawer", &msgs).unwrap(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            tx.auth_info.signer_infos[0].mode_info,
+            ModeInfo::single(SignMode::LegacyAminoJson)
+        );
+
+        // Sign doc hash and signature were checked with a separate implementation, which rebuilds
+        // the sign doc by the Cosmos SDK legacy amino JSON rules and verifies the signature with
+        // Node.js crypto. Signatures are deterministic (RFC6979)
+        let sign_bytes =
+            amino_sign_doc_bytes("osmosis-1", 42, 7, &fee, "cosmos-withdrawer", &msgs).unwrap();
+        assert_eq!(
+            hex::encode(Sha256::digest(&sign_bytes)),
+            "5e72d49a158f79998caf1d6b47ded3b5a270094de38f3cac91d044215286efd9"
+        );
+        let signature = Signature::from_slice(&tx.signatures[0]).unwrap();
+        verifying_key.verify(&sign_bytes, &signature).unwrap();
+        assert_eq!(
+            hex::encode(&tx.signatures[0]),
+            "8bb92b1ee4fd9e33f32de05e97fed4a38fab7abe8fc9d074acb4388bde8495e44c5876f3980dfd5897a4c8b82242591af29bc2d306effc2355fb3f627805f0c2"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sign_transaction_eip712() {
+        let mnemonic = "relief raise grow sketch turtle endless lens replace morning symptom short coin cousin hospital sauce foam stumble wife kind tortoise member heavy web render";
+        let key = derive_key(mnemonic, "", &default_hd_path(60).unwrap()).unwrap();
+        let verifying_key = *key.verifying_key();
+
+        // Ethermint, and Injective with its own public key & extension types, domain name and
+        // timeout height in the typed data
+        for (injective, chain_id, denom, delegator, validator) in [
+            (
+                false,
+                "evmos_9001-2",
+                "aevmos",
+                "evmos16nf0mht68937d27cwrqqdtwv9y2alm06nevstf",
+                "evmosvaloper16nf0mht68937d27cwrqqdtwv9y2alm067hrq25",
+            ),
+            (
+                true,
+                "injective-1",
+                "inj",
+                "inj16nf0mht68937d27cwrqqdtwv9y2alm06m326re",
+                "injvaloper16nf0mht68937d27cwrqqdtwv9y2alm06vhllza",
+            ),
+        ] {
+            let account_prefix = if injective { "inj" } else { "evmos" };
+            let chain_info = ChainInfo {
+                id: chain_id.parse().unwrap(),
+                chain_supports_setting_withdrawal_address: true,
+                bech32: Bech32Prefixes::parse(account_prefix, None).unwrap(),
+            };
+            let signer =
+                TxSigner::new(key.clone(), WalletKeyType::EthermintSecp256k1 { injective })
+                    .with_numbers(42, 7)
+                    .with_sign_mode(TxSignMode::Eip712);
+            let fee_payer = signer
+                .account_id(&Hrp::parse_unchecked(account_prefix))
+                .unwrap()
+                .to_string();
+
+            let msgs: Vec<CosmosJsonSerializable> = vec![
+                MsgExecCustom {
+                    grantee: fee_payer.clone(),
+                    msgs: vec![
+                        MsgWithdrawDelegatorReward {
+                            delegator_address: delegator.to_string(),
+                            validator_address: validator.to_string(),
+                        }
+                        .into(),
+                    ],
+                }
+                .into(),
+            ];
+            let fee = Fee::from_amount_and_gas(
+                Coin {
+                    denom: denom.parse().unwrap(),
+                    amount: 4_000_000_000_000_000,
+                },
+                200_000_u64,
+            );
+            let body = construct_transaction_body("cosmos-withdrawer", &msgs).unwrap();
+            let tx = sign_transaction(&chain_info, &signer, fee.clone(), body)
+                .await
+                .unwrap();
+
+            let signer_info = &tx.auth_info.signer_infos[0];
+            assert_eq!(
+                signer_info.mode_info,
+                ModeInfo::single(SignMode::LegacyAminoJson)
+            );
+            assert_eq!(
+                Any::from(signer_info.public_key.clone().unwrap()).type_url,
+                if injective {
+                    "/injective.crypto.v1beta1.ethsecp256k1.PubKey"
+                } else {
+                    "/ethermint.crypto.v1.ethsecp256k1.PubKey"
+                }
+            );
+            assert_eq!(tx.body.timeout_height, 0u32.into());
+            assert_eq!(tx.signatures, vec![Vec::<u8>::new()]);
+            assert_eq!(tx.body.extension_options.len(), 1);
+
+            let extension = &tx.body.extension_options[0];
+            let (typed_data_chain_id, extension_fee_payer, fee_payer_sig) = if injective {
+                assert_eq!(
+                    extension.type_url,
+                    "/injective.types.v1beta1.ExtensionOptionsWeb3Tx"
+                );
+                let extension =
+                    InjectiveExtensionOptionsWeb3Tx::decode(extension.value.as_slice()).unwrap();
+                (
+                    extension.typed_data_chain_id,
+                    extension.fee_payer,
+                    extension.fee_payer_sig,
+                )
+            } else {
+                assert_eq!(
+                    extension.type_url,
+                    "/ethermint.types.v1.ExtensionOptionsWeb3Tx"
+                );
+                let extension = ExtensionOptionsWeb3Tx::decode(extension.value.as_slice()).unwrap();
+                (
+                    extension.typed_data_chain_id,
+                    extension.fee_payer,
+                    extension.fee_payer_sig,
+                )
+            };
+            assert_eq!(typed_data_chain_id, if injective { 1 } else { 9001 });
+            assert_eq!(extension_fee_payer, fee_payer);
+
+            let sign_doc =
+                amino_sign_doc(chain_id, 42, 7, &fee, "cosmos-withdrawer", &msgs).unwrap();
+            let typed_data =
+                legacy_typed_data(sign_doc, typed_data_chain_id, &fee_payer, injective).unwrap();
+            assert_eq!(
+                typed_data.domain["name"],
+                if injective {
+                    "Injective Web3"
+                } else {
+                    "Cosmos Web3"
+                }
+            );
+            assert_eq!(
+                typed_data.message.get("timeout_height"),
+                injective.then(|| json!("0")).as_ref()
+            );
+            let hash = Keccak256::digest(typed_data.sign_bytes().unwrap());
+            let signature = Signature::from_slice(&fee_payer_sig[..64]).unwrap();
+            let recovery_id = RecoveryId::from_byte(fee_payer_sig[64]).unwrap();
+            assert_eq!(
+                VerifyingKey::recover_from_prehash(&hash, &signature, recovery_id).unwrap(),
+                verifying_key
+            );
+        }
+
+        let chain_info = ChainInfo {
+            id: "evmos_9001-2".parse().unwrap(),
+            chain_supports_setting_withdrawal_address: true,
+            bech32: Bech32Prefixes::parse("evmos", None).unwrap(),
+        };
+        let fee = Fee::from_amount_and_gas(
+            Coin {
+                denom: "aevmos".parse().unwrap(),
+                amount: 4_000_000_000_000_000,
+            },
+            200_000_u64,
+        );
+        let body = construct_transaction_body(
+            "cosmos-withdrawer",
+            &[MsgWithdrawDelegatorReward {
+                delegator_address: "evmos16nf0mht68937d27cwrqqdtwv9y2alm06nevstf".to_string(),
+                validator_address: "evmosvaloper16nf0mht68937d27cwrqqdtwv9y2alm067hrq25"
+                    .to_string(),
+            }
+            .into()],
+        )
+        .unwrap();
+        let signer = TxSigner::random(WalletKeyType::Secp256k1).with_sign_mode(TxSignMode::Eip712);
+        let err = sign_transaction(&chain_info, &signer, fee, body)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "EIP-712 sign mode requires an eth_secp256k1 key"
+        );
+    }
 }
@@ -0,0 +1,418 @@
+use std::path::PathBuf;
+
+use bip32::secp256k1::ecdsa::{Signature, SigningKey, VerifyingKey, signature::Verifier};
+use cosmrs::{
+    Tx,
+    crypto::{CompactBitArray, LegacyAminoMultisig, PublicKey},
+    proto::cosmos::crypto::multisig::v1beta1::MultiSignature,
+    tx::{Body, Fee, ModeInfo, SignMode, SignerInfo, SignerPublicKey, mode_info::Multi},
+};
+use eyre::{Context, bail};
+use prost::Message;
+use tracing::debug;
+
+use super::{
+    LocalSigner, Signer, WalletKeyType, default_hd_path, derive_key, read_mnemonic_passphrase,
+};
+use crate::{
+    chain::ChainInfo,
+    cmd::AccountArgs,
+    secret::SecretSource,
+    ser::{CosmosJsonSerializable, amino_sign_doc_bytes},
+};
+
+/// Signs on behalf of a `LegacyAminoPubKey` multisig account, using keys of its members.
+///
+/// Members sign in `SIGN_MODE_LEGACY_AMINO_JSON`, as `SIGN_MODE_DIRECT` sign bytes would have to
+/// include the set of signing members.
+pub struct MultisigSigner {
+    multisig: LegacyAminoMultisig,
+    /// Member index in the multisig public key & its signer, ordered by index
+    members: Vec<(usize, LocalSigner)>,
+    account_number: u64,
+    sequence: u64,
+}
+
+impl MultisigSigner {
+    pub fn new(
+        multisig: LegacyAminoMultisig,
+        member_keys: Vec<SigningKey>,
+        account_number: u64,
+        sequence: u64,
+    ) -> eyre::Result<Self> {
+        let mut members = Vec::with_capacity(member_keys.len());
+        for key in member_keys {
+            let public_key = PublicKey::from(key.verifying_key());
+            let Some(index) = multisig
+                .public_keys
+                .iter()
+                .position(|member| member == &public_key)
+            else {
+                bail!(
+                    "key with public key {} is not a member of the multisig",
+                    public_key.to_json()
+                );
+            };
+
+            if members.iter().any(|(i, _)| *i == index) {
+                bail!("multisig member #{index} key was given more than once");
+            }
+            members.push((index, LocalSigner::new(key)));
+        }
+
+        if members.len() < multisig.threshold as usize {
+            bail!(
+                "{}-of-{} multisig requires {} member keys, got {}",
+                multisig.threshold,
+                multisig.public_keys.len(),
+                multisig.threshold,
+                members.len()
+            );
+        }
+
+        members.sort_by_key(|(index, _)| *index);
+
+        Ok(Self {
+            multisig,
+            members,
+            account_number,
+            sequence,
+        })
+    }
+
+    fn signer_info(&self) -> SignerInfo {
+        multisig_signer_info(
+            &self.multisig,
+            self.members.iter().map(|(index, _)| *index),
+            self.sequence,
+        )
+    }
+}
+
+/// Signer info of the multisig account, with given members having signed. Indices have to be
+/// ascending, in the order of the signatures.
+fn multisig_signer_info(
+    multisig: &LegacyAminoMultisig,
+    indices: impl IntoIterator<Item = usize>,
+    sequence: u64,
+) -> SignerInfo {
+    let size = multisig.public_keys.len();
+    let mut elems = vec![0u8; size.div_ceil(8)];
+    let mut signed = 0;
+    for index in indices {
+        elems[index / 8] |= 1 << (7 - index % 8);
+        signed += 1;
+    }
+
+    SignerInfo {
+        public_key: Some(SignerPublicKey::LegacyAminoMultisig(multisig.clone())),
+        mode_info: ModeInfo::Multi(Multi {
+            bitarray: CompactBitArray::new((size % 8) as u32, elems),
+            mode_infos: vec![ModeInfo::single(SignMode::LegacyAminoJson); signed],
+        }),
+        sequence,
+    }
+}
+
+/// Signature of a single multisig member over the amino JSON sign bytes of the multisig
+/// transaction, to be combined with signatures of other members
+#[derive(Clone, Debug)]
+pub struct MemberSignature {
+    pub public_key: PublicKey,
+    pub signature: Vec<u8>,
+}
+
+/// Signs amino JSON sign bytes of the multisig account transaction with a member key
+pub async fn sign_multisig_member(
+    member: &dyn Signer,
+    sign_bytes: &[u8],
+) -> eyre::Result<MemberSignature> {
+    Ok(MemberSignature {
+        public_key: member.public_key().into(),
+        signature: member.sign(sign_bytes, WalletKeyType::Secp256k1).await?,
+    })
+}
+
+/// Combines signatures of multisig members into a multisig transaction. Every signature has to be
+/// made by a distinct member over given sign bytes, and there have to be at least threshold of them.
+pub fn combine_multisig_signatures(
+    multisig: &LegacyAminoMultisig,
+    member_signatures: Vec<MemberSignature>,
+    sign_bytes: &[u8],
+    sequence: u64,
+    fee: Fee,
+    body: Body,
+) -> eyre::Result<Tx> {
+    let mut signatures: Vec<(usize, Vec<u8>)> = Vec::with_capacity(member_signatures.len());
+    for MemberSignature {
+        public_key,
+        signature,
+    } in member_signatures
+    {
+        let Some(index) = multisig
+            .public_keys
+            .iter()
+            .position(|member| member == &public_key)
+        else {
+            bail!(
+                "signature of public key {} is not made by a member of the multisig",
+                public_key.to_json()
+            );
+        };
+        if signatures.iter().any(|(i, _)| *i == index) {
+            bail!("multisig member #{index} signature was given more than once");
+        }
+
+        let verifying_key = VerifyingKey::from_sec1_bytes(&public_key.to_bytes())
+            .wrap_err("invalid member public key")?;
+        let member_signature =
+            Signature::from_slice(&signature).wrap_err("invalid member signature")?;
+        verifying_key
+            .verify(sign_bytes, &member_signature)
+            .wrap_err_with(|| {
+                format!("signature of multisig member #{index} does not match the transaction")
+            })?;
+
+        signatures.push((index, signature));
+    }
+
+    if signatures.len() < multisig.threshold as usize {
+        bail!(
+            "{}-of-{} multisig requires {} member signatures, got {}",
+            multisig.threshold,
+            multisig.public_keys.len(),
+            multisig.threshold,
+            signatures.len()
+        );
+    }
+
+    signatures.sort_by_key(|(index, _)| *index);
+    let auth_info = multisig_signer_info(
+        multisig,
+        signatures.iter().map(|(index, _)| *index),
+        sequence,
+    )
+    .auth_info(fee);
+
+    Ok(Tx {
+        body,
+        auth_info,
+        signatures: vec![
+            MultiSignature {
+                signatures: signatures
+                    .into_iter()
+                    .map(|(_, signature)| signature)
+                    .collect(),
+            }
+            .encode_to_vec(),
+        ],
+    })
+}
+
+/// Derives multisig member keys from mnemonics stored in given files, one mnemonic per file.
+/// Delegator passphrase & derivation path options apply to all members.
+pub fn load_multisig_signer(
+    account_args: &AccountArgs,
+    multisig: LegacyAminoMultisig,
+    mnemonic_files: &[PathBuf],
+    account_number: u64,
+    sequence: u64,
+) -> eyre::Result<MultisigSigner> {
+    if mnemonic_files.is_empty() {
+        bail!(
+            "delegator is a {}-of-{} multisig account, either use --generate-only or pass member mnemonics with --multisig-member-mnemonic-file",
+            multisig.threshold,
+            multisig.public_keys.len()
+        );
+    }
+
+    let passphrase = read_mnemonic_passphrase(
+        account_args.delegator_mnemonic_passphrase.as_deref(),
+        account_args.delegator_mnemonic_passphrase_file.as_deref(),
+    )?;
+    let hd_path = match &account_args.delegator_hd_path {
+        Some(hd_path) => hd_path.clone(),
+        None => default_hd_path(account_args.delegator_mnemonic_coin_type)?,
+    };
+
+    let member_keys = mnemonic_files
+        .iter()
+        .map(|path| {
+            let mnemonic = SecretSource {
+                file: Some(path),
+                ..Default::default()
+            }
+            .read("member mnemonic")?
+            .unwrap_or_default();
+
+            derive_key(mnemonic.trim(), &passphrase, &hd_path)
+                .wrap_err_with(|| format!("failed to derive member key from '{}'", path.display()))
+        })
+        .collect::<eyre::Result<Vec<_>>>()?;
+
+    MultisigSigner::new(multisig, member_keys, account_number, sequence)
+}
+
+/// Signs transaction with all available member keys, and combines the signatures into a single
+/// multisig signature
+pub async fn sign_multisig_transaction(
+    chain_info: &ChainInfo,
+    signer: &MultisigSigner,
+    fee: Fee,
+    body: Body,
+    msgs: &[CosmosJsonSerializable],
+) -> eyre::Result<Tx> {
+    let sign_bytes = amino_sign_doc_bytes(
+        chain_info.id.as_str(),
+        signer.account_number,
+        signer.sequence,
+        &fee,
+        &body.memo,
+        msgs,
+    )
+    .wrap_err("failed to create amino JSON sign doc")?;
+
+    let mut signatures = Vec::with_capacity(signer.members.len());
+    for (index, member) in &signer.members {
+        debug!(index, "signing with multisig member key");
+        signatures.push(member.sign(&sign_bytes, WalletKeyType::Secp256k1).await?);
+    }
+
+    let auth_info = signer.signer_info().auth_info(fee);
+
+    Ok(Tx {
+        body,
+        auth_info,
+        signatures: vec![MultiSignature { signatures }.encode_to_vec()],
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use bech32::Hrp;
+    use bip32::secp256k1::ecdsa::{Signature, SigningKey, VerifyingKey, signature::Verifier};
+    use cosmrs::{
+        Coin,
+        crypto::{LegacyAminoMultisig, PublicKey},
+        proto::cosmos::{
+            crypto::multisig::v1beta1::{CompactBitArray, MultiSignature},
+            distribution::v1beta1::MsgSetWithdrawAddress,
+            tx::v1beta1::{ModeInfo, Tx, mode_info},
+        },
+        tx::{BodyBuilder, Fee, SignMode},
+    };
+    use pretty_assertions::assert_eq;
+    use prost::Message;
+
+    use super::{MultisigSigner, sign_multisig_transaction};
+    use crate::{
+        chain::{Bech32Prefixes, ChainInfo},
+        ser::{CosmosJsonSerializable, amino_sign_doc_bytes},
+    };
+
+    fn test_key(byte: u8) -> SigningKey {
+        SigningKey::from_slice(&[byte; 32]).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_sign_multisig_transaction() {
+        let keys = [test_key(1), test_key(2), test_key(3)];
+        let multisig = LegacyAminoMultisig {
+            threshold: 2,
+            public_keys: keys
+                .iter()
+                .map(|key| PublicKey::from(key.verifying_key()))
+                .collect(),
+        };
+
+        let chain_info = ChainInfo {
+            id: "osmosis-1".parse().unwrap(),
+            chain_supports_setting_withdrawal_address: true,
+            bech32: Bech32Prefixes {
+                account_prefix: Hrp::parse_unchecked("osmo"),
+                valoper_prefix: Hrp::parse_unchecked("osmovaloper"),
+            },
+        };
+        let msgs: Vec<CosmosJsonSerializable> = vec![
+            MsgSetWithdrawAddress {
+                delegator_address: "osmo1delegator".to_string(),
+                withdraw_address: "osmo1reward".to_string(),
+            }
+            .into(),
+        ];
+        let fee = Fee::from_amount_and_gas(
+            Coin {
+                denom: "uosmo".parse().unwrap(),
+                amount: 5000,
+            },
+            200_000_u64,
+        );
+        let body = || {
+            BodyBuilder::new()
+                .memo("multisig")
+                .msgs(msgs.iter().map(|msg| msg.to_any().unwrap()))
+                .finish()
+        };
+
+        // Not enough members
+        let err = MultisigSigner::new(multisig.clone(), vec![keys[0].clone()], 5, 3)
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "2-of-3 multisig requires 2 member keys, got 1"
+        );
+
+        // Not a member
+        assert!(
+            MultisigSigner::new(multisig.clone(), vec![keys[0].clone(), test_key(4)], 5, 3)
+                .is_err()
+        );
+
+        // Members passed out of order
+        let signer = MultisigSigner::new(
+            multisig.clone(),
+            vec![keys[2].clone(), keys[0].clone()],
+            5,
+            3,
+        )
+        .unwrap();
+        let tx = sign_multisig_transaction(&chain_info, &signer, fee.clone(), body(), &msgs)
+            .await
+            .unwrap();
+
+        let tx_proto = Tx::from(tx);
+        let signer_info = &tx_proto.auth_info.as_ref().unwrap().signer_infos[0];
+        assert_eq!(signer_info.sequence, 3);
+        assert_eq!(
+            signer_info.mode_info.clone().unwrap().sum.unwrap(),
+            mode_info::Sum::Multi(mode_info::Multi {
+                bitarray: Some(CompactBitArray {
+                    extra_bits_stored: 3,
+                    elems: vec![0b1010_0000],
+                }),
+                mode_infos: vec![
+                    ModeInfo {
+                        sum: Some(mode_info::Sum::Single(mode_info::Single {
+                            mode: SignMode::LegacyAminoJson.into(),
+                        })),
+                    };
+                    2
+                ],
+            })
+        );
+        assert_eq!(
+            LegacyAminoMultisig::try_from(signer_info.public_key.clone().unwrap()).unwrap(),
+            multisig
+        );
+
+        let sign_bytes = amino_sign_doc_bytes("osmosis-1", 5, 3, &fee, "multisig", &msgs).unwrap();
+        let multi_signature = MultiSignature::decode(tx_proto.signatures[0].as_slice()).unwrap();
+        assert_eq!(multi_signature.signatures.len(), 2);
+        for (signature, key) in multi_signature.signatures.iter().zip([&keys[0], &keys[2]]) {
+            VerifyingKey::from(key)
+                .verify(&sign_bytes, &Signature::from_slice(signature).unwrap())
+                .unwrap();
+        }
+    }
+}
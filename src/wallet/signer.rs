@@ -0,0 +1,455 @@
+use std::{future::Future, io::ErrorKind, pin::Pin, process::Stdio, time::Duration};
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use bip32::{
+    PrivateKey,
+    secp256k1::ecdsa::{
+        RecoveryId, Signature, SigningKey, VerifyingKey, signature::Verifier,
+        signature::hazmat::PrehashVerifier,
+    },
+};
+use eyre::{Context, ContextCompat, bail, eyre};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use sha3::Digest;
+use tokio::{io::AsyncWriteExt, process::Command};
+use tracing::debug;
+
+use super::WalletKeyType;
+
+/// Remote signers may wait for a manual approval, so be generous here
+const REMOTE_SIGNER_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub type SignFuture<'a> = Pin<Box<dyn Future<Output = eyre::Result<Vec<u8>>> + Send + 'a>>;
+
+/// Signs transaction sign bytes on behalf of an account. Backends other than [`LocalSigner`] keep
+/// the private key out of the withdrawer process.
+pub trait Signer: Send + Sync {
+    /// Public key of the signing key
+    fn public_key(&self) -> VerifyingKey;
+
+    /// Signs serialized `SignDoc`. secp256k1 keys produce a 64 byte `r || s` signature over SHA256 of
+    /// the bytes, eth_secp256k1 keys produce a 65 byte `r || s || v` signature over Keccak256 of the bytes.
+    fn sign<'a>(&'a self, sign_bytes: &'a [u8], key_type: WalletKeyType) -> SignFuture<'a>;
+}
+
+/// Signer holding the private key in process memory
+pub struct LocalSigner {
+    /// Zeroized when the signer is dropped, by `SigningKey`'s own `Drop`
+    key: SigningKey,
+}
+
+impl LocalSigner {
+    pub fn new(key: SigningKey) -> Self {
+        Self { key }
+    }
+}
+
+impl Signer for LocalSigner {
+    fn public_key(&self) -> VerifyingKey {
+        self.key.public_key()
+    }
+
+    fn sign<'a>(&'a self, sign_bytes: &'a [u8], key_type: WalletKeyType) -> SignFuture<'a> {
+        Box::pin(async move {
+            match key_type {
+                WalletKeyType::Secp256k1 => Ok(self
+                    .key
+                    .sign_recoverable(sign_bytes)
+                    .wrap_err("failed to sign SignDoc")?
+                    .0
+                    .to_vec()),
+                WalletKeyType::EthermintSecp256k1 { .. } => {
+                    let hash = sha3::Keccak256::digest(sign_bytes);
+                    let (signature, recovery_id) = self
+                        .key
+                        .sign_prehash_recoverable(&hash)
+                        .wrap_err("failed to sign SignDoc")?;
+
+                    let mut signature_bytes = signature.to_vec();
+                    signature_bytes.push(recovery_id.to_byte());
+                    Ok(signature_bytes)
+                }
+            }
+        })
+    }
+}
+
+/// Request sent to HTTP and command signers, as JSON
+#[derive(Debug, Serialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+enum SignerRequest {
+    PublicKey,
+    Sign {
+        key_type: &'static str,
+        /// Base64 encoded `SignDoc` bytes
+        sign_bytes: String,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+struct PublicKeyResponse {
+    /// Base64 encoded compressed secp256k1 public key
+    public_key: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SignResponse {
+    /// Base64 encoded signature
+    signature: String,
+}
+
+/// Signer delegating to an HTTP service. Requests are POSTed to the URL as JSON:
+///
+/// - `{"method":"public_key"}`, answered with `{"public_key":"<base64>"}`
+/// - `{"method":"sign","key_type":"secp256k1","sign_bytes":"<base64>"}`, answered with `{"signature":"<base64>"}`
+pub struct HttpSigner {
+    client: reqwest::Client,
+    url: String,
+    public_key: VerifyingKey,
+}
+
+impl HttpSigner {
+    /// Sets up the signer, fetching its public key
+    pub async fn connect(url: String) -> eyre::Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(REMOTE_SIGNER_TIMEOUT)
+            .build()
+            .wrap_err("failed to set up remote signer client")?;
+
+        let response: PublicKeyResponse =
+            http_request(&client, &url, &SignerRequest::PublicKey).await?;
+        let public_key = decode_public_key(&response.public_key)?;
+        debug!(url, "connected to remote signer");
+
+        Ok(Self {
+            client,
+            url,
+            public_key,
+        })
+    }
+}
+
+impl Signer for HttpSigner {
+    fn public_key(&self) -> VerifyingKey {
+        self.public_key
+    }
+
+    fn sign<'a>(&'a self, sign_bytes: &'a [u8], key_type: WalletKeyType) -> SignFuture<'a> {
+        Box::pin(async move {
+            let response: SignResponse =
+                http_request(&self.client, &self.url, &sign_request(sign_bytes, key_type)).await?;
+
+            decode_signature(&self.public_key, sign_bytes, key_type, &response.signature)
+                .wrap_err("remote signer returned an invalid signature")
+        })
+    }
+}
+
+async fn http_request<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    url: &str,
+    request: &SignerRequest,
+) -> eyre::Result<T> {
+    client
+        .post(url)
+        .json(request)
+        .send()
+        .await
+        .wrap_err("failed to send remote signer request")?
+        .error_for_status()
+        .wrap_err("remote signer responded with an error")?
+        .json()
+        .await
+        .wrap_err("failed to parse remote signer response")
+}
+
+/// Signer delegating to an external command, run with `sh -c` once per request. The request is
+/// written to its stdin and the response read from its stdout, using the same JSON messages as
+/// [`HttpSigner`].
+pub struct CommandSigner {
+    command: String,
+    public_key: VerifyingKey,
+}
+
+impl CommandSigner {
+    /// Sets up the signer, fetching its public key
+    pub async fn connect(command: String) -> eyre::Result<Self> {
+        let response: PublicKeyResponse = run_command(&command, &SignerRequest::PublicKey).await?;
+        let public_key = decode_public_key(&response.public_key)?;
+
+        Ok(Self {
+            command,
+            public_key,
+        })
+    }
+}
+
+impl Signer for CommandSigner {
+    fn public_key(&self) -> VerifyingKey {
+        self.public_key
+    }
+
+    fn sign<'a>(&'a self, sign_bytes: &'a [u8], key_type: WalletKeyType) -> SignFuture<'a> {
+        Box::pin(async move {
+            let response: SignResponse =
+                run_command(&self.command, &sign_request(sign_bytes, key_type)).await?;
+
+            decode_signature(&self.public_key, sign_bytes, key_type, &response.signature)
+                .wrap_err("signer command returned an invalid signature")
+        })
+    }
+}
+
+async fn run_command<T: DeserializeOwned>(
+    command: &str,
+    request: &SignerRequest,
+) -> eyre::Result<T> {
+    let mut input = serde_json::to_vec(request)?;
+    input.push(b'\n');
+
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .wrap_err("failed to start signer command")?;
+
+    let mut stdin = child.stdin.take().expect("stdin should be piped");
+    match stdin.write_all(&input).await {
+        // Command exited without reading the request, its exit status tells more
+        Err(err) if err.kind() == ErrorKind::BrokenPipe => {}
+        result => result.wrap_err("failed to write signer command input")?,
+    }
+    drop(stdin);
+
+    let output = child
+        .wait_with_output()
+        .await
+        .wrap_err("failed to run signer command")?;
+
+    if !output.status.success() {
+        bail!(
+            "signer command failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    serde_json::from_slice(&output.stdout).wrap_err("failed to parse signer command output")
+}
+
+fn sign_request(sign_bytes: &[u8], key_type: WalletKeyType) -> SignerRequest {
+    SignerRequest::Sign {
+        key_type: key_type.type_name(),
+        sign_bytes: BASE64_STANDARD.encode(sign_bytes),
+    }
+}
+
+fn decode_public_key(public_key: &str) -> eyre::Result<VerifyingKey> {
+    let bytes = BASE64_STANDARD
+        .decode(public_key)
+        .wrap_err("failed to decode signer public key")?;
+
+    VerifyingKey::from_sec1_bytes(&bytes).wrap_err("invalid secp256k1 signer public key")
+}
+
+/// Decodes signature returned by an external signer, and checks that it was made by the expected key
+fn decode_signature(
+    public_key: &VerifyingKey,
+    sign_bytes: &[u8],
+    key_type: WalletKeyType,
+    signature: &str,
+) -> eyre::Result<Vec<u8>> {
+    let mut bytes = BASE64_STANDARD
+        .decode(signature)
+        .wrap_err("failed to decode signature")?;
+
+    match key_type {
+        WalletKeyType::Secp256k1 => {
+            let signature =
+                Signature::from_slice(&bytes).wrap_err("expected 64 byte secp256k1 signature")?;
+            public_key
+                .verify(sign_bytes, &signature)
+                .map_err(|_| eyre!("signature does not match signer public key"))?;
+        }
+        WalletKeyType::EthermintSecp256k1 { .. } => {
+            if bytes.len() != 65 {
+                bail!("expected 65 byte eth_secp256k1 signature");
+            }
+
+            // Ethereum tooling commonly returns 27/28 for recovery id
+            if bytes[64] >= 27 {
+                bytes[64] -= 27;
+            }
+
+            let signature = Signature::from_slice(&bytes[..64]).wrap_err("invalid signature")?;
+            let recovery_id = RecoveryId::from_byte(bytes[64]).wrap_err("invalid recovery id")?;
+            let hash = sha3::Keccak256::digest(sign_bytes);
+
+            public_key
+                .verify_prehash(&hash, &signature)
+                .map_err(|_| eyre!("signature does not match signer public key"))?;
+            let recovered = VerifyingKey::recover_from_prehash(&hash, &signature, recovery_id)
+                .map_err(|_| eyre!("failed to recover public key from signature"))?;
+            if &recovered != public_key {
+                bail!("signature recovery id does not match signer public key");
+            }
+        }
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use base64::{Engine, prelude::BASE64_STANDARD};
+    use bip32::secp256k1::ecdsa::SigningKey;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use tokio::net::TcpListener;
+
+    use super::{CommandSigner, HttpSigner, LocalSigner, Signer};
+    use crate::{
+        test_util::{read_json_request, write_json_response},
+        wallet::WalletKeyType,
+    };
+
+    const SIGN_BYTES: &[u8] = b"\x0a\x04test\x12\x04auth\x1a\x09osmosis-1\x20\x01";
+
+    const KEY_TYPES: [WalletKeyType; 2] = [
+        WalletKeyType::Secp256k1,
+        WalletKeyType::EthermintSecp256k1 { injective: false },
+    ];
+
+    fn test_key(byte: u8) -> SigningKey {
+        SigningKey::from_slice(&[byte; 32]).unwrap()
+    }
+
+    fn encoded_public_key(key: &SigningKey) -> String {
+        BASE64_STANDARD.encode(key.verifying_key().to_sec1_bytes())
+    }
+
+    /// Stand-in remote signer, answering each request on a new connection
+    async fn serve_signer(listener: TcpListener, key: SigningKey) {
+        let signer = LocalSigner::new(key);
+
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let body = read_json_request(&mut stream).await;
+
+            let response = match body["method"].as_str().unwrap() {
+                "public_key" => json!({
+                    "public_key": BASE64_STANDARD.encode(signer.public_key().to_sec1_bytes()),
+                }),
+                "sign" => {
+                    let key_type = body["key_type"].as_str().unwrap().parse().unwrap();
+                    let sign_bytes = BASE64_STANDARD
+                        .decode(body["sign_bytes"].as_str().unwrap())
+                        .unwrap();
+                    let signature = signer.sign(&sign_bytes, key_type).await.unwrap();
+                    json!({ "signature": BASE64_STANDARD.encode(signature) })
+                }
+                method => panic!("unexpected method '{method}'"),
+            };
+
+            write_json_response(&mut stream, Some(&response)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http_signer() {
+        let key = test_key(7);
+        let local = LocalSigner::new(key.clone());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/sign", listener.local_addr().unwrap());
+        let server = tokio::spawn(serve_signer(listener, key));
+
+        let remote = HttpSigner::connect(url).await.unwrap();
+        assert_eq!(remote.public_key(), local.public_key());
+
+        for key_type in KEY_TYPES {
+            // RFC6979 signatures are deterministic
+            assert_eq!(
+                remote.sign(SIGN_BYTES, key_type).await.unwrap(),
+                local.sign(SIGN_BYTES, key_type).await.unwrap(),
+            );
+        }
+
+        server.abort();
+    }
+
+    /// Writes a stand-in signer script answering with canned responses of `key`
+    async fn signer_script(name: &str, key: &SigningKey, public_key: &str) -> std::path::PathBuf {
+        let local = LocalSigner::new(key.clone());
+        let mut signatures = Vec::new();
+        for key_type in KEY_TYPES {
+            let signature = local.sign(SIGN_BYTES, key_type).await.unwrap();
+            signatures.push(BASE64_STANDARD.encode(signature));
+        }
+
+        let script = format!(
+            r#"request=$(cat)
+case "$request" in
+  *'"method":"public_key"'*) echo '{{"public_key":"{public_key}"}}' ;;
+  *'"key_type":"secp256k1"'*) echo '{{"signature":"{}"}}' ;;
+  *'"key_type":"eth_secp256k1"'*) echo '{{"signature":"{}"}}' ;;
+  *) echo "unexpected request $request" >&2; exit 3 ;;
+esac
+"#,
+            signatures[0], signatures[1],
+        );
+
+        let path = std::env::temp_dir().join(format!(
+            "cosmos-withdrawer-signer-{name}-{}.sh",
+            std::process::id()
+        ));
+        std::fs::write(&path, script).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn test_command_signer() {
+        let key = test_key(7);
+        let local = LocalSigner::new(key.clone());
+
+        let path = signer_script("valid", &key, &encoded_public_key(&key)).await;
+        let signer = CommandSigner::connect(format!("sh {}", path.display()))
+            .await
+            .unwrap();
+        assert_eq!(signer.public_key(), local.public_key());
+        for key_type in KEY_TYPES {
+            assert_eq!(
+                signer.sign(SIGN_BYTES, key_type).await.unwrap(),
+                local.sign(SIGN_BYTES, key_type).await.unwrap(),
+            );
+        }
+        std::fs::remove_file(&path).unwrap();
+
+        // Signatures made by another key are rejected
+        let path = signer_script("mismatch", &test_key(8), &encoded_public_key(&key)).await;
+        let signer = CommandSigner::connect(format!("sh {}", path.display()))
+            .await
+            .unwrap();
+        for key_type in KEY_TYPES {
+            let err = signer.sign(SIGN_BYTES, key_type).await.unwrap_err();
+            assert_eq!(
+                format!("{:#}", err.root_cause()),
+                "signature does not match signer public key"
+            );
+        }
+        std::fs::remove_file(&path).unwrap();
+
+        let err = CommandSigner::connect("echo 'signer is locked' >&2; exit 1".to_string())
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "signer command failed with exit status: 1: signer is locked"
+        );
+    }
+}